@@ -0,0 +1,206 @@
+use super::filter::{unfilter_rows, FilterError};
+use super::ihdr::Ihdr;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Pass {
+    pub x_start: u32,
+    pub y_start: u32,
+    pub x_step: u32,
+    pub y_step: u32,
+    // size of the block a pixel of this pass stands in for in a preview
+    pub block_width: u32,
+    pub block_height: u32,
+}
+
+pub const PASSES: [Pass; 7] = [
+    Pass { x_start: 0, y_start: 0, x_step: 8, y_step: 8, block_width: 8, block_height: 8 },
+    Pass { x_start: 4, y_start: 0, x_step: 8, y_step: 8, block_width: 4, block_height: 8 },
+    Pass { x_start: 0, y_start: 4, x_step: 4, y_step: 8, block_width: 4, block_height: 4 },
+    Pass { x_start: 2, y_start: 0, x_step: 4, y_step: 4, block_width: 2, block_height: 4 },
+    Pass { x_start: 0, y_start: 2, x_step: 2, y_step: 4, block_width: 2, block_height: 2 },
+    Pass { x_start: 1, y_start: 0, x_step: 2, y_step: 2, block_width: 1, block_height: 2 },
+    Pass { x_start: 0, y_start: 1, x_step: 1, y_step: 2, block_width: 1, block_height: 1 },
+];
+
+impl Pass {
+    pub fn size(&self, width: u32, height: u32) -> (u32, u32) {
+        let along = |length: u32, start: u32, step: u32| match length > start {
+            true => (length - start).div_ceil(step),
+            false => 0,
+        };
+        (along(width, self.x_start, self.x_step), along(height, self.y_start, self.y_step))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PassImage {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u8>,
+}
+
+// splits the inflated stream into the seven reduced images, unfiltering
+// each one on its own; empty passes carry no data and no filter bytes
+pub fn split_passes(data: &[u8], ihdr: &Ihdr) -> Result<Vec<PassImage>, FilterError> {
+    let mut offset = 0usize;
+    let mut passes = Vec::with_capacity(PASSES.len());
+    for pass in PASSES.iter() {
+        let (width, height) = pass.size(ihdr.width, ihdr.height);
+        if width == 0 || height == 0 {
+            passes.push(PassImage { width, height, pixels: Vec::new() });
+            continue;
+        }
+        let row_bytes = ihdr.row_bytes(width);
+        let length = row_bytes.checked_add(1).and_then(|n| n.checked_mul(height as usize)).ok_or(FilterError::SizeOverflow)?;
+        let end = offset.checked_add(length).ok_or(FilterError::SizeOverflow)?;
+        let pass_data = data.get(offset..end).ok_or(FilterError::TruncatedData)?;
+        let pixels = unfilter_rows(pass_data, ihdr.filter_bpp(), row_bytes, height as usize)?;
+        passes.push(PassImage { width, height, pixels });
+        offset = end;
+    }
+    Ok(passes)
+}
+
+fn copy_pixel(src: &[u8], src_x: usize, dst: &mut [u8], dst_x: usize, bits_per_pixel: usize) {
+    if bits_per_pixel >= 8 {
+        let bytes = bits_per_pixel / 8;
+        dst[dst_x * bytes..(dst_x + 1) * bytes].copy_from_slice(&src[src_x * bytes..(src_x + 1) * bytes]);
+    } else {
+        let mask = (1u8 << bits_per_pixel) - 1;
+        let src_bit = src_x * bits_per_pixel;
+        let value = (src[src_bit / 8] >> (8 - bits_per_pixel - src_bit % 8)) & mask;
+        let dst_bit = dst_x * bits_per_pixel;
+        let shift = 8 - bits_per_pixel - dst_bit % 8;
+        dst[dst_bit / 8] = (dst[dst_bit / 8] & !(mask << shift)) | (value << shift);
+    }
+}
+
+// writes every pixel of `pass_image` into the full size `pixels`; with
+// `fill_blocks` each pixel also covers the rest of its block, which gives
+// the blocky preview of a partially received image
+fn scatter(pass: &Pass, pass_image: &PassImage, ihdr: &Ihdr, pixels: &mut [u8], fill_blocks: bool) {
+    let bits_per_pixel = ihdr.bits_per_pixel();
+    let src_row_bytes = ihdr.row_bytes(pass_image.width);
+    let dst_row_bytes = ihdr.row_bytes(ihdr.width);
+    let (block_width, block_height) = match fill_blocks {
+        true => (pass.block_width, pass.block_height),
+        false => (1, 1),
+    };
+    for py in 0..pass_image.height as usize {
+        let src = &pass_image.pixels[py * src_row_bytes..(py + 1) * src_row_bytes];
+        let y = pass.y_start + py as u32 * pass.y_step;
+        for px in 0..pass_image.width as usize {
+            let x = pass.x_start + px as u32 * pass.x_step;
+            for dst_y in y..(y + block_height).min(ihdr.height) {
+                let dst = &mut pixels[dst_y as usize * dst_row_bytes..(dst_y as usize + 1) * dst_row_bytes];
+                for dst_x in x..(x + block_width).min(ihdr.width) {
+                    copy_pixel(src, px, dst, dst_x as usize, bits_per_pixel);
+                }
+            }
+        }
+    }
+}
+
+// the full size buffer, only allocated once the passes have shown that the
+// data is really there
+fn full_image(ihdr: &Ihdr) -> Result<Vec<u8>, FilterError> {
+    let size = ihdr.row_bytes(ihdr.width).checked_mul(ihdr.height as usize).ok_or(FilterError::SizeOverflow)?;
+    Ok(vec![0; size])
+}
+
+pub fn deinterlace(data: &[u8], ihdr: &Ihdr) -> Result<Vec<u8>, FilterError> {
+    let passes = split_passes(data, ihdr)?;
+    let mut pixels = full_image(ihdr)?;
+    for (pass, pass_image) in PASSES.iter().zip(passes) {
+        scatter(pass, &pass_image, ihdr, &mut pixels, false);
+    }
+    Ok(pixels)
+}
+
+// full size pixel buffers as they look after each of the seven passes
+pub fn progressive_previews(data: &[u8], ihdr: &Ihdr) -> Result<Vec<Vec<u8>>, FilterError> {
+    let passes = split_passes(data, ihdr)?;
+    let mut pixels = full_image(ihdr)?;
+    let mut previews = Vec::with_capacity(PASSES.len());
+    for (pass, pass_image) in PASSES.iter().zip(passes) {
+        scatter(pass, &pass_image, ihdr, &mut pixels, true);
+        previews.push(pixels.clone());
+    }
+    Ok(previews)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ihdr::{ColorType, InterlaceMethod};
+
+    fn interlaced_ihdr(width: u32, height: u32, bit_depth: u8) -> Ihdr {
+        let mut ihdr = Ihdr::new(width, height, bit_depth, ColorType::Grayscale).unwrap();
+        ihdr.interlace_method = InterlaceMethod::Adam7;
+        ihdr
+    }
+
+    // 3x3 grayscale image where pixel (x, y) has the value 10 * y + x
+    #[rustfmt::skip]
+    const SMALL_PASSES: [u8; 15] = [
+        0, 0,           // pass 1: (0, 0)
+        0, 2,           // pass 4: (2, 0)
+        0, 20, 22,      // pass 5: (0, 2) (2, 2)
+        0, 1,           // pass 6: (1, 0)
+        0, 21,          //         (1, 2)
+        0, 10, 11, 12,  // pass 7: row 1
+    ];
+
+    #[test]
+    fn test_pass_sizes() {
+        let sizes: Vec<(u32, u32)> = PASSES.iter().map(|pass| pass.size(3, 3)).collect();
+        assert_eq!(sizes, vec![(1, 1), (0, 1), (1, 0), (1, 1), (2, 1), (1, 2), (3, 1)]);
+        let sizes: Vec<(u32, u32)> = PASSES.iter().map(|pass| pass.size(8, 8)).collect();
+        assert_eq!(sizes, vec![(1, 1), (1, 1), (2, 1), (2, 2), (4, 2), (4, 4), (8, 4)]);
+    }
+
+    #[test]
+    fn test_deinterlace() {
+        let pixels = deinterlace(&SMALL_PASSES, &interlaced_ihdr(3, 3, 8)).unwrap();
+        assert_eq!(pixels, vec![0, 1, 2, 10, 11, 12, 20, 21, 22]);
+    }
+
+    #[test]
+    fn test_deinterlace_truncated() {
+        let result = deinterlace(&SMALL_PASSES[..12], &interlaced_ihdr(3, 3, 8));
+        assert_eq!(result, Err(FilterError::TruncatedData));
+
+        // a huge header with almost no data fails before anything is allocated
+        let mut huge = Ihdr::new(0x7fff_ffff, 0x7fff_ffff, 16, ColorType::Rgba).unwrap();
+        huge.interlace_method = InterlaceMethod::Adam7;
+        assert_eq!(deinterlace(&[0; 4], &huge), Err(FilterError::TruncatedData));
+        assert_eq!(progressive_previews(&[0; 4], &huge), Err(FilterError::TruncatedData));
+    }
+
+    #[test]
+    fn test_deinterlace_sub_byte_pixels() {
+        // 3x3 one bit image, only pixel (1, 2) from pass 6 is set
+        #[rustfmt::skip]
+        let data = [
+            0, 0,
+            0, 0,
+            0, 0,
+            0, 0,
+            0, 0b1000_0000,
+            0, 0,
+        ];
+        let pixels = deinterlace(&data, &interlaced_ihdr(3, 3, 1)).unwrap();
+        assert_eq!(pixels, vec![0, 0, 0b0100_0000]);
+    }
+
+    #[test]
+    fn test_progressive_previews() {
+        let previews = progressive_previews(&SMALL_PASSES, &interlaced_ihdr(3, 3, 8)).unwrap();
+        assert_eq!(previews.len(), 7);
+        assert_eq!(previews[0], vec![0; 9]);
+        assert_eq!(previews[3], vec![0, 0, 2, 0, 0, 2, 0, 0, 2]);
+        assert_eq!(previews[4], vec![0, 0, 2, 0, 0, 2, 20, 20, 22]);
+        assert_eq!(previews[5], vec![0, 1, 2, 0, 1, 2, 20, 21, 22]);
+        assert_eq!(previews[6], vec![0, 1, 2, 10, 11, 12, 20, 21, 22]);
+    }
+}
@@ -0,0 +1,743 @@
+use std::fmt::Display;
+
+use super::chunk::{chunk, read_u16, read_u32, Chunk};
+use super::decoder::{self, DecodeError};
+use super::encoder::{self, EncodeOptions};
+use super::ihdr::{ColorType, Ihdr, IhdrError};
+use super::image::{Image, ImageError};
+use super::png::{ChunkPlacement, Png};
+
+// composite canvases are RGBA and held in memory, so a canvas bigger than
+// this is refused rather than allocated
+const MAX_CANVAS_LEN: usize = 1 << 28;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Actl {
+    pub num_frames: u32,
+    // 0 loops forever
+    pub num_plays: u32,
+}
+
+impl Actl {
+    pub fn to_chunk(self) -> Chunk {
+        let mut data = self.num_frames.to_be_bytes().to_vec();
+        data.extend_from_slice(&self.num_plays.to_be_bytes());
+        chunk("acTL", data)
+    }
+}
+
+impl TryFrom<&Chunk> for Actl {
+    type Error = ApngError;
+
+    fn try_from(chunk: &Chunk) -> Result<Self, Self::Error> {
+        if chunk.data().len() != 8 {
+            return Err(ApngError::InvalidLength("acTL"));
+        }
+        let actl = Actl { num_frames: read_u32(chunk.data(), 0), num_plays: read_u32(chunk.data(), 4) };
+        match actl.num_frames {
+            0 => Err(ApngError::NoFrames),
+            _ => Ok(actl),
+        }
+    }
+}
+
+// what happens to the frame region once the frame has been shown
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DisposeOp {
+    None,
+    Background,
+    Previous,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlendOp {
+    Source,
+    Over,
+}
+
+impl Display for DisposeOp {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DisposeOp::None => write!(f, "none"),
+            DisposeOp::Background => write!(f, "background"),
+            DisposeOp::Previous => write!(f, "previous"),
+        }
+    }
+}
+
+impl Display for BlendOp {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BlendOp::Source => write!(f, "source"),
+            BlendOp::Over => write!(f, "over"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Fctl {
+    pub sequence_number: u32,
+    pub width: u32,
+    pub height: u32,
+    pub x_offset: u32,
+    pub y_offset: u32,
+    pub delay_num: u16,
+    pub delay_den: u16,
+    pub dispose_op: DisposeOp,
+    pub blend_op: BlendOp,
+}
+
+impl Fctl {
+    // a zero denominator means hundredths of a second
+    pub fn delay(&self) -> f64 {
+        let den = if self.delay_den == 0 { 100 } else { self.delay_den };
+        self.delay_num as f64 / den as f64
+    }
+
+    pub fn to_chunk(self) -> Chunk {
+        let mut data = Vec::with_capacity(26);
+        for value in [self.sequence_number, self.width, self.height, self.x_offset, self.y_offset] {
+            data.extend_from_slice(&value.to_be_bytes());
+        }
+        data.extend_from_slice(&self.delay_num.to_be_bytes());
+        data.extend_from_slice(&self.delay_den.to_be_bytes());
+        data.push(self.dispose_op as u8);
+        data.push(self.blend_op as u8);
+        chunk("fcTL", data)
+    }
+
+    fn check_region(&self, ihdr: &Ihdr) -> Result<(), ApngError> {
+        let fits = |offset: u32, size: u32, canvas: u32| size > 0 && offset.checked_add(size).is_some_and(|end| end <= canvas);
+        match fits(self.x_offset, self.width, ihdr.width) && fits(self.y_offset, self.height, ihdr.height) {
+            true => Ok(()),
+            false => Err(ApngError::RegionOutOfBounds(self.sequence_number)),
+        }
+    }
+}
+
+impl TryFrom<&Chunk> for Fctl {
+    type Error = ApngError;
+
+    fn try_from(chunk: &Chunk) -> Result<Self, Self::Error> {
+        let data = chunk.data();
+        if data.len() != 26 {
+            return Err(ApngError::InvalidLength("fcTL"));
+        }
+        let dispose_op = match data[24] {
+            0 => DisposeOp::None,
+            1 => DisposeOp::Background,
+            2 => DisposeOp::Previous,
+            op => return Err(ApngError::InvalidDisposeOp(op)),
+        };
+        let blend_op = match data[25] {
+            0 => BlendOp::Source,
+            1 => BlendOp::Over,
+            op => return Err(ApngError::InvalidBlendOp(op)),
+        };
+        Ok(Fctl {
+            sequence_number: read_u32(data, 0),
+            width: read_u32(data, 4),
+            height: read_u32(data, 8),
+            x_offset: read_u32(data, 12),
+            y_offset: read_u32(data, 16),
+            delay_num: read_u16(data, 20),
+            delay_den: read_u16(data, 22),
+            dispose_op,
+            blend_op,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Frame {
+    pub control: Fctl,
+    // zlib stream of the frame, from IDAT for the default image or from
+    // the fdAT chunks with their sequence numbers stripped
+    pub data: Vec<u8>,
+    pub is_default_image: bool,
+}
+
+impl Frame {
+    pub fn decode(&self, png: &Png) -> Result<Image, ApngError> {
+        let mut ihdr = png.ihdr().map_err(ApngError::Ihdr)?;
+        ihdr.width = self.control.width;
+        ihdr.height = self.control.height;
+        decoder::decode_subimage(png, &ihdr, &self.data).map_err(ApngError::Decode)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Animation {
+    pub actl: Actl,
+    pub frames: Vec<Frame>,
+}
+
+impl Animation {
+    pub fn from_png(png: &Png) -> Result<Animation, ApngError> {
+        let actl = Actl::try_from(png.chunk_by_type("acTL").ok_or(ApngError::NotAnimated)?)?;
+        let ihdr = png.ihdr().map_err(ApngError::Ihdr)?;
+        let mut frames: Vec<Frame> = Vec::new();
+        let mut next_sequence = 0;
+        let mut seen_idat = false;
+        for chunk in png.chunks() {
+            match &chunk.chunk_type().bytes() {
+                b"fcTL" => {
+                    let control = Fctl::try_from(chunk)?;
+                    check_sequence(control.sequence_number, &mut next_sequence)?;
+                    control.check_region(&ihdr)?;
+                    if let Some(frame) = frames.last().filter(|frame| frame.data.is_empty()) {
+                        return Err(ApngError::MissingFrameData(frame.control.sequence_number));
+                    }
+                    frames.push(Frame { control, data: Vec::new(), is_default_image: !seen_idat });
+                }
+                b"IDAT" => {
+                    seen_idat = true;
+                    if let Some(frame) = frames.last_mut() {
+                        frame.data.extend_from_slice(chunk.data());
+                    }
+                }
+                b"fdAT" => {
+                    let data = chunk.data();
+                    if data.len() < 4 {
+                        return Err(ApngError::InvalidLength("fdAT"));
+                    }
+                    check_sequence(read_u32(data, 0), &mut next_sequence)?;
+                    match frames.last_mut() {
+                        Some(frame) if !frame.is_default_image => frame.data.extend_from_slice(&data[4..]),
+                        _ => return Err(ApngError::MisplacedFrameData(read_u32(data, 0))),
+                    }
+                }
+                _ => (),
+            }
+        }
+        if let Some(frame) = frames.last().filter(|frame| frame.data.is_empty()) {
+            return Err(ApngError::MissingFrameData(frame.control.sequence_number));
+        }
+        if let Some(first) = frames.first().filter(|frame| frame.is_default_image) {
+            let control = first.control;
+            if control.x_offset != 0 || control.y_offset != 0 || control.width != ihdr.width || control.height != ihdr.height {
+                return Err(ApngError::InvalidDefaultFrame);
+            }
+        }
+        if frames.len() != actl.num_frames as usize {
+            return Err(ApngError::FrameCountMismatch { declared: actl.num_frames, found: frames.len() });
+        }
+        Ok(Animation { actl, frames })
+    }
+
+    // the default image is shown by decoders without APNG support, and may
+    // or may not be the first frame of the animation
+    pub fn includes_default_image(&self) -> bool {
+        self.frames.first().is_some_and(|frame| frame.is_default_image)
+    }
+
+    pub fn duration(&self) -> f64 {
+        self.frames.iter().map(|frame| frame.control.delay()).sum()
+    }
+
+    // every frame as a full RGBA canvas, with blending and disposal applied
+    // in 8 bits per channel; canvases are made one at a time as the iterator
+    // is advanced, so only the current one is in memory
+    pub fn composite<'a>(&'a self, png: &'a Png) -> Result<Composite<'a>, ApngError> {
+        let ihdr = png.ihdr().map_err(ApngError::Ihdr)?;
+        let canvas_len = (ihdr.width as usize)
+            .checked_mul(ihdr.height as usize)
+            .and_then(|pixels| pixels.checked_mul(4))
+            .filter(|&len| len <= MAX_CANVAS_LEN)
+            .ok_or(ApngError::CanvasTooLarge(MAX_CANVAS_LEN))?;
+        Ok(Composite {
+            frames: self.frames.iter().enumerate(),
+            png,
+            width: ihdr.width,
+            height: ihdr.height,
+            canvas: vec![0u8; canvas_len],
+            failed: false,
+        })
+    }
+}
+
+pub struct Composite<'a> {
+    frames: std::iter::Enumerate<std::slice::Iter<'a, Frame>>,
+    png: &'a Png,
+    width: u32,
+    height: u32,
+    canvas: Vec<u8>,
+    failed: bool,
+}
+
+impl Composite<'_> {
+    fn next_canvas(&mut self, index: usize, frame: &Frame) -> Result<Image, ApngError> {
+        let control = frame.control;
+        let pixels = frame.decode(self.png)?.to_rgba8().map_err(ApngError::Image)?;
+        let canvas_width = self.width as usize;
+        let (x, y, width) = (control.x_offset as usize, control.y_offset as usize, control.width as usize);
+        let region = |row: usize| {
+            let start = ((y + row) * canvas_width + x) * 4;
+            start..start + width * 4
+        };
+        let canvas = &mut self.canvas;
+        // the first frame has nothing to go back to
+        let dispose_op = match (index, control.dispose_op) {
+            (0, DisposeOp::Previous) => DisposeOp::Background,
+            (_, op) => op,
+        };
+        let saved: Vec<Vec<u8>> = match dispose_op {
+            DisposeOp::Previous => (0..control.height as usize).map(|row| canvas[region(row)].to_vec()).collect(),
+            _ => Vec::new(),
+        };
+        for (row, source) in pixels.chunks_exact(width * 4).enumerate() {
+            let target = &mut canvas[region(row)];
+            match control.blend_op {
+                BlendOp::Source => target.copy_from_slice(source),
+                BlendOp::Over => {
+                    for (dst, src) in target.chunks_exact_mut(4).zip(source.chunks_exact(4)) {
+                        blend_over(dst, src);
+                    }
+                }
+            }
+        }
+        let image = Image::new(self.width, self.height, ColorType::Rgba, 8, canvas.clone());
+        match dispose_op {
+            DisposeOp::None => (),
+            DisposeOp::Background => (0..control.height as usize).for_each(|row| canvas[region(row)].fill(0)),
+            DisposeOp::Previous => {
+                for (row, pixels) in saved.iter().enumerate() {
+                    canvas[region(row)].copy_from_slice(pixels);
+                }
+            }
+        }
+        Ok(image)
+    }
+}
+
+impl Iterator for Composite<'_> {
+    type Item = Result<Image, ApngError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        // later frames build on a canvas that couldn't be finished
+        if self.failed {
+            return None;
+        }
+        let (index, frame) = self.frames.next()?;
+        let canvas = self.next_canvas(index, frame);
+        self.failed = canvas.is_err();
+        Some(canvas)
+    }
+}
+
+struct BuilderFrame {
+    image: Image,
+    delay_num: u16,
+    delay_den: u16,
+}
+
+// the first frame doubles as the default image, so decoders without APNG
+// support show it as a still
+pub struct AnimationBuilder {
+    frames: Vec<BuilderFrame>,
+    num_plays: u32,
+    crop: bool,
+    options: EncodeOptions,
+}
+
+impl Default for AnimationBuilder {
+    fn default() -> Self {
+        AnimationBuilder::new()
+    }
+}
+
+impl AnimationBuilder {
+    pub fn new() -> AnimationBuilder {
+        AnimationBuilder { frames: Vec::new(), num_plays: 0, crop: false, options: EncodeOptions::default() }
+    }
+
+    pub fn num_plays(mut self, num_plays: u32) -> AnimationBuilder {
+        self.num_plays = num_plays;
+        self
+    }
+
+    // store only the bounding box of the pixels that changed since the
+    // previous frame
+    pub fn crop_to_changes(mut self, crop: bool) -> AnimationBuilder {
+        self.crop = crop;
+        self
+    }
+
+    pub fn encode_options(mut self, options: EncodeOptions) -> AnimationBuilder {
+        self.options = options;
+        self
+    }
+
+    // frames must all share the size and pixel format of the first one
+    pub fn frame(mut self, image: Image, delay_num: u16, delay_den: u16) -> AnimationBuilder {
+        self.frames.push(BuilderFrame { image, delay_num, delay_den });
+        self
+    }
+
+    pub fn png_frame(self, png: &Png, delay_num: u16, delay_den: u16) -> Result<AnimationBuilder, ApngError> {
+        let image = png.decode_image().map_err(ApngError::Decode)?;
+        Ok(self.frame(image, delay_num, delay_den))
+    }
+
+    pub fn build(&self) -> Result<Png, ApngError> {
+        let first = &self.frames.first().ok_or(ApngError::NoFrames)?.image;
+        for (index, frame) in self.frames.iter().enumerate() {
+            let image = &frame.image;
+            if (image.width, image.height) != (first.width, first.height) {
+                return Err(ApngError::FrameSizeMismatch(index));
+            }
+            let format = (image.color_type, image.bit_depth, &image.palette, &image.transparency);
+            if format != (first.color_type, first.bit_depth, &first.palette, &first.transparency) {
+                return Err(ApngError::FrameFormatMismatch(index));
+            }
+        }
+        let mut png = encoder::encode(first, &self.options).map_err(|err| ApngError::Encode(format!("{:?}", err)))?;
+        let ihdr = png.ihdr().map_err(ApngError::Ihdr)?;
+        let insert = |png: &mut Png, chunk: Chunk, placement: ChunkPlacement| {
+            png.insert_chunk(chunk, placement).map(|_| ()).map_err(|err| ApngError::Png(format!("{:?}", err)))
+        };
+        let actl = Actl { num_frames: self.frames.len() as u32, num_plays: self.num_plays };
+        insert(&mut png, actl.to_chunk(), ChunkPlacement::BeforeFirstIdat)?;
+
+        let mut sequence_number = 0;
+        let mut previous: Option<&Image> = None;
+        for frame in &self.frames {
+            let (x_offset, y_offset, width, height) = match previous.filter(|_| self.crop) {
+                Some(previous) => changed_region(previous, &frame.image),
+                None => (0, 0, ihdr.width, ihdr.height),
+            };
+            let control = Fctl {
+                sequence_number,
+                width,
+                height,
+                x_offset,
+                y_offset,
+                delay_num: frame.delay_num,
+                delay_den: frame.delay_den,
+                dispose_op: DisposeOp::None,
+                blend_op: BlendOp::Source,
+            };
+            sequence_number += 1;
+            if previous.is_none() {
+                insert(&mut png, control.to_chunk(), ChunkPlacement::BeforeFirstIdat)?;
+                previous = Some(&frame.image);
+                continue;
+            }
+            insert(&mut png, control.to_chunk(), ChunkPlacement::BeforeIend)?;
+            let region = frame.image.crop(x_offset, y_offset, width, height);
+            let region_ihdr = Ihdr { width, height, ..ihdr.clone() };
+            for idat in encoder::idat_chunks(&region, &region_ihdr, &self.options) {
+                let mut data = sequence_number.to_be_bytes().to_vec();
+                data.extend_from_slice(idat.data());
+                insert(&mut png, chunk("fdAT", data), ChunkPlacement::BeforeIend)?;
+                sequence_number += 1;
+            }
+            previous = Some(&frame.image);
+        }
+        Ok(png)
+    }
+}
+
+// bounding box of the differing pixels as (x, y, width, height); identical
+// frames still need a non empty region
+fn changed_region(previous: &Image, current: &Image) -> (u32, u32, u32, u32) {
+    let channels = current.color_type.channels();
+    let mut bounds: Option<(u32, u32, u32, u32)> = None;
+    for y in 0..current.height {
+        let (before, after) = (previous.row_samples(y), current.row_samples(y));
+        let changed = before.chunks_exact(channels).zip(after.chunks_exact(channels)).enumerate()
+            .filter(|(_, (a, b))| a != b)
+            .map(|(x, _)| x as u32);
+        for x in changed {
+            bounds = Some(match bounds {
+                Some((left, top, right, _)) => (left.min(x), top, right.max(x), y),
+                None => (x, y, x, y),
+            });
+        }
+    }
+    match bounds {
+        Some((left, top, right, bottom)) => (left, top, right - left + 1, bottom - top + 1),
+        None => (0, 0, 1, 1),
+    }
+}
+
+fn check_sequence(found: u32, next: &mut u32) -> Result<(), ApngError> {
+    if found != *next {
+        return Err(ApngError::SequenceMismatch { expected: *next, found });
+    }
+    *next += 1;
+    Ok(())
+}
+
+// non premultiplied "over" operator
+fn blend_over(dst: &mut [u8], src: &[u8]) {
+    let src_alpha = src[3] as u32;
+    match src_alpha {
+        0 => (),
+        255 => dst.copy_from_slice(src),
+        _ => {
+            let dst_alpha = dst[3] as u32 * (255 - src_alpha) / 255;
+            let out_alpha = src_alpha + dst_alpha;
+            for channel in 0..3 {
+                dst[channel] = ((src[channel] as u32 * src_alpha + dst[channel] as u32 * dst_alpha) / out_alpha) as u8;
+            }
+            dst[3] = out_alpha as u8;
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum ApngError {
+    NotAnimated,
+    NoFrames,
+    InvalidLength(&'static str),
+    InvalidDisposeOp(u8),
+    InvalidBlendOp(u8),
+    SequenceMismatch { expected: u32, found: u32 },
+    RegionOutOfBounds(u32),
+    MissingFrameData(u32),
+    MisplacedFrameData(u32),
+    InvalidDefaultFrame,
+    FrameCountMismatch { declared: u32, found: usize },
+    FrameSizeMismatch(usize),
+    FrameFormatMismatch(usize),
+    CanvasTooLarge(usize),
+    Encode(String),
+    Png(String),
+    Ihdr(IhdrError),
+    Decode(DecodeError),
+    Image(ImageError),
+}
+
+impl Display for ApngError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ApngError::NotAnimated => write!(f, "not an animated PNG, there's no acTL chunk"),
+            ApngError::NoFrames => write!(f, "acTL declares zero frames"),
+            ApngError::InvalidLength(chunk_type) => write!(f, "{} chunk has an invalid length", chunk_type),
+            ApngError::InvalidDisposeOp(op) => write!(f, "unknown dispose op {}", op),
+            ApngError::InvalidBlendOp(op) => write!(f, "unknown blend op {}", op),
+            ApngError::SequenceMismatch { expected, found } => {
+                write!(f, "expected sequence number {}, found {}", expected, found)
+            }
+            ApngError::RegionOutOfBounds(sequence) => {
+                write!(f, "frame region of fcTL {} is empty or outside the canvas", sequence)
+            }
+            ApngError::MissingFrameData(sequence) => write!(f, "fcTL {} has no image data", sequence),
+            ApngError::MisplacedFrameData(sequence) => write!(f, "fdAT {} doesn't follow an fcTL", sequence),
+            ApngError::InvalidDefaultFrame => write!(f, "a default image frame must cover the whole canvas"),
+            ApngError::FrameCountMismatch { declared, found } => {
+                write!(f, "acTL declares {} frames but {} were found", declared, found)
+            }
+            ApngError::FrameSizeMismatch(index) => write!(f, "frame {} isn't the size of the first frame", index),
+            ApngError::FrameFormatMismatch(index) => {
+                write!(f, "frame {} doesn't share the color type, bit depth and palette of the first frame", index)
+            }
+            ApngError::CanvasTooLarge(limit) => write!(f, "the canvas takes more than {} bytes", limit),
+            ApngError::Encode(reason) => write!(f, "can't encode frame: {}", reason),
+            ApngError::Png(reason) => write!(f, "{}", reason),
+            ApngError::Ihdr(err) => write!(f, "invalid IHDR: {:?}", err),
+            ApngError::Decode(err) => write!(f, "can't decode frame: {:?}", err),
+            ApngError::Image(err) => write!(f, "can't convert frame: {:?}", err),
+        }
+    }
+}
+
+impl std::error::Error for ApngError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::encoder::{idat_chunks, EncodeOptions};
+
+    const RED: [u8; 4] = [255, 0, 0, 255];
+    const BLUE: [u8; 4] = [0, 0, 255, 255];
+    const HALF_GREEN: [u8; 4] = [0, 255, 0, 128];
+
+    fn solid(width: u32, height: u32, color: [u8; 4]) -> Image {
+        Image::new(width, height, ColorType::Rgba, 8, color.repeat((width * height) as usize))
+    }
+
+    fn compressed(image: &Image) -> Vec<u8> {
+        let ihdr = Ihdr::new(image.width, image.height, 8, ColorType::Rgba).unwrap();
+        let options = EncodeOptions { idat_size: usize::MAX, ..EncodeOptions::default() };
+        idat_chunks(image, &ihdr, &options).remove(0).data().to_vec()
+    }
+
+    fn fctl(sequence_number: u32, region: (u32, u32, u32, u32), dispose_op: DisposeOp, blend_op: BlendOp) -> Fctl {
+        let (x_offset, y_offset, width, height) = region;
+        Fctl { sequence_number, width, height, x_offset, y_offset, delay_num: 1, delay_den: 10, dispose_op, blend_op }
+    }
+
+    fn fdat(sequence_number: u32, data: &[u8]) -> Chunk {
+        let mut bytes = sequence_number.to_be_bytes().to_vec();
+        bytes.extend_from_slice(data);
+        chunk("fdAT", bytes)
+    }
+
+    // a 4x4 red default image, then a 2x2 half transparent green square
+    // blended over it and disposed to the background, then a blue pixel
+    fn animation_chunks() -> Vec<Chunk> {
+        let background = solid(4, 4, RED);
+        vec![
+            Ihdr::new(4, 4, 8, ColorType::Rgba).unwrap().to_chunk(),
+            Actl { num_frames: 3, num_plays: 0 }.to_chunk(),
+            fctl(0, (0, 0, 4, 4), DisposeOp::None, BlendOp::Source).to_chunk(),
+            chunk("IDAT", compressed(&background)),
+            fctl(1, (1, 1, 2, 2), DisposeOp::Background, BlendOp::Over).to_chunk(),
+            fdat(2, &compressed(&solid(2, 2, HALF_GREEN))),
+            fctl(3, (3, 3, 1, 1), DisposeOp::Previous, BlendOp::Source).to_chunk(),
+            fdat(4, &compressed(&solid(1, 1, BLUE))),
+            chunk("IEND", Vec::new()),
+        ]
+    }
+
+    fn animation_png() -> Png {
+        Png::from_chunks(animation_chunks()).unwrap()
+    }
+
+    fn pixel(image: &Image, x: usize, y: usize) -> &[u8] {
+        let at = (y * image.width as usize + x) * 4;
+        &image.pixels[at..at + 4]
+    }
+
+    #[test]
+    fn test_control_chunks_round_trip() {
+        let control = fctl(7, (1, 2, 3, 4), DisposeOp::Previous, BlendOp::Over);
+        assert_eq!(Fctl::try_from(&control.to_chunk()), Ok(control));
+        assert_eq!(control.delay(), 0.1);
+        let actl = Actl { num_frames: 2, num_plays: 3 };
+        assert_eq!(Actl::try_from(&actl.to_chunk()), Ok(actl));
+        assert_eq!(Actl::try_from(&Actl { num_frames: 0, num_plays: 0 }.to_chunk()), Err(ApngError::NoFrames));
+
+        let mut data = control.to_chunk().data().to_vec();
+        data[24] = 3;
+        assert_eq!(Fctl::try_from(&chunk("fcTL", data)), Err(ApngError::InvalidDisposeOp(3)));
+    }
+
+    #[test]
+    fn test_read_animation() {
+        let png = animation_png();
+        let animation = Animation::from_png(&png).unwrap();
+        assert_eq!(animation.frames.len(), 3);
+        assert!(animation.includes_default_image());
+        assert!((animation.duration() - 0.3).abs() < 1e-9);
+        assert_eq!(animation.frames[1].control.x_offset, 1);
+        assert_eq!(animation.frames[2].decode(&png).unwrap().pixels, BLUE.to_vec());
+    }
+
+    #[test]
+    fn test_composite() {
+        let png = animation_png();
+        let canvases: Vec<Image> = Animation::from_png(&png).unwrap().composite(&png).unwrap().collect::<Result<_, _>>().unwrap();
+        assert_eq!(canvases.len(), 3);
+        assert!(canvases[0].pixels.chunks(4).all(|p| p == RED));
+        assert_eq!(pixel(&canvases[1], 0, 0), RED);
+        assert_eq!(pixel(&canvases[1], 1, 1), [127, 128, 0, 255]);
+        // frame 1 was disposed to transparent black before frame 2
+        assert_eq!(pixel(&canvases[2], 1, 1), [0, 0, 0, 0]);
+        assert_eq!(pixel(&canvases[2], 3, 3), BLUE);
+        assert_eq!(pixel(&canvases[2], 0, 0), RED);
+
+        // the canvas size comes from IHDR and is checked before allocating
+        let mut png = animation_png();
+        let animation = Animation::from_png(&png).unwrap();
+        png.replace_chunk(0, Ihdr::new(0x7fff_ffff, 0x7fff_ffff, 8, ColorType::Rgba).unwrap().to_chunk()).unwrap();
+        assert_eq!(animation.composite(&png).err(), Some(ApngError::CanvasTooLarge(MAX_CANVAS_LEN)));
+    }
+
+    #[test]
+    fn test_sequence_errors() {
+        let mut chunks = animation_chunks();
+        chunks[5] = fdat(5, &compressed(&solid(2, 2, HALF_GREEN)));
+        let png = Png::from_chunks_unchecked(chunks);
+        assert_eq!(Animation::from_png(&png), Err(ApngError::SequenceMismatch { expected: 2, found: 5 }));
+
+        let mut chunks = animation_chunks();
+        chunks.remove(7);
+        let png = Png::from_chunks_unchecked(chunks);
+        assert_eq!(Animation::from_png(&png), Err(ApngError::MissingFrameData(3)));
+
+        let mut chunks = animation_chunks();
+        chunks[1] = Actl { num_frames: 4, num_plays: 0 }.to_chunk();
+        let png = Png::from_chunks_unchecked(chunks);
+        assert_eq!(Animation::from_png(&png), Err(ApngError::FrameCountMismatch { declared: 4, found: 3 }));
+    }
+
+    #[test]
+    fn test_invalid_regions() {
+        let mut chunks = animation_chunks();
+        chunks[6] = fctl(3, (3, 3, 2, 1), DisposeOp::None, BlendOp::Source).to_chunk();
+        let png = Png::from_chunks_unchecked(chunks);
+        assert_eq!(Animation::from_png(&png), Err(ApngError::RegionOutOfBounds(3)));
+
+        let mut chunks = animation_chunks();
+        chunks[2] = fctl(0, (0, 0, 3, 4), DisposeOp::None, BlendOp::Source).to_chunk();
+        let png = Png::from_chunks_unchecked(chunks);
+        assert_eq!(Animation::from_png(&png), Err(ApngError::InvalidDefaultFrame));
+    }
+
+    fn gradient(width: u32, height: u32, shift: u8) -> Image {
+        let pixels = (0..width * height * 4).map(|i| (i as u8).wrapping_mul(3).wrapping_add(shift)).collect();
+        Image::new(width, height, ColorType::Rgba, 8, pixels)
+    }
+
+    #[test]
+    fn test_build_round_trip() {
+        let frames = [gradient(5, 4, 0), gradient(5, 4, 50), solid(5, 4, BLUE)];
+        let options = EncodeOptions { idat_size: 16, ..EncodeOptions::default() };
+        let mut builder = AnimationBuilder::new().num_plays(3).encode_options(options);
+        for frame in &frames {
+            builder = builder.frame(frame.clone(), 1, 25);
+        }
+        let png = builder.build().unwrap();
+        assert!(png.ordering_violations().is_empty());
+        let animation = png.animation().unwrap();
+        assert_eq!(animation.actl, Actl { num_frames: 3, num_plays: 3 });
+        assert!(animation.includes_default_image());
+        assert_eq!(animation.frames[2].control.delay(), 0.04);
+        assert_eq!(png.decode_image().unwrap(), frames[0]);
+        for (canvas, frame) in animation.composite(&png).unwrap().zip(&frames) {
+            assert_eq!(&canvas.unwrap(), frame);
+        }
+    }
+
+    #[test]
+    fn test_build_crops_to_changes() {
+        let first = solid(6, 6, RED);
+        let mut second = first.clone();
+        for (x, y) in [(1, 2), (3, 4)] {
+            second.pixels[(y * 6 + x) * 4..(y * 6 + x) * 4 + 4].copy_from_slice(&BLUE);
+        }
+        let png = AnimationBuilder::new()
+            .crop_to_changes(true)
+            .frame(first.clone(), 1, 10)
+            .frame(second.clone(), 1, 10)
+            .frame(second.clone(), 1, 10)
+            .build()
+            .unwrap();
+        let animation = png.animation().unwrap();
+        let regions: Vec<_> = animation.frames.iter()
+            .map(|frame| (frame.control.x_offset, frame.control.y_offset, frame.control.width, frame.control.height))
+            .collect();
+        assert_eq!(regions, vec![(0, 0, 6, 6), (1, 2, 3, 3), (0, 0, 1, 1)]);
+        let canvases: Vec<Image> = animation.composite(&png).unwrap().collect::<Result<_, _>>().unwrap();
+        assert_eq!(canvases, vec![first, second.clone(), second]);
+    }
+
+    #[test]
+    fn test_build_rejects_mismatched_frames() {
+        assert_eq!(AnimationBuilder::new().build().err(), Some(ApngError::NoFrames));
+        let builder = AnimationBuilder::new().frame(solid(2, 2, RED), 1, 1).frame(solid(2, 3, RED), 1, 1);
+        assert_eq!(builder.build().err(), Some(ApngError::FrameSizeMismatch(1)));
+        let gray = Image::new(2, 2, ColorType::Grayscale, 8, vec![0; 4]);
+        let builder = AnimationBuilder::new().frame(solid(2, 2, RED), 1, 1).frame(gray, 1, 1);
+        assert_eq!(builder.build().err(), Some(ApngError::FrameFormatMismatch(1)));
+    }
+
+    #[test]
+    fn test_not_animated() {
+        let png = Png::from_image(&solid(1, 1, RED), &EncodeOptions::default()).unwrap();
+        assert_eq!(Animation::from_png(&png), Err(ApngError::NotAnimated));
+    }
+}
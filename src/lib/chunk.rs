@@ -1,10 +1,35 @@
 use super::chunk_type::ChunkType;
+use super::reader::SIGNATURE;
 extern crate crc;
 use crc::{Crc, CRC_32_ISO_HDLC};
 
 use std::fmt::Display;
+use std::str::FromStr;
 use std::string::FromUtf8Error;
 
+static CRC: Crc<u32> = Crc::<u32>::new(&CRC_32_ISO_HDLC);
+
+fn checksum(chunk_type: &ChunkType, data: &[u8]) -> u32 {
+    let mut digest = CRC.digest();
+    digest.update(&chunk_type.bytes());
+    digest.update(data);
+    digest.finalize()
+}
+
+// a chunk of one of the types this crate writes itself, which are all valid
+pub(crate) fn chunk(chunk_type: &str, data: Vec<u8>) -> Chunk {
+    Chunk::new(ChunkType::from_str(chunk_type).expect("a known chunk type"), data)
+}
+
+// big endian fields, the caller has checked the length
+pub(crate) fn read_u32(data: &[u8], at: usize) -> u32 {
+    u32::from_be_bytes([data[at], data[at + 1], data[at + 2], data[at + 3]])
+}
+
+pub(crate) fn read_u16(data: &[u8], at: usize) -> u16 {
+    u16::from_be_bytes([data[at], data[at + 1]])
+}
+
 pub struct Chunk {
     chunk_type: ChunkType,
     chunk_data: Vec<u8>,
@@ -13,15 +38,7 @@ pub struct Chunk {
 
 impl Chunk {
     pub fn new(chunk_type: ChunkType, data: Vec<u8>) -> Chunk {
-        let mut ip_for_checksum = Vec::new();
-        for chunk_type_byte in chunk_type.bytes() {
-            ip_for_checksum.push(chunk_type_byte);
-        }
-        for ele in data.iter() {
-            ip_for_checksum.push(*ele);
-        }
-        let crc = Crc::<u32>::new(&CRC_32_ISO_HDLC);
-        let checksum = crc.checksum(&ip_for_checksum[..]);
+        let checksum = checksum(&chunk_type, &data);
         Self {
             chunk_type,
             chunk_data: data,
@@ -45,6 +62,10 @@ impl Chunk {
         self.chunk_checksum
     }
 
+    pub(crate) fn is_type(&self, chunk_type: &str) -> bool {
+        self.chunk_type.bytes() == chunk_type.as_bytes()
+    }
+
     pub fn data_as_string(&self) -> Result<String, FromUtf8Error> {
         let res = String::from_utf8(self.chunk_data.clone())?;
         Ok(res)
@@ -78,52 +99,103 @@ impl TryFrom<&Vec<u8>> for Chunk {
     type Error = ChunkError;
     
     fn try_from(byte_vec: &Vec<u8>) -> Result<Self, Self::Error> {        
-        match byte_vec.len() < 12 {
-            true => Err(ChunkError::InvalidInput),
-            false => {
-                let length_arr = &byte_vec[..4];
-                let data_len = u32::from_be_bytes([
-                    length_arr[0], length_arr[1], length_arr[2], length_arr[3]
-                    ]);
-                match byte_vec.len() < (data_len + 12) as usize {
-                    true => Err(ChunkError::InvalidLength),
-                    false => {
-                        let ip_len = byte_vec.len();
-                        let mut data_vec = Vec::new();
-                        for i in 0..(ip_len-12) {
-                            data_vec.push(byte_vec[8+i]);
-                        }
-                        let chunk_type_arr = [byte_vec[4], byte_vec[5], byte_vec[6], byte_vec[7]];
-                        let chunk_type = ChunkType::try_from(chunk_type_arr).unwrap();
-                        
-                        let mut crc_vec = Vec::new();
-                        for i in 0..4 {
-                            crc_vec.push(byte_vec[ip_len-4+i]);
-                        }
-                        let crc = u32::from_be_bytes([
-                            crc_vec[0], crc_vec[1], crc_vec[2], crc_vec[3]
-                        ]);
-                        
-                        // calculate checksum to validate the data
-                        let ip_expected_crc = Crc::<u32>::new(&CRC_32_ISO_HDLC);
-                        let ip_expected_checksum = ip_expected_crc.checksum(&byte_vec[4..(ip_len-4)]);
-
-                        match crc == ip_expected_checksum {
-                            true => Ok(Self {
-                                chunk_type,
-                                chunk_data: data_vec,
-                                chunk_checksum: crc
-                            }),
-                            false => Err(ChunkError::InvalidCrcReceived),
-                        }
-                    }
-                }
+        match ChunkRef::parse(byte_vec)? {
+            (chunk, []) => Ok(chunk.to_chunk()),
+            _ => Err(ChunkError::InvalidLength),
+        }
+    }
+}
+
+// a chunk borrowed from a larger buffer, such as a whole file or a memory
+// map; parsing one allocates nothing
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChunkRef<'a> {
+    chunk_type: ChunkType,
+    data: &'a [u8],
+    crc: u32,
+}
+
+impl<'a> ChunkRef<'a> {
+    // the chunk at the start of `bytes`, and the bytes that follow it
+    pub fn parse(bytes: &'a [u8]) -> Result<(ChunkRef<'a>, &'a [u8]), ChunkError> {
+        if bytes.len() < 12 {
+            return Err(ChunkError::InvalidInput);
+        }
+        let length = u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as usize;
+        if bytes.len() - 12 < length {
+            return Err(ChunkError::InvalidLength);
+        }
+        // ChunkType takes any four bytes, the PNG spec only letters
+        if !bytes[4..8].iter().all(u8::is_ascii_alphabetic) {
+            return Err(ChunkError::InvalidInput);
+        }
+        let chunk_type = ChunkType::try_from([bytes[4], bytes[5], bytes[6], bytes[7]]).unwrap();
+        let (data, rest) = bytes[8..].split_at(length);
+        let crc = u32::from_be_bytes([rest[0], rest[1], rest[2], rest[3]]);
+        if checksum(&chunk_type, data) != crc {
+            return Err(ChunkError::InvalidCrcReceived);
+        }
+        Ok((ChunkRef { chunk_type, data, crc }, &rest[4..]))
+    }
+
+    pub fn length(&self) -> u32 {
+        self.data.len() as u32
+    }
+
+    pub fn chunk_type(&self) -> &ChunkType {
+        &self.chunk_type
+    }
+
+    pub fn data(&self) -> &'a [u8] {
+        self.data
+    }
+
+    pub fn crc(&self) -> u32 {
+        self.crc
+    }
+
+    pub fn to_chunk(self) -> Chunk {
+        Chunk { chunk_type: self.chunk_type, chunk_data: self.data.to_vec(), chunk_checksum: self.crc }
+    }
+}
+
+// every chunk of a PNG held in memory, borrowed in file order
+pub struct ChunkRefs<'a> {
+    rest: &'a [u8],
+}
+
+impl<'a> ChunkRefs<'a> {
+    pub fn new(png_bytes: &'a [u8]) -> Result<ChunkRefs<'a>, ChunkError> {
+        match png_bytes.strip_prefix(&SIGNATURE) {
+            Some(rest) => Ok(ChunkRefs { rest }),
+            None => Err(ChunkError::InvalidInput),
+        }
+    }
+}
+
+impl<'a> Iterator for ChunkRefs<'a> {
+    type Item = Result<ChunkRef<'a>, ChunkError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.rest.is_empty() {
+            return None;
+        }
+        match ChunkRef::parse(self.rest) {
+            Ok((chunk, rest)) => {
+                self.rest = rest;
+                Some(Ok(chunk))
+            }
+            Err(err) => {
+                // nothing after a malformed chunk can be trusted
+                self.rest = &[];
+                Some(Err(err))
             }
         }
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq, Eq)]
+#[allow(clippy::enum_variant_names)]
 pub enum ChunkError {
     InvalidInput,
     InvalidLength,
@@ -239,6 +311,53 @@ mod tests {
         assert!(chunk.is_err());
     }
 
+    fn png_bytes(chunks: &[Chunk]) -> Vec<u8> {
+        let mut bytes = SIGNATURE.to_vec();
+        chunks.iter().for_each(|chunk| bytes.extend(chunk.as_bytes()));
+        bytes
+    }
+
+    #[test]
+    fn test_chunk_refs_borrow_the_input() {
+        let chunks = [testing_chunk(), Chunk::new(ChunkType::from_str("IEND").unwrap(), Vec::new())];
+        let bytes = png_bytes(&chunks);
+        let refs: Vec<ChunkRef> = ChunkRefs::new(&bytes).unwrap().collect::<Result<_, _>>().unwrap();
+        assert_eq!(refs.len(), 2);
+        assert_eq!(refs[0].chunk_type().to_string(), "RuSt");
+        assert_eq!(refs[0].crc(), 2882656334);
+        // the data points into `bytes` rather than a copy
+        assert_eq!(refs[0].data().as_ptr(), bytes[16..].as_ptr());
+        assert_eq!(refs[1].length(), 0);
+        assert_eq!(refs[0].to_chunk().as_bytes(), chunks[0].as_bytes());
+    }
+
+    #[test]
+    fn test_chunk_refs_stop_at_malformed_chunks() {
+        assert_eq!(ChunkRefs::new(b"not a png").err(), Some(ChunkError::InvalidInput));
+        let mut bytes = png_bytes(&[testing_chunk(), testing_chunk()]);
+        bytes[20] ^= 1;
+        let mut refs = ChunkRefs::new(&bytes).unwrap();
+        assert_eq!(refs.next(), Some(Err(ChunkError::InvalidCrcReceived)));
+        assert_eq!(refs.next(), None);
+
+        let bytes = png_bytes(&[testing_chunk()]);
+        let (_, rest) = ChunkRef::parse(&bytes[8..]).unwrap();
+        assert!(rest.is_empty());
+        assert_eq!(ChunkRef::parse(&bytes[8..40]).err(), Some(ChunkError::InvalidLength));
+
+        // a correct CRC doesn't make digits a chunk type
+        let chunk = Chunk::new(ChunkType::try_from(*b"R2St").unwrap(), b"data".to_vec());
+        assert_eq!(ChunkRef::parse(&chunk.as_bytes()).err(), Some(ChunkError::InvalidInput));
+    }
+
+    #[test]
+    fn test_known_chunk_helpers() {
+        let gama = chunk("gAMA", 45455u32.to_be_bytes().to_vec());
+        assert!(gama.is_type("gAMA") && !gama.is_type("GAMA"));
+        assert_eq!(read_u32(gama.data(), 0), 45455);
+        assert_eq!(read_u16(gama.data(), 2), 45455);
+    }
+
     #[test]
     pub fn test_chunk_trait_impls() {
         let data_length: u32 = 42;
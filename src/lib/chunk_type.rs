@@ -3,39 +3,33 @@ use std::str::FromStr;
 
 use std::fmt::Display;
 
-#[derive(Debug, Clone, PartialEq, Eq)]
-pub struct ChunkType{
-    byte_vec: Vec<u8>,
-    validity: bool,
-}
+// four bytes, so it's Copy and never touches the heap
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ChunkType([u8; 4]);
 
 impl ChunkType {
     pub fn bytes(&self) -> [u8; 4]{
-        let mut bytes_: [u8; 4] = [0; 4];
-        for i in 0..4 {
-            bytes_[i] = self.byte_vec[i];
-        }
-        return bytes_;
+        self.0
     }
 
     pub fn is_valid(&self) -> bool{
-        self.validity
+        self.is_reserved_bit_valid()
     }
 
     pub fn is_critical(&self) -> bool{
-        self.byte_vec[0].is_ascii_uppercase()
+        self.0[0].is_ascii_uppercase()
     }
 
     pub fn is_public(&self) -> bool{
-        self.byte_vec[1].is_ascii_uppercase()
+        self.0[1].is_ascii_uppercase()
     }
 
     pub fn is_reserved_bit_valid(&self) -> bool{
-        self.byte_vec[2].is_ascii_uppercase()
+        self.0[2].is_ascii_uppercase()
     }
 
     pub fn is_safe_to_copy(&self) -> bool{
-        self.byte_vec[3].is_ascii_lowercase()
+        self.0[3].is_ascii_lowercase()
     }
 
 }
@@ -44,9 +38,7 @@ impl TryFrom<[u8; 4]> for ChunkType {
     type Error = ChunkTypeError;
 
     fn try_from(byte_stream: [u8; 4]) -> Result<Self, Self::Error> {
-        let byte_vec = byte_stream.to_vec();
-        let validity = byte_vec[2].is_ascii_uppercase();
-        Ok(Self { byte_vec , validity })
+        Ok(Self(byte_stream))
     }
 }
 
@@ -55,7 +47,7 @@ fn is_within_desired_ascii_range(s: &str) -> bool {
         false
     } else {
         for c in s.chars() {
-            if c < 'A' || c > 'z' || (c > 'Z' && c < 'a') {
+            if !c.is_ascii_alphabetic() {
                 return false;
             }
         }
@@ -72,17 +64,19 @@ impl FromStr for ChunkType {
         }else if !is_within_desired_ascii_range(s) {
             Err(ChunkTypeError::InvalidChunkTypeAscii)
         } else {
-            let byte_vec = s.as_bytes().to_vec();
-            let validity = byte_vec[2].is_ascii_uppercase();
-            Ok(Self { byte_vec, validity })
+            let mut bytes = [0; 4];
+            bytes.copy_from_slice(s.as_bytes());
+            Ok(Self(bytes))
         }
     }
 }
 
 impl Display for ChunkType {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let ascii_string: String = self.byte_vec.iter().map(|&byte| byte as char).collect();
-        write!(f, "{}", ascii_string)
+        for byte in self.0 {
+            write!(f, "{}", byte as char)?;
+        }
+        Ok(())
     }
 }
 
@@ -2,29 +2,169 @@
 use std::ffi::OsString;
 
 use super::chunk_type::ChunkType;
+use super::exif::Orientation;
+use super::lsb::LsbOptions;
+use ed25519_dalek::VerifyingKey;
+use x25519_dalek::PublicKey;
+
+#[derive(Debug)]
+pub enum HidingMethod {
+    Chunk(ChunkType),
+    Lsb(LsbOptions),
+}
+
+// which of several chunks of one type a command acts on; indices count
+// only chunks of that type
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Selection {
+    First,
+    All,
+    Index(usize),
+}
+
+// "-" as the file path reads the payload from stdin
+#[derive(Debug)]
+pub enum PayloadSource {
+    Message(String),
+    File(OsString),
+    Stdin,
+}
 
 #[derive(Debug)]
 pub struct Encode {
     pub input_file_path: OsString,
-    pub chunk_type: ChunkType,
-    pub message: String,
+    pub method: HidingMethod,
+    pub payload: PayloadSource,
     pub output_file_path: Option<OsString>,
+    pub password: Option<String>,
+    pub recipients: Vec<PublicKey>,
+    pub private_key_path: Option<OsString>,
+    pub compress: bool,
+    // pieces are only used with the chunk method
+    pub split_size: Option<usize>,
 }
 
 #[derive(Debug)]
 pub struct Decode {
     pub input_file_path: OsString,
-    pub chunk_type: ChunkType,
+    pub method: HidingMethod,
+    pub password: Option<String>,
+    pub private_key_path: Option<OsString>,
+    // "-" writes to stdout
+    pub out_path: Option<OsString>,
+    pub selection: Selection,
 }
 
 #[derive(Debug)]
 pub struct Remove {
     pub input_file_path: OsString,
     pub chunk_type: ChunkType,
+    pub selection: Selection,
+    pub output_file_path: Option<OsString>,
 }
 
 #[derive(Debug)]
 pub struct Print {
     pub input_file_path: OsString,
+    // lists offsets, types and lengths without loading chunk data
+    pub headers_only: bool,
+}
+
+
+#[derive(Debug)]
+pub struct Keygen {
+    pub output_file_path: OsString,
+    pub signing: bool,
+    pub force: bool,
 }
 
+#[derive(Debug)]
+pub struct Sign {
+    pub input_file_path: OsString,
+    pub private_key_path: OsString,
+    pub include: Vec<ChunkType>,
+    pub output_file_path: Option<OsString>,
+}
+
+#[derive(Debug)]
+pub struct Verify {
+    pub input_file_path: OsString,
+    pub public_key: Option<VerifyingKey>,
+}
+
+#[derive(Debug)]
+pub enum TextAction {
+    List,
+    Set {
+        keyword: String,
+        value: String,
+        compress: bool,
+        // either one makes it an iTXt chunk, as does non Latin-1 text
+        language_tag: Option<String>,
+        translated_keyword: Option<String>,
+    },
+    Delete { keyword: String },
+}
+
+#[derive(Debug)]
+pub struct Text {
+    pub input_file_path: OsString,
+    pub action: TextAction,
+    pub output_file_path: Option<OsString>,
+}
+
+// with no rewrites given the tags are only printed
+#[derive(Debug)]
+pub struct ShowExif {
+    pub input_file_path: OsString,
+    pub orientation: Option<Orientation>,
+    pub model: Option<String>,
+    pub output_file_path: Option<OsString>,
+}
+
+#[derive(Debug)]
+pub struct StripGps {
+    pub input_file_path: OsString,
+    pub output_file_path: Option<OsString>,
+}
+
+#[derive(Debug)]
+pub struct PrintPalette {
+    pub input_file_path: OsString,
+}
+
+#[derive(Debug)]
+pub struct SetDpi {
+    pub input_file_path: OsString,
+    pub dpi: f64,
+    pub output_file_path: Option<OsString>,
+}
+
+// sets the modification time to now
+#[derive(Debug)]
+pub struct Touch {
+    pub input_file_path: OsString,
+    pub output_file_path: Option<OsString>,
+}
+
+#[derive(Debug)]
+pub enum FramesAction {
+    List,
+    // every frame is composited onto the full canvas
+    Extract { out_dir: OsString },
+}
+
+#[derive(Debug)]
+pub struct Frames {
+    pub input_file_path: OsString,
+    pub action: FramesAction,
+}
+
+#[derive(Debug)]
+pub struct BuildAnimation {
+    pub frame_file_paths: Vec<OsString>,
+    pub delay_ms: u16,
+    pub num_plays: u32,
+    pub crop: bool,
+    pub output_file_path: OsString,
+}
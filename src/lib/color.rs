@@ -0,0 +1,474 @@
+use std::fmt::Display;
+
+use super::chunk::{chunk, read_u32, Chunk};
+use super::decoder;
+use super::encoder;
+use super::png::Png;
+use super::text;
+
+// gAMA and cHRM store their values times 100000
+const SCALE: f64 = 100_000.0;
+// an ICC profile starts with a 128 byte header and a tag count
+const ICC_MIN_LEN: usize = 132;
+// iCCP profiles are inflated no further than this
+const ICC_MAX_LEN: usize = 16 << 20;
+
+fn check_type(chunk: &Chunk, expected: &'static str) -> Result<(), ColorError> {
+    match chunk.is_type(expected) {
+        true => Ok(()),
+        false => Err(ColorError::WrongChunkType(expected)),
+    }
+}
+
+fn check_length(chunk: &Chunk, expected: usize) -> Result<(), ColorError> {
+    match chunk.data().len() == expected {
+        true => Ok(()),
+        false => Err(ColorError::InvalidLength(chunk.chunk_type().to_string())),
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Gama {
+    pub gamma: u32,
+}
+
+impl Gama {
+    pub fn new(gamma: u32) -> Result<Gama, ColorError> {
+        match gamma {
+            0 => Err(ColorError::ZeroGamma),
+            _ => Ok(Gama { gamma }),
+        }
+    }
+
+    // the encoding exponent, 1/2.2 is stored as 45455
+    pub fn value(&self) -> f64 {
+        self.gamma as f64 / SCALE
+    }
+
+    pub fn to_chunk(self) -> Result<Chunk, ColorError> {
+        Gama::new(self.gamma)?;
+        Ok(chunk("gAMA", self.gamma.to_be_bytes().to_vec()))
+    }
+}
+
+impl TryFrom<&Chunk> for Gama {
+    type Error = ColorError;
+
+    fn try_from(chunk: &Chunk) -> Result<Self, Self::Error> {
+        check_type(chunk, "gAMA")?;
+        check_length(chunk, 4)?;
+        Gama::new(read_u32(chunk.data(), 0))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Chromaticity {
+    pub x: u32,
+    pub y: u32,
+}
+
+impl Chromaticity {
+    pub fn xy(&self) -> (f64, f64) {
+        (self.x as f64 / SCALE, self.y as f64 / SCALE)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Chrm {
+    pub white: Chromaticity,
+    pub red: Chromaticity,
+    pub green: Chromaticity,
+    pub blue: Chromaticity,
+}
+
+impl Chrm {
+    pub fn validate(&self) -> Result<(), ColorError> {
+        let points = [self.white, self.red, self.green, self.blue];
+        // x + y can't exceed 1 for a real colour, and the white point needs
+        // a non zero y to be converted to XYZ
+        if points.iter().any(|p| p.x as u64 + p.y as u64 > SCALE as u64) || self.white.y == 0 {
+            return Err(ColorError::InvalidChromaticity);
+        }
+        Ok(())
+    }
+
+    pub fn to_chunk(self) -> Result<Chunk, ColorError> {
+        self.validate()?;
+        let points = [self.white, self.red, self.green, self.blue];
+        Ok(chunk("cHRM", points.iter().flat_map(|p| p.x.to_be_bytes().into_iter().chain(p.y.to_be_bytes())).collect()))
+    }
+}
+
+impl TryFrom<&Chunk> for Chrm {
+    type Error = ColorError;
+
+    fn try_from(chunk: &Chunk) -> Result<Self, Self::Error> {
+        check_type(chunk, "cHRM")?;
+        check_length(chunk, 32)?;
+        let point = |i: usize| Chromaticity { x: read_u32(chunk.data(), i * 8), y: read_u32(chunk.data(), i * 8 + 4) };
+        let chrm = Chrm { white: point(0), red: point(1), green: point(2), blue: point(3) };
+        chrm.validate()?;
+        Ok(chrm)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RenderingIntent {
+    Perceptual,
+    RelativeColorimetric,
+    Saturation,
+    AbsoluteColorimetric,
+}
+
+impl RenderingIntent {
+    pub fn from_byte(byte: u8) -> Result<RenderingIntent, ColorError> {
+        match byte {
+            0 => Ok(RenderingIntent::Perceptual),
+            1 => Ok(RenderingIntent::RelativeColorimetric),
+            2 => Ok(RenderingIntent::Saturation),
+            3 => Ok(RenderingIntent::AbsoluteColorimetric),
+            _ => Err(ColorError::InvalidRenderingIntent(byte)),
+        }
+    }
+
+    pub fn as_byte(&self) -> u8 {
+        match self {
+            RenderingIntent::Perceptual => 0,
+            RenderingIntent::RelativeColorimetric => 1,
+            RenderingIntent::Saturation => 2,
+            RenderingIntent::AbsoluteColorimetric => 3,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Srgb {
+    pub intent: RenderingIntent,
+}
+
+impl Srgb {
+    // every intent can be written, the Result matches the other color chunks
+    pub fn to_chunk(self) -> Result<Chunk, ColorError> {
+        Ok(chunk("sRGB", vec![self.intent.as_byte()]))
+    }
+}
+
+impl TryFrom<&Chunk> for Srgb {
+    type Error = ColorError;
+
+    fn try_from(chunk: &Chunk) -> Result<Self, Self::Error> {
+        check_type(chunk, "sRGB")?;
+        check_length(chunk, 1)?;
+        Ok(Srgb { intent: RenderingIntent::from_byte(chunk.data()[0])? })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Iccp {
+    pub name: String,
+    // inflated
+    pub profile: Vec<u8>,
+}
+
+impl Iccp {
+    pub fn new(name: &str, profile: Vec<u8>) -> Result<Iccp, ColorError> {
+        let iccp = Iccp { name: name.to_string(), profile };
+        iccp.validate()?;
+        Ok(iccp)
+    }
+
+    // the name as Latin-1 bytes
+    fn validate(&self) -> Result<Vec<u8>, ColorError> {
+        let name = text::validate_keyword(&self.name).map_err(|_| ColorError::InvalidProfileName)?;
+        let profile = &self.profile;
+        // the header's size field must match and the signature must be there
+        if profile.len() < ICC_MIN_LEN || read_u32(profile, 0) as usize != profile.len() || &profile[36..40] != b"acsp" {
+            return Err(ColorError::InvalidProfile);
+        }
+        Ok(name)
+    }
+
+    // the fields are public, so they're checked again here
+    pub fn to_chunk(&self) -> Result<Chunk, ColorError> {
+        let mut data = self.validate()?;
+        data.extend_from_slice(&[0, 0]);
+        data.extend(encoder::deflate(&self.profile, 9));
+        Ok(chunk("iCCP", data))
+    }
+}
+
+impl TryFrom<&Chunk> for Iccp {
+    type Error = ColorError;
+
+    fn try_from(chunk: &Chunk) -> Result<Self, Self::Error> {
+        check_type(chunk, "iCCP")?;
+        let data = chunk.data();
+        let name_end = data.iter().position(|&byte| byte == 0).ok_or(ColorError::InvalidProfileName)?;
+        let name: String = data[..name_end].iter().map(|&byte| byte as char).collect();
+        match data.get(name_end + 1) {
+            Some(0) => (),
+            Some(&method) => return Err(ColorError::UnknownCompressionMethod(method)),
+            None => return Err(ColorError::InvalidLength("iCCP".to_string())),
+        }
+        let profile = decoder::inflate_limited(&data[name_end + 2..], ICC_MAX_LEN).map_err(|err| match err {
+            decoder::DecodeError::TooLarge(limit) => ColorError::ProfileTooLarge(limit),
+            err => ColorError::Inflate(format!("{:?}", err)),
+        })?;
+        Iccp::new(&name, profile)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cicp {
+    // code points from ITU-T H.273
+    pub colour_primaries: u8,
+    pub transfer_function: u8,
+    pub matrix_coefficients: u8,
+    pub video_full_range: bool,
+}
+
+impl Cicp {
+    pub fn validate(&self) -> Result<(), ColorError> {
+        // PNG pixels are always RGB, so only the identity matrix is allowed
+        match self.matrix_coefficients {
+            0 => Ok(()),
+            matrix => Err(ColorError::UnsupportedMatrix(matrix)),
+        }
+    }
+
+    pub fn to_chunk(self) -> Result<Chunk, ColorError> {
+        self.validate()?;
+        Ok(chunk("cICP", vec![self.colour_primaries, self.transfer_function, self.matrix_coefficients, self.video_full_range as u8]))
+    }
+}
+
+impl TryFrom<&Chunk> for Cicp {
+    type Error = ColorError;
+
+    fn try_from(chunk: &Chunk) -> Result<Self, Self::Error> {
+        check_type(chunk, "cICP")?;
+        check_length(chunk, 4)?;
+        let data = chunk.data();
+        let video_full_range = match data[3] {
+            0 => false,
+            1 => true,
+            flag => return Err(ColorError::InvalidRangeFlag(flag)),
+        };
+        let cicp = Cicp { colour_primaries: data[0], transfer_function: data[1], matrix_coefficients: data[2], video_full_range };
+        cicp.validate()?;
+        Ok(cicp)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ColorSpace {
+    Cicp(Cicp),
+    IccProfile(Iccp),
+    Srgb(RenderingIntent),
+    Calibrated { gamma: Option<Gama>, chromaticities: Option<Chrm> },
+    Unspecified,
+}
+
+// the color space in effect, and the chunks that were passed over on the
+// way to it because they couldn't be read
+#[derive(Debug, PartialEq, Eq)]
+pub struct ResolvedColorSpace {
+    pub color_space: ColorSpace,
+    pub skipped: Vec<(&'static str, ColorError)>,
+}
+
+// cICP wins over iCCP, which wins over sRGB, which wins over gAMA and cHRM;
+// a malformed chunk is skipped, so the next one in line takes its place
+pub fn color_space(png: &Png) -> ResolvedColorSpace {
+    let mut skipped = Vec::new();
+    let color_space = if let Some(cicp) = read_chunk(png, "cICP", &mut skipped) {
+        ColorSpace::Cicp(cicp)
+    } else if let Some(iccp) = read_chunk(png, "iCCP", &mut skipped) {
+        ColorSpace::IccProfile(iccp)
+    } else if let Some(srgb) = read_chunk::<Srgb>(png, "sRGB", &mut skipped) {
+        ColorSpace::Srgb(srgb.intent)
+    } else {
+        let gamma = read_chunk(png, "gAMA", &mut skipped);
+        let chromaticities = read_chunk(png, "cHRM", &mut skipped);
+        match (gamma, chromaticities) {
+            (None, None) => ColorSpace::Unspecified,
+            (gamma, chromaticities) => ColorSpace::Calibrated { gamma, chromaticities },
+        }
+    };
+    ResolvedColorSpace { color_space, skipped }
+}
+
+// None for a missing chunk as well as a malformed one, which is noted down
+fn read_chunk<'a, T>(png: &'a Png, chunk_type: &'static str, skipped: &mut Vec<(&'static str, ColorError)>) -> Option<T>
+where
+    T: TryFrom<&'a Chunk, Error = ColorError>,
+{
+    match T::try_from(png.chunk_by_type(chunk_type)?) {
+        Ok(value) => Some(value),
+        Err(err) => {
+            skipped.push((chunk_type, err));
+            None
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum ColorError {
+    WrongChunkType(&'static str),
+    InvalidLength(String),
+    ZeroGamma,
+    InvalidChromaticity,
+    InvalidRenderingIntent(u8),
+    InvalidProfileName,
+    UnknownCompressionMethod(u8),
+    Inflate(String),
+    ProfileTooLarge(usize),
+    InvalidProfile,
+    UnsupportedMatrix(u8),
+    InvalidRangeFlag(u8),
+}
+
+impl Display for ColorError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ColorError::WrongChunkType(expected) => write!(f, "expected a {} chunk", expected),
+            ColorError::InvalidLength(chunk_type) => write!(f, "{} chunk has the wrong length", chunk_type),
+            ColorError::ZeroGamma => write!(f, "gAMA can't be zero"),
+            ColorError::InvalidChromaticity => write!(f, "cHRM holds impossible chromaticities"),
+            ColorError::InvalidRenderingIntent(intent) => write!(f, "unknown sRGB rendering intent {}", intent),
+            ColorError::InvalidProfileName => write!(f, "iCCP profile name is not a valid keyword"),
+            ColorError::UnknownCompressionMethod(method) => write!(f, "unknown iCCP compression method {}", method),
+            ColorError::Inflate(reason) => write!(f, "couldn't inflate the ICC profile: {}", reason),
+            ColorError::ProfileTooLarge(limit) => write!(f, "ICC profile inflates to more than {} bytes", limit),
+            ColorError::InvalidProfile => write!(f, "iCCP doesn't hold a valid ICC profile"),
+            ColorError::UnsupportedMatrix(matrix) => write!(f, "cICP matrix coefficients must be 0, not {}", matrix),
+            ColorError::InvalidRangeFlag(flag) => write!(f, "cICP full range flag must be 0 or 1, not {}", flag),
+        }
+    }
+}
+
+impl std::error::Error for ColorError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SRGB_CHRM: Chrm = Chrm {
+        white: Chromaticity { x: 31270, y: 32900 },
+        red: Chromaticity { x: 64000, y: 33000 },
+        green: Chromaticity { x: 30000, y: 60000 },
+        blue: Chromaticity { x: 15000, y: 6000 },
+    };
+
+    fn icc_profile() -> Vec<u8> {
+        let mut profile = vec![0; ICC_MIN_LEN];
+        profile[..4].copy_from_slice(&(ICC_MIN_LEN as u32).to_be_bytes());
+        profile[36..40].copy_from_slice(b"acsp");
+        profile
+    }
+
+    fn png_with(extra: Vec<Chunk>) -> Png {
+        let mut chunks = vec![chunk("IHDR", Vec::new())];
+        chunks.extend(extra);
+        chunks.push(chunk("IDAT", Vec::new()));
+        chunks.push(chunk("IEND", Vec::new()));
+        Png::from_chunks(chunks).unwrap()
+    }
+
+    #[test]
+    fn test_chunk_round_trips() {
+        let gama = Gama::new(45455).unwrap();
+        assert_eq!(Gama::try_from(&gama.to_chunk().unwrap()).unwrap(), gama);
+        assert!((gama.value() - 0.45455).abs() < 1e-9);
+        assert_eq!(Chrm::try_from(&SRGB_CHRM.to_chunk().unwrap()).unwrap(), SRGB_CHRM);
+        let srgb = Srgb { intent: RenderingIntent::Saturation };
+        assert_eq!(Srgb::try_from(&srgb.to_chunk().unwrap()).unwrap(), srgb);
+        let iccp = Iccp::new("Display P3", icc_profile()).unwrap();
+        assert_eq!(Iccp::try_from(&iccp.to_chunk().unwrap()).unwrap(), iccp);
+        let cicp = Cicp { colour_primaries: 9, transfer_function: 16, matrix_coefficients: 0, video_full_range: true };
+        assert_eq!(Cicp::try_from(&cicp.to_chunk().unwrap()).unwrap(), cicp);
+    }
+
+    #[test]
+    fn test_validation() {
+        assert_eq!(Gama::try_from(&chunk("gAMA", vec![0; 4])), Err(ColorError::ZeroGamma));
+        assert_eq!(Gama::try_from(&chunk("gAMA", vec![1; 3])), Err(ColorError::InvalidLength("gAMA".to_string())));
+        assert_eq!(Gama::try_from(&chunk("sRGB", vec![0])), Err(ColorError::WrongChunkType("gAMA")));
+        assert_eq!(Chrm::try_from(&chunk("cHRM", vec![0; 32])), Err(ColorError::InvalidChromaticity));
+        assert_eq!(Srgb::try_from(&chunk("sRGB", vec![4])), Err(ColorError::InvalidRenderingIntent(4)));
+        assert_eq!(Cicp::try_from(&chunk("cICP", vec![1, 13, 1, 1])), Err(ColorError::UnsupportedMatrix(1)));
+        assert_eq!(Cicp::try_from(&chunk("cICP", vec![1, 13, 0, 2])), Err(ColorError::InvalidRangeFlag(2)));
+
+        let mut profile = icc_profile();
+        profile.push(0);
+        assert_eq!(Iccp::new("Profile", profile), Err(ColorError::InvalidProfile));
+        assert_eq!(Iccp::new("", icc_profile()), Err(ColorError::InvalidProfileName));
+        assert_eq!(Iccp::try_from(&chunk("iCCP", b"P\0\x01".to_vec())), Err(ColorError::UnknownCompressionMethod(1)));
+        let mut data = b"P\0\0".to_vec();
+        data.extend(encoder::deflate(&vec![0; ICC_MAX_LEN + 1], 9));
+        assert_eq!(Iccp::try_from(&chunk("iCCP", data)), Err(ColorError::ProfileTooLarge(ICC_MAX_LEN)));
+    }
+
+    #[test]
+    fn test_color_space_precedence() {
+        let gama = Gama::new(45455).unwrap();
+        let srgb = Srgb { intent: RenderingIntent::Perceptual };
+        let iccp = Iccp::new("Profile", icc_profile()).unwrap();
+        let cicp = Cicp { colour_primaries: 1, transfer_function: 13, matrix_coefficients: 0, video_full_range: true };
+
+        assert_eq!(color_space(&png_with(vec![])).color_space, ColorSpace::Unspecified);
+        assert_eq!(
+            color_space(&png_with(vec![gama.to_chunk().unwrap()])).color_space,
+            ColorSpace::Calibrated { gamma: Some(gama), chromaticities: None }
+        );
+        let chunks = vec![gama.to_chunk().unwrap(), SRGB_CHRM.to_chunk().unwrap(), srgb.to_chunk().unwrap()];
+        assert_eq!(color_space(&png_with(chunks)).color_space, ColorSpace::Srgb(RenderingIntent::Perceptual));
+        assert_eq!(
+            color_space(&png_with(vec![srgb.to_chunk().unwrap(), iccp.to_chunk().unwrap()])).color_space,
+            ColorSpace::IccProfile(iccp.clone())
+        );
+        let chunks = vec![gama.to_chunk().unwrap(), iccp.to_chunk().unwrap(), cicp.to_chunk().unwrap()];
+        assert_eq!(color_space(&png_with(chunks)).color_space, ColorSpace::Cicp(cicp));
+    }
+
+    #[test]
+    fn test_invalid_chunks_are_skipped() {
+        let gama = Gama::new(45455).unwrap();
+        let png = png_with(vec![chunk("sRGB", vec![9]), gama.to_chunk().unwrap()]);
+        assert_eq!(
+            color_space(&png),
+            ResolvedColorSpace {
+                color_space: ColorSpace::Calibrated { gamma: Some(gama), chromaticities: None },
+                skipped: vec![("sRGB", ColorError::InvalidRenderingIntent(9))],
+            }
+        );
+
+        // a broken profile falls back to sRGB
+        let srgb = Srgb { intent: RenderingIntent::Perceptual };
+        let png = png_with(vec![chunk("iCCP", b"P\0\0broken".to_vec()), srgb.to_chunk().unwrap()]);
+        let resolved = color_space(&png);
+        assert_eq!(resolved.color_space, ColorSpace::Srgb(RenderingIntent::Perceptual));
+        assert!(matches!(resolved.skipped[..], [("iCCP", ColorError::Inflate(_))]));
+    }
+
+    #[test]
+    fn test_iccp_fields_are_checked() {
+        let mut iccp = Iccp::new("Profile", icc_profile()).unwrap();
+        iccp.name = "Caf\u{e9} \u{65e5}".to_string();
+        assert_eq!(iccp.to_chunk().err(), Some(ColorError::InvalidProfileName));
+        iccp.name = "Caf\u{e9}".to_string();
+        assert_eq!(&iccp.to_chunk().unwrap().data()[..5], b"Caf\xe9\0");
+        iccp.profile.pop();
+        assert_eq!(iccp.to_chunk().err(), Some(ColorError::InvalidProfile));
+    }
+
+    #[test]
+    fn test_color_fields_are_checked() {
+        assert_eq!(Gama { gamma: 0 }.to_chunk().err(), Some(ColorError::ZeroGamma));
+        let mut chrm = SRGB_CHRM;
+        chrm.red.x = 90000;
+        assert_eq!(chrm.to_chunk().err(), Some(ColorError::InvalidChromaticity));
+        let cicp = Cicp { colour_primaries: 1, transfer_function: 13, matrix_coefficients: 1, video_full_range: true };
+        assert_eq!(cicp.to_chunk().err(), Some(ColorError::UnsupportedMatrix(1)));
+    }
+}
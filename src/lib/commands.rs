@@ -1,11 +1,16 @@
 extern crate clap;
 
-use clap::{App, AppSettings, SubCommand, Arg};
+use clap::{App, AppSettings, SubCommand, Arg, ArgMatches, ErrorKind};
 use std::ffi::OsString;
 use std::str::FromStr;
 
 use super::chunk_type::ChunkType;
-use super::cli::{Encode, Decode, Remove, Print};
+use super::cli::{Encode, Decode, Remove, Print, Keygen, Sign, Verify, HidingMethod, PayloadSource, Selection, Text, TextAction, ShowExif, StripGps, PrintPalette, SetDpi, Touch, Frames, FramesAction, BuildAnimation};
+use super::exif::Orientation;
+use super::hex;
+use super::lsb::{Channel, LsbOptions};
+use ed25519_dalek::VerifyingKey;
+use x25519_dalek::PublicKey;
 
 #[derive(Debug)]
 pub enum Command {
@@ -13,6 +18,17 @@ pub enum Command {
     Decd(Decode),
     Remv(Remove),
     Prnt(Print),
+    Kgen(Keygen),
+    Sgnt(Sign),
+    Vrfy(Verify),
+    Txts(Text),
+    Exfs(ShowExif),
+    Sgps(StripGps),
+    Pltt(PrintPalette),
+    Sdpi(SetDpi),
+    Tuch(Touch),
+    Frms(Frames),
+    Anim(BuildAnimation),
 }
 
 pub fn parse_command() -> Command {
@@ -41,11 +57,73 @@ where
                         )
                         .arg(
                             Arg::with_name("chunk_type")
-                            .help("Chunk type of the incoming message")
+                            .help("Chunk type of the incoming message, required for the chunk method")
                             .long("chunk_type")
                             .short("c")
                             .value_name("CHUNK-TYPE")
-                            .required(true)
+                            .required(false)
+                        )
+                        .arg(
+                            Arg::with_name("method")
+                            .help("Where the message is hidden: a separate chunk or the pixel LSBs")
+                            .long("method")
+                            .value_name("METHOD")
+                            .possible_values(&["chunk", "lsb"])
+                            .default_value("chunk")
+                        )
+                        .arg(
+                            Arg::with_name("bits")
+                            .help("Bits per channel used by the lsb method")
+                            .long("bits")
+                            .value_name("BITS")
+                            .default_value("1")
+                        )
+                        .arg(
+                            Arg::with_name("channels")
+                            .help("Channels used by the lsb method, any of r, g, b, y (gray) and a")
+                            .long("channels")
+                            .value_name("CHANNELS")
+                            .default_value("rgby")
+                        )
+                        .arg(
+                            Arg::with_name("password")
+                            .help("Password used to encrypt or decrypt the message")
+                            .long("password")
+                            .short("p")
+                            .value_name("PASSWORD")
+                            .required(false)
+                        )
+                        .arg(
+                            Arg::with_name("recipient")
+                            .help("Hex encoded X25519 public key of a recipient, may be repeated")
+                            .long("recipient")
+                            .short("r")
+                            .value_name("PUBLIC-KEY")
+                            .multiple(true)
+                            .number_of_values(1)
+                            .requires("private_key")
+                            .conflicts_with("password")
+                        )
+                        .arg(
+                            Arg::with_name("private_key")
+                            .help("File holding your hex encoded X25519 private key")
+                            .long("private-key")
+                            .short("k")
+                            .value_name("FILE")
+                            .required(false)
+                        )
+                        .arg(
+                            Arg::with_name("split_size")
+                            .help("Split the payload across chunks holding at most this many bytes each")
+                            .long("split-size")
+                            .value_name("BYTES")
+                            .required(false)
+                        )
+                        .arg(
+                            Arg::with_name("compress")
+                            .help("Deflate the message before hiding it")
+                            .long("compress")
+                            .short("z")
                         )
                         .arg(
                             Arg::with_name("message")
@@ -53,7 +131,15 @@ where
                             .long("msg")
                             .short("m")
                             .value_name("MESSAGE")
-                            .required(true)
+                            .required_unless("file")
+                        )
+                        .arg(
+                            Arg::with_name("file")
+                            .help("File to hide instead of a message, - reads from stdin")
+                            .long("file")
+                            .short("f")
+                            .value_name("FILE")
+                            .conflicts_with("message")
                         )
                         .arg(
                             Arg::with_name("output_file")
@@ -76,11 +162,70 @@ where
                         )
                         .arg(
                             Arg::with_name("chunk_type")
-                            .help("Chunk type of the incoming message")
+                            .help("Chunk type of the incoming message, required for the chunk method")
                             .long("chunk_type")
                             .short("c")
                             .value_name("CHUNK-TYPE")
-                            .required(true)
+                            .required(false)
+                        )
+                        .arg(
+                            Arg::with_name("method")
+                            .help("Where the message is hidden: a separate chunk or the pixel LSBs")
+                            .long("method")
+                            .value_name("METHOD")
+                            .possible_values(&["chunk", "lsb"])
+                            .default_value("chunk")
+                        )
+                        .arg(
+                            Arg::with_name("bits")
+                            .help("Bits per channel used by the lsb method")
+                            .long("bits")
+                            .value_name("BITS")
+                            .default_value("1")
+                        )
+                        .arg(
+                            Arg::with_name("channels")
+                            .help("Channels used by the lsb method, any of r, g, b, y (gray) and a")
+                            .long("channels")
+                            .value_name("CHANNELS")
+                            .default_value("rgby")
+                        )
+                        .arg(
+                            Arg::with_name("password")
+                            .help("Password used to encrypt or decrypt the message")
+                            .long("password")
+                            .short("p")
+                            .value_name("PASSWORD")
+                            .required(false)
+                        )
+                        .arg(
+                            Arg::with_name("private_key")
+                            .help("File holding your hex encoded X25519 private key")
+                            .long("private-key")
+                            .short("k")
+                            .value_name("FILE")
+                            .required(false)
+                        )
+                        .arg(
+                            Arg::with_name("out")
+                            .help("Write the hidden bytes to this file, or a directory to keep the stored name, - for stdout")
+                            .long("out")
+                            .conflicts_with("all")
+                            .value_name("PATH")
+                            .required(false)
+                        )
+                        .arg(
+                            Arg::with_name("all")
+                            .help("Act on every chunk of the chunk type")
+                            .long("all")
+                            .conflicts_with("index")
+                        )
+                        .arg(
+                            Arg::with_name("index")
+                            .help("Act on the nth chunk of the chunk type, counting from 0")
+                            .long("index")
+                            .value_name("N")
+                            .required(false)
                         )
                     )
                     .subcommand(SubCommand::with_name("remove")
@@ -101,6 +246,27 @@ where
                             .value_name("CHUNK-TYPE")
                             .required(true)
                         )
+                        .arg(
+                            Arg::with_name("all")
+                            .help("Act on every chunk of the chunk type")
+                            .long("all")
+                            .conflicts_with("index")
+                        )
+                        .arg(
+                            Arg::with_name("index")
+                            .help("Act on the nth chunk of the chunk type, counting from 0")
+                            .long("index")
+                            .value_name("N")
+                            .required(false)
+                        )
+                        .arg(
+                            Arg::with_name("output_file")
+                            .help("File the PNG without the removed chunks is written to, if ommited no file is changed")
+                            .long("output_file")
+                            .short("op")
+                            .value_name("FILE")
+                            .required(false)
+                        )
                     )
                     .subcommand(SubCommand::with_name("print")
                         .about("Prints the PNG in a readable format")
@@ -112,53 +278,574 @@ where
                             .value_name("FILE")
                             .required(true)   
                         )
+                        .arg(
+                            Arg::with_name("headers_only")
+                            .help("Only list the chunk layout, in constant memory for files of any size")
+                            .long("headers-only")
+                        )
+                    )
+                    .subcommand(SubCommand::with_name("keygen")
+                        .about("Generates an X25519 key pair for addressing messages to recipients")
+                        .arg(
+                            Arg::with_name("output_file")
+                            .help("File the hex encoded private key is written to")
+                            .long("output_file")
+                            .short("op")
+                            .value_name("FILE")
+                            .required(true)
+                        )
+                        .arg(
+                            Arg::with_name("signing")
+                            .help("Generate an Ed25519 key pair for sign/verify instead")
+                            .long("signing")
+                        )
+                        .arg(
+                            Arg::with_name("force")
+                            .help("Overwrite the output file if it already exists")
+                            .long("force")
+                        )
+                    )
+                    .subcommand(SubCommand::with_name("sign")
+                        .about("Signs the critical chunks, and optionally chosen ancillary chunks, of a PNG file")
+                        .arg(
+                            Arg::with_name("input_file")
+                            .help("Input file name")
+                            .long("input_file")
+                            .short("ip")
+                            .value_name("FILE")
+                            .required(true)
+                        )
+                        .arg(
+                            Arg::with_name("private_key")
+                            .help("File holding your hex encoded Ed25519 private key")
+                            .long("private-key")
+                            .short("k")
+                            .value_name("FILE")
+                            .required(true)
+                        )
+                        .arg(
+                            Arg::with_name("include")
+                            .help("Ancillary chunk type to cover as well, may be repeated")
+                            .long("include")
+                            .value_name("CHUNK-TYPE")
+                            .multiple(true)
+                            .number_of_values(1)
+                        )
+                        .arg(
+                            Arg::with_name("output_file")
+                            .help("Output file name, if ommited the input file is overwritten")
+                            .long("output_file")
+                            .short("op")
+                            .value_name("FILE")
+                            .required(false)
+                        )
+                    )
+                    .subcommand(SubCommand::with_name("verify")
+                        .about("Verifies the signature of a PNG file and lists the chunks it covers")
+                        .arg(
+                            Arg::with_name("input_file")
+                            .help("Input file name")
+                            .long("input_file")
+                            .short("ip")
+                            .value_name("FILE")
+                            .required(true)
+                        )
+                        .arg(
+                            Arg::with_name("public_key")
+                            .help("Hex encoded Ed25519 public key the signature must come from, without it the signer is unverified and the exit status is 1")
+                            .long("public-key")
+                            .value_name("PUBLIC-KEY")
+                            .required(false)
+                        )
+                    )
+                    .subcommand(SubCommand::with_name("text")
+                        .about("Reads and writes the standard tEXt, zTXt and iTXt chunks")
+                        .setting(AppSettings::SubcommandRequired)
+                        .subcommand(SubCommand::with_name("list")
+                            .about("Lists every text chunk")
+                            .arg(
+                                Arg::with_name("input_file")
+                                .help("Input file name")
+                                .long("input_file")
+                                .short("ip")
+                                .value_name("FILE")
+                                .required(true)
+                            )
+                        )
+                        .subcommand(SubCommand::with_name("set")
+                            .about("Sets a keyword, replacing any text chunks that already have it")
+                            .arg(
+                                Arg::with_name("input_file")
+                                .help("Input file name")
+                                .long("input_file")
+                                .short("ip")
+                                .value_name("FILE")
+                                .required(true)
+                            )
+                            .arg(
+                                Arg::with_name("output_file")
+                                .help("Output file name, if ommited the input file is overwritten")
+                                .long("output_file")
+                                .short("op")
+                                .value_name("FILE")
+                                .required(false)
+                            )
+                            .arg(
+                                Arg::with_name("keyword")
+                                .help("Keyword, e.g. Title, Author or Comment")
+                                .required(true)
+                                .index(1)
+                            )
+                            .arg(
+                                Arg::with_name("value")
+                                .help("Text stored under the keyword")
+                                .required(true)
+                                .index(2)
+                            )
+                            .arg(
+                                Arg::with_name("compress")
+                                .help("Store the text compressed, as zTXt or compressed iTXt")
+                                .long("compress")
+                                .short("z")
+                            )
+                            .arg(
+                                Arg::with_name("language")
+                                .help("Language tag of the text, e.g. en-GB; stores an iTXt chunk")
+                                .long("language")
+                                .value_name("TAG")
+                                .required(false)
+                            )
+                            .arg(
+                                Arg::with_name("translated_keyword")
+                                .help("Keyword translated into the text's language; stores an iTXt chunk")
+                                .long("translated-keyword")
+                                .value_name("KEYWORD")
+                                .required(false)
+                            )
+                        )
+                        .subcommand(SubCommand::with_name("delete")
+                            .about("Deletes every text chunk with the keyword")
+                            .arg(
+                                Arg::with_name("input_file")
+                                .help("Input file name")
+                                .long("input_file")
+                                .short("ip")
+                                .value_name("FILE")
+                                .required(true)
+                            )
+                            .arg(
+                                Arg::with_name("output_file")
+                                .help("Output file name, if ommited the input file is overwritten")
+                                .long("output_file")
+                                .short("op")
+                                .value_name("FILE")
+                                .required(false)
+                            )
+                            .arg(
+                                Arg::with_name("keyword")
+                                .help("Keyword to delete")
+                                .required(true)
+                                .index(1)
+                            )
+                        )
+                    )
+                    .subcommand(SubCommand::with_name("exif")
+                        .about("Prints the eXIf metadata of a PNG file, optionally rewriting tags")
+                        .arg(
+                            Arg::with_name("input_file")
+                            .help("Input file name")
+                            .long("input_file")
+                            .short("ip")
+                            .value_name("FILE")
+                            .required(true)
+                        )
+                        .arg(
+                            Arg::with_name("output_file")
+                            .help("Output file name, if ommited the input file is overwritten")
+                            .long("output_file")
+                            .short("op")
+                            .value_name("FILE")
+                            .required(false)
+                        )
+                        .arg(
+                            Arg::with_name("orientation")
+                            .help("New orientation, 1 to 8 as in the EXIF spec")
+                            .long("orientation")
+                            .value_name("N")
+                            .required(false)
+                        )
+                        .arg(
+                            Arg::with_name("model")
+                            .help("New camera model")
+                            .long("model")
+                            .value_name("MODEL")
+                            .required(false)
+                        )
+                    )
+                    .subcommand(SubCommand::with_name("strip-gps")
+                        .about("Removes the GPS position from the eXIf metadata of a PNG file")
+                        .arg(
+                            Arg::with_name("input_file")
+                            .help("Input file name")
+                            .long("input_file")
+                            .short("ip")
+                            .value_name("FILE")
+                            .required(true)
+                        )
+                        .arg(
+                            Arg::with_name("output_file")
+                            .help("Output file name, if ommited the input file is overwritten")
+                            .long("output_file")
+                            .short("op")
+                            .value_name("FILE")
+                            .required(false)
+                        )
+                    )
+                    .subcommand(SubCommand::with_name("palette")
+                        .about("Prints the palette entries of a PNG file with their alpha values")
+                        .arg(
+                            Arg::with_name("input_file")
+                            .help("Input file name")
+                            .long("input_file")
+                            .short("ip")
+                            .value_name("FILE")
+                            .required(true)
+                        )
+                    )
+                    .subcommand(SubCommand::with_name("set-dpi")
+                        .about("Sets the print resolution of a PNG file in dots per inch")
+                        .arg(
+                            Arg::with_name("input_file")
+                            .help("Input file name")
+                            .long("input_file")
+                            .short("ip")
+                            .value_name("FILE")
+                            .required(true)
+                        )
+                        .arg(
+                            Arg::with_name("dpi")
+                            .help("Resolution in dots per inch, used for both axes")
+                            .long("dpi")
+                            .value_name("DPI")
+                            .required(true)
+                        )
+                        .arg(
+                            Arg::with_name("output_file")
+                            .help("Output file name, if ommited the input file is overwritten")
+                            .long("output_file")
+                            .short("op")
+                            .value_name("FILE")
+                            .required(false)
+                        )
+                    )
+                    .subcommand(SubCommand::with_name("touch")
+                        .about("Sets the modification time of a PNG file to the current time")
+                        .arg(
+                            Arg::with_name("input_file")
+                            .help("Input file name")
+                            .long("input_file")
+                            .short("ip")
+                            .value_name("FILE")
+                            .required(true)
+                        )
+                        .arg(
+                            Arg::with_name("output_file")
+                            .help("Output file name, if ommited the input file is overwritten")
+                            .long("output_file")
+                            .short("op")
+                            .value_name("FILE")
+                            .required(false)
+                        )
+                    )
+                    .subcommand(SubCommand::with_name("frames")
+                        .about("Reads the frames of an animated PNG (APNG)")
+                        .setting(AppSettings::SubcommandRequired)
+                        .subcommand(SubCommand::with_name("list")
+                            .about("Lists every frame with its region, delay, dispose and blend ops")
+                            .arg(
+                                Arg::with_name("input_file")
+                                .help("Input file name")
+                                .long("input_file")
+                                .short("ip")
+                                .value_name("FILE")
+                                .required(true)
+                            )
+                        )
+                        .subcommand(SubCommand::with_name("extract")
+                            .about("Writes every frame, composited onto the full canvas, as a PNG file")
+                            .arg(
+                                Arg::with_name("input_file")
+                                .help("Input file name")
+                                .long("input_file")
+                                .short("ip")
+                                .value_name("FILE")
+                                .required(true)
+                            )
+                            .arg(
+                                Arg::with_name("out_dir")
+                                .help("Directory the frames are written to, created if missing")
+                                .long("out-dir")
+                                .value_name("DIR")
+                                .required(true)
+                            )
+                        )
+                        .subcommand(SubCommand::with_name("build")
+                            .about("Builds an animated PNG from still PNG frames of the same size and format")
+                            .arg(
+                                Arg::with_name("frames")
+                                .help("Frame files, in display order")
+                                .value_name("FILE")
+                                .multiple(true)
+                                .required(true)
+                            )
+                            .arg(
+                                Arg::with_name("output_file")
+                                .help("Output file name")
+                                .long("output_file")
+                                .short("op")
+                                .value_name("FILE")
+                                .required(true)
+                            )
+                            .arg(
+                                Arg::with_name("delay")
+                                .help("Delay of every frame in milliseconds")
+                                .long("delay")
+                                .value_name("MS")
+                                .default_value("100")
+                            )
+                            .arg(
+                                Arg::with_name("loops")
+                                .help("Number of times the animation plays, 0 loops forever")
+                                .long("loops")
+                                .value_name("COUNT")
+                                .default_value("0")
+                            )
+                            .arg(
+                                Arg::with_name("crop")
+                                .help("Store only the region that changed since the previous frame")
+                                .long("crop")
+                            )
+                        )
                     )
                     .get_matches_from_safe(args)?;
     
     if let Some(encode) = arg_matches.subcommand_matches("encode") {
-        let ip_fp = OsString::from(encode.value_of("input_file").unwrap());
-        let ct = ChunkType::from_str(encode.value_of("chunk_type").unwrap()).unwrap();
-        let msg = encode.value_of("message").unwrap().to_string();
-        if let Some(op_fp) = encode.value_of("output_file"){
-            Ok(Command::Encd(
-                Encode { 
-                    input_file_path: ip_fp, 
-                    chunk_type: ct,
-                    message: msg, 
-                    output_file_path: Some(OsString::from(op_fp)),
-                }
-            ))
-        }else {
-            Ok(Command::Encd(
-                Encode { 
-                    input_file_path: ip_fp, 
-                    chunk_type: ct,
-                    message: msg, 
-                    output_file_path: None,
-                }
-            ))
-        }
+        Ok(Command::Encd(
+            Encode { 
+                input_file_path: OsString::from(encode.value_of("input_file").unwrap()), 
+                method: parse_hiding_method(encode)?,
+                payload: match (encode.value_of("message"), encode.value_of_os("file")) {
+                    (Some(message), _) => PayloadSource::Message(message.to_string()),
+                    (None, Some(path)) if path == "-" => PayloadSource::Stdin,
+                    (None, path) => PayloadSource::File(path.unwrap().to_os_string()),
+                },
+                output_file_path: encode.value_of("output_file").map(OsString::from),
+                password: encode.value_of("password").map(String::from),
+                recipients: parse_recipients(encode)?,
+                private_key_path: encode.value_of("private_key").map(OsString::from),
+                compress: encode.is_present("compress"),
+                split_size: encode.value_of("split_size")
+                    .map(|size| size.parse::<usize>().map_err(|_| clap::Error::with_description(
+                        &format!("invalid split size '{}'", size), ErrorKind::InvalidValue)))
+                    .transpose()?,
+            }
+        ))
     } else if let Some(decode) = arg_matches.subcommand_matches("decode") {
         Ok(Command::Decd(
             Decode { 
                 input_file_path: OsString::from(decode.value_of("input_file").unwrap()), 
-                chunk_type: ChunkType::from_str(decode.value_of("chunk_type").unwrap()).unwrap()
+                method: parse_hiding_method(decode)?,
+                password: decode.value_of("password").map(String::from),
+                private_key_path: decode.value_of("private_key").map(OsString::from),
+                out_path: decode.value_of_os("out").map(OsString::from),
+                selection: parse_selection(decode)?,
             }
         ))
     } else if let Some(remove) = arg_matches.subcommand_matches("remove") {
         Ok(Command::Remv(
             Remove { 
                 input_file_path: OsString::from(remove.value_of("input_file").unwrap()), 
-                chunk_type: ChunkType::from_str(remove.value_of("chunk_type").unwrap()).unwrap()
+                chunk_type: ChunkType::from_str(remove.value_of("chunk_type").unwrap()).unwrap(),
+                selection: parse_selection(remove)?,
+                output_file_path: remove.value_of("output_file").map(OsString::from),
             }
         ))
     } else if let Some(print_) = arg_matches.subcommand_matches("print") {
         Ok(Command::Prnt(
             Print { 
-                input_file_path: OsString::from(print_.value_of("input_file").unwrap())
+                input_file_path: OsString::from(print_.value_of("input_file").unwrap()),
+                headers_only: print_.is_present("headers_only"),
+            }
+        ))
+    } else if let Some(keygen) = arg_matches.subcommand_matches("keygen") {
+        Ok(Command::Kgen(
+            Keygen {
+                output_file_path: OsString::from(keygen.value_of("output_file").unwrap()),
+                signing: keygen.is_present("signing"),
+                force: keygen.is_present("force"),
+            }
+        ))
+    } else if let Some(sign) = arg_matches.subcommand_matches("sign") {
+        let include = sign.values_of("include").into_iter().flatten()
+            .map(|chunk_type| ChunkType::from_str(chunk_type).map_err(|_| clap::Error::with_description(
+                &format!("invalid chunk type '{}'", chunk_type), ErrorKind::InvalidValue)))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Command::Sgnt(
+            Sign {
+                input_file_path: OsString::from(sign.value_of("input_file").unwrap()),
+                private_key_path: OsString::from(sign.value_of("private_key").unwrap()),
+                include,
+                output_file_path: sign.value_of("output_file").map(OsString::from),
+            }
+        ))
+    } else if let Some(verify) = arg_matches.subcommand_matches("verify") {
+        let public_key = match verify.value_of("public_key") {
+            Some(key) => Some(hex::decode_array::<32>(key).ok()
+                .and_then(|bytes| VerifyingKey::from_bytes(&bytes).ok())
+                .ok_or_else(|| clap::Error::with_description(
+                    &format!("invalid public key '{}'", key), ErrorKind::InvalidValue))?),
+            None => None,
+        };
+        Ok(Command::Vrfy(
+            Verify {
+                input_file_path: OsString::from(verify.value_of("input_file").unwrap()),
+                public_key,
+            }
+        ))
+    } else if let Some(text) = arg_matches.subcommand_matches("text") {
+        let (name, matches) = text.subcommand();
+        let matches = matches.unwrap();
+        let action = match name {
+            "list" => TextAction::List,
+            "set" => TextAction::Set {
+                keyword: matches.value_of("keyword").unwrap().to_string(),
+                value: matches.value_of("value").unwrap().to_string(),
+                compress: matches.is_present("compress"),
+                language_tag: matches.value_of("language").map(String::from),
+                translated_keyword: matches.value_of("translated_keyword").map(String::from),
+            },
+            _ => TextAction::Delete { keyword: matches.value_of("keyword").unwrap().to_string() },
+        };
+        Ok(Command::Txts(
+            Text {
+                input_file_path: OsString::from(matches.value_of("input_file").unwrap()),
+                action,
+                output_file_path: matches.value_of("output_file").map(OsString::from),
+            }
+        ))
+    } else if let Some(exif) = arg_matches.subcommand_matches("exif") {
+        let orientation = match exif.value_of("orientation") {
+            Some(value) => Some(value.parse::<u16>().ok().and_then(Orientation::from_value)
+                .ok_or_else(|| clap::Error::with_description(
+                    &format!("invalid orientation '{}', expected 1 to 8", value), ErrorKind::InvalidValue))?),
+            None => None,
+        };
+        Ok(Command::Exfs(
+            ShowExif {
+                input_file_path: OsString::from(exif.value_of("input_file").unwrap()),
+                orientation,
+                model: exif.value_of("model").map(String::from),
+                output_file_path: exif.value_of("output_file").map(OsString::from),
+            }
+        ))
+    } else if let Some(strip) = arg_matches.subcommand_matches("strip-gps") {
+        Ok(Command::Sgps(
+            StripGps {
+                input_file_path: OsString::from(strip.value_of("input_file").unwrap()),
+                output_file_path: strip.value_of("output_file").map(OsString::from),
+            }
+        ))
+    } else if let Some(palette) = arg_matches.subcommand_matches("palette") {
+        Ok(Command::Pltt(
+            PrintPalette {
+                input_file_path: OsString::from(palette.value_of("input_file").unwrap()),
+            }
+        ))
+    } else if let Some(set_dpi) = arg_matches.subcommand_matches("set-dpi") {
+        let value = set_dpi.value_of("dpi").unwrap();
+        let dpi = value.parse::<f64>().ok().filter(|dpi| *dpi > 0.0 && dpi.is_finite())
+            .ok_or_else(|| clap::Error::with_description(
+                &format!("invalid resolution '{}', expected a positive number", value), ErrorKind::InvalidValue))?;
+        Ok(Command::Sdpi(
+            SetDpi {
+                input_file_path: OsString::from(set_dpi.value_of("input_file").unwrap()),
+                dpi,
+                output_file_path: set_dpi.value_of("output_file").map(OsString::from),
+            }
+        ))
+    } else if let Some(touch) = arg_matches.subcommand_matches("touch") {
+        Ok(Command::Tuch(
+            Touch {
+                input_file_path: OsString::from(touch.value_of("input_file").unwrap()),
+                output_file_path: touch.value_of("output_file").map(OsString::from),
+            }
+        ))
+    } else if let Some(frames) = arg_matches.subcommand_matches("frames") {
+        let (name, matches) = frames.subcommand();
+        let matches = matches.unwrap();
+        if name == "build" {
+            let number = |name: &str| matches.value_of(name).unwrap().parse::<u32>().map_err(|_| clap::Error::with_description(
+                &format!("invalid {} '{}', expected a whole number", name, matches.value_of(name).unwrap()), ErrorKind::InvalidValue));
+            let delay_ms = u16::try_from(number("delay")?).map_err(|_| clap::Error::with_description(
+                "delay can be at most 65535 ms", ErrorKind::InvalidValue))?;
+            return Ok(Command::Anim(
+                BuildAnimation {
+                    frame_file_paths: matches.values_of("frames").unwrap().map(OsString::from).collect(),
+                    delay_ms,
+                    num_plays: number("loops")?,
+                    crop: matches.is_present("crop"),
+                    output_file_path: OsString::from(matches.value_of("output_file").unwrap()),
+                }
+            ));
+        }
+        let action = match name {
+            "list" => FramesAction::List,
+            _ => FramesAction::Extract { out_dir: OsString::from(matches.value_of("out_dir").unwrap()) },
+        };
+        Ok(Command::Frms(
+            Frames {
+                input_file_path: OsString::from(matches.value_of("input_file").unwrap()),
+                action,
             }
         ))
     } else {
         panic!("This shouldn't happen {:?}", arg_matches);
     }
-}
\ No newline at end of file
+}
+
+fn parse_hiding_method(matches: &ArgMatches) -> Result<HidingMethod, clap::Error> {
+    match matches.value_of("method") {
+        Some("lsb") => {
+            let bits_per_channel = matches.value_of("bits").unwrap().parse::<u8>()
+                .map_err(|_| clap::Error::with_description("--bits must be a number from 1 to 8", ErrorKind::InvalidValue))?;
+            let channels = matches.value_of("channels").unwrap().chars()
+                .map(|c| Channel::from_char(c).ok_or_else(|| clap::Error::with_description(
+                    &format!("unknown channel '{}', expected r, g, b, y or a", c), ErrorKind::InvalidValue)))
+                .collect::<Result<Vec<Channel>, clap::Error>>()?;
+            Ok(HidingMethod::Lsb(LsbOptions { bits_per_channel, channels }))
+        },
+        _ => match matches.value_of("chunk_type") {
+            Some(chunk_type) => Ok(HidingMethod::Chunk(ChunkType::from_str(chunk_type).unwrap())),
+            None => Err(clap::Error::with_description(
+                "--chunk_type is required for the chunk method", ErrorKind::MissingRequiredArgument)),
+        },
+    }
+}
+
+fn parse_selection(matches: &ArgMatches) -> Result<Selection, clap::Error> {
+    if matches.is_present("all") {
+        return Ok(Selection::All);
+    }
+    match matches.value_of("index") {
+        Some(index) => index.parse::<usize>().map(Selection::Index).map_err(|_| clap::Error::with_description(
+            &format!("invalid chunk index '{}'", index), ErrorKind::InvalidValue)),
+        None => Ok(Selection::First),
+    }
+}
+
+fn parse_recipients(matches: &ArgMatches) -> Result<Vec<PublicKey>, clap::Error> {
+    matches.values_of("recipient").into_iter().flatten()
+        .map(|key| hex::decode_array::<32>(key).map(PublicKey::from).map_err(|err| clap::Error::with_description(
+            &format!("invalid recipient key '{}': {}", key, err), ErrorKind::InvalidValue)))
+        .collect()
+}
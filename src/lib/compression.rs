@@ -0,0 +1,146 @@
+use std::fmt::Display;
+
+use super::decoder;
+use super::encoder;
+
+pub const MAGIC: [u8; 4] = *b"PNGz";
+// magic, method and the uncompressed length (u32)
+const HEADER_LEN: usize = 4 + 1 + 4;
+
+// Payloads without the magic are stored as-is, which is how every version
+// before compression wrote them, so decoding falls back to the raw bytes.
+// A plain message can start with the magic by chance, so it only counts
+// when the rest of the header parses too.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompressionMethod {
+    Deflate,
+}
+
+impl CompressionMethod {
+    pub fn from_byte(byte: u8) -> Option<CompressionMethod> {
+        match byte {
+            0 => Some(CompressionMethod::Deflate),
+            _ => None,
+        }
+    }
+
+    pub fn as_byte(&self) -> u8 {
+        match self {
+            CompressionMethod::Deflate => 0,
+        }
+    }
+}
+
+pub fn is_compressed(data: &[u8]) -> bool {
+    data.len() >= HEADER_LEN && data.starts_with(&MAGIC) && CompressionMethod::from_byte(data[4]).is_some()
+}
+
+pub fn compress(data: &[u8], method: CompressionMethod, level: u32) -> Result<Vec<u8>, CompressionError> {
+    if data.len() > u32::MAX as usize {
+        return Err(CompressionError::PayloadTooLarge);
+    }
+    let mut compressed = Vec::with_capacity(HEADER_LEN + data.len() / 2);
+    compressed.extend_from_slice(&MAGIC);
+    compressed.push(method.as_byte());
+    compressed.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    match method {
+        CompressionMethod::Deflate => compressed.extend(encoder::deflate(data, level)),
+    }
+    Ok(compressed)
+}
+
+pub fn decompress(data: &[u8]) -> Result<Vec<u8>, CompressionError> {
+    if !data.starts_with(&MAGIC) || data.len() < HEADER_LEN {
+        return Err(CompressionError::InvalidHeader);
+    }
+    let method = CompressionMethod::from_byte(data[4]).ok_or(CompressionError::UnknownMethod(data[4]))?;
+    let length = u32::from_be_bytes([data[5], data[6], data[7], data[8]]) as usize;
+    let inflated = match method {
+        CompressionMethod::Deflate => decoder::inflate_limited(&data[HEADER_LEN..], length).map_err(|err| match err {
+            decoder::DecodeError::TooLarge(_) => CompressionError::TooLong { expected: length },
+            err => CompressionError::Inflate(format!("{:?}", err)),
+        })?,
+    };
+    if inflated.len() != length {
+        return Err(CompressionError::LengthMismatch { expected: length, actual: inflated.len() });
+    }
+    Ok(inflated)
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum CompressionError {
+    PayloadTooLarge,
+    InvalidHeader,
+    UnknownMethod(u8),
+    Inflate(String),
+    LengthMismatch { expected: usize, actual: usize },
+    TooLong { expected: usize },
+}
+
+impl Display for CompressionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CompressionError::PayloadTooLarge => write!(f, "payloads over 4 GiB can't be compressed"),
+            CompressionError::InvalidHeader => write!(f, "payload is not compressed or its header is damaged"),
+            CompressionError::UnknownMethod(method) => write!(f, "unknown compression method {}", method),
+            CompressionError::Inflate(reason) => write!(f, "couldn't inflate the payload: {}", reason),
+            CompressionError::LengthMismatch { expected, actual } => {
+                write!(f, "inflated payload is {} bytes, header says {}", actual, expected)
+            }
+            CompressionError::TooLong { expected } => {
+                write!(f, "inflated payload is longer than the {} bytes its header says", expected)
+            }
+        }
+    }
+}
+
+impl std::error::Error for CompressionError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compress_round_trip() {
+        let payload = "a fairly repetitive message ".repeat(40).into_bytes();
+        let compressed = compress(&payload, CompressionMethod::Deflate, 9).unwrap();
+        assert!(is_compressed(&compressed));
+        assert!(compressed.len() < payload.len() / 4);
+        assert_eq!(decompress(&compressed).unwrap(), payload);
+    }
+
+    #[test]
+    fn test_empty_payload() {
+        let compressed = compress(b"", CompressionMethod::Deflate, 6).unwrap();
+        assert_eq!(decompress(&compressed).unwrap(), b"");
+    }
+
+    #[test]
+    fn test_plain_payload_is_not_compressed() {
+        assert!(!is_compressed(b"This is where your secret message will be!"));
+        assert_eq!(decompress(b"plain"), Err(CompressionError::InvalidHeader));
+        // the magic alone doesn't make a header
+        assert!(!is_compressed(b"PNGz is a fine name for a tool"));
+        assert!(!is_compressed(b"PNGz"));
+    }
+
+    #[test]
+    fn test_damaged_header() {
+        let mut compressed = compress(b"hello hello hello", CompressionMethod::Deflate, 6).unwrap();
+        compressed[4] = 7;
+        assert_eq!(decompress(&compressed), Err(CompressionError::UnknownMethod(7)));
+
+        let mut compressed = compress(b"hello hello hello", CompressionMethod::Deflate, 6).unwrap();
+        compressed[8] += 1;
+        assert_eq!(decompress(&compressed), Err(CompressionError::LengthMismatch { expected: 18, actual: 17 }));
+    }
+
+    #[test]
+    fn test_inflation_stops_at_header_length() {
+        let payload = vec![0; 1 << 20];
+        let mut compressed = compress(&payload, CompressionMethod::Deflate, 9).unwrap();
+        compressed[5..9].copy_from_slice(&1000u32.to_be_bytes());
+        assert_eq!(decompress(&compressed), Err(CompressionError::TooLong { expected: 1000 }));
+    }
+}
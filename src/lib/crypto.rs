@@ -0,0 +1,206 @@
+use std::fmt::Display;
+
+use argon2::{Algorithm, Argon2, Params, Version};
+use chacha20poly1305::aead::rand_core::RngCore;
+use chacha20poly1305::aead::{Aead, KeyInit, OsRng, Payload};
+use chacha20poly1305::{XChaCha20Poly1305, XNonce};
+
+pub const MAGIC: [u8; 4] = *b"PNGe";
+const VERSION: u8 = 1;
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 24;
+// magic, version, three u32 KDF parameters, salt and nonce
+const HEADER_LEN: usize = 4 + 1 + 12 + SALT_LEN + NONCE_LEN;
+
+// Argon2id cost parameters, stored in the header so they can be raised
+// later without breaking older messages
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KdfParams {
+    pub memory_kib: u32,
+    pub iterations: u32,
+    pub parallelism: u32,
+}
+
+impl KdfParams {
+    // the parameters come from the message header, so they're capped before
+    // a crafted message can ask for terabytes of memory or hours of hashing
+    pub const MAX: KdfParams = KdfParams { memory_kib: 1 << 20, iterations: 10, parallelism: 16 };
+
+    fn check(&self) -> Result<(), CryptoError> {
+        let max = KdfParams::MAX;
+        match self.memory_kib <= max.memory_kib && self.iterations <= max.iterations && self.parallelism <= max.parallelism {
+            true => Ok(()),
+            false => Err(CryptoError::CostTooHigh(*self)),
+        }
+    }
+}
+
+impl Default for KdfParams {
+    fn default() -> Self {
+        KdfParams {
+            memory_kib: Params::DEFAULT_M_COST,
+            iterations: Params::DEFAULT_T_COST,
+            parallelism: Params::DEFAULT_P_COST,
+        }
+    }
+}
+
+fn derive_key(password: &str, salt: &[u8], params: &KdfParams) -> Result<[u8; 32], CryptoError> {
+    let argon_params = Params::new(params.memory_kib, params.iterations, params.parallelism, Some(32))
+        .map_err(|err| CryptoError::Kdf(err.to_string()))?;
+    let mut key = [0; 32];
+    Argon2::new(Algorithm::Argon2id, Version::V0x13, argon_params)
+        .hash_password_into(password.as_bytes(), salt, &mut key)
+        .map_err(|err| CryptoError::Kdf(err.to_string()))?;
+    Ok(key)
+}
+
+pub fn is_encrypted(data: &[u8]) -> bool {
+    data.starts_with(&MAGIC)
+}
+
+pub fn encrypt(plaintext: &[u8], password: &str) -> Result<Vec<u8>, CryptoError> {
+    encrypt_with_params(plaintext, password, &KdfParams::default())
+}
+
+pub fn encrypt_with_params(plaintext: &[u8], password: &str, params: &KdfParams) -> Result<Vec<u8>, CryptoError> {
+    // anything above the limits couldn't be decrypted again
+    params.check()?;
+    let mut salt = [0; SALT_LEN];
+    let mut nonce = [0; NONCE_LEN];
+    OsRng.fill_bytes(&mut salt);
+    OsRng.fill_bytes(&mut nonce);
+
+    let mut header = Vec::with_capacity(HEADER_LEN);
+    header.extend_from_slice(&MAGIC);
+    header.push(VERSION);
+    header.extend_from_slice(&params.memory_kib.to_be_bytes());
+    header.extend_from_slice(&params.iterations.to_be_bytes());
+    header.extend_from_slice(&params.parallelism.to_be_bytes());
+    header.extend_from_slice(&salt);
+    header.extend_from_slice(&nonce);
+
+    let key = derive_key(password, &salt, params)?;
+    let cipher = XChaCha20Poly1305::new(&key.into());
+    // the header is authenticated too, so its parameters can't be swapped out
+    let ciphertext = cipher
+        .encrypt(XNonce::from_slice(&nonce), Payload { msg: plaintext, aad: &header })
+        .map_err(|_| CryptoError::Authentication)?;
+    header.extend(ciphertext);
+    Ok(header)
+}
+
+pub fn decrypt(data: &[u8], password: &str) -> Result<Vec<u8>, CryptoError> {
+    if !is_encrypted(data) || data.len() < HEADER_LEN {
+        return Err(CryptoError::InvalidHeader);
+    }
+    if data[4] != VERSION {
+        return Err(CryptoError::UnsupportedVersion(data[4]));
+    }
+    let read_u32 = |at: usize| u32::from_be_bytes([data[at], data[at + 1], data[at + 2], data[at + 3]]);
+    let params = KdfParams { memory_kib: read_u32(5), iterations: read_u32(9), parallelism: read_u32(13) };
+    params.check()?;
+    let salt = &data[17..17 + SALT_LEN];
+    let nonce = &data[17 + SALT_LEN..HEADER_LEN];
+
+    let key = derive_key(password, salt, &params)?;
+    let cipher = XChaCha20Poly1305::new(&key.into());
+    cipher
+        .decrypt(XNonce::from_slice(nonce), Payload { msg: &data[HEADER_LEN..], aad: &data[..HEADER_LEN] })
+        .map_err(|_| CryptoError::Authentication)
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum CryptoError {
+    InvalidHeader,
+    UnsupportedVersion(u8),
+    Kdf(String),
+    CostTooHigh(KdfParams),
+    Authentication,
+}
+
+impl Display for CryptoError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CryptoError::InvalidHeader => write!(f, "message is not encrypted or its header is damaged"),
+            CryptoError::UnsupportedVersion(version) => write!(f, "unsupported encryption format version {}", version),
+            CryptoError::Kdf(reason) => write!(f, "key derivation failed: {}", reason),
+            CryptoError::CostTooHigh(params) => write!(
+                f,
+                "key derivation asks for {} KiB, {} iterations and {} lanes, the limits are {} KiB, {} and {}",
+                params.memory_kib,
+                params.iterations,
+                params.parallelism,
+                KdfParams::MAX.memory_kib,
+                KdfParams::MAX.iterations,
+                KdfParams::MAX.parallelism
+            ),
+            CryptoError::Authentication => write!(f, "wrong password or the message was tampered with"),
+        }
+    }
+}
+
+impl std::error::Error for CryptoError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // cheap parameters so the tests stay fast
+    const TEST_PARAMS: KdfParams = KdfParams { memory_kib: 64, iterations: 1, parallelism: 1 };
+
+    #[test]
+    fn test_encrypt_decrypt() {
+        let sealed = encrypt_with_params(b"meet at noon", "hunter2", &TEST_PARAMS).unwrap();
+        assert!(is_encrypted(&sealed));
+        assert_eq!(sealed.len(), HEADER_LEN + 12 + 16);
+        assert!(!sealed.windows(12).any(|window| window == b"meet at noon"));
+        assert_eq!(decrypt(&sealed, "hunter2").unwrap(), b"meet at noon");
+    }
+
+    #[test]
+    fn test_wrong_password() {
+        let sealed = encrypt_with_params(b"meet at noon", "hunter2", &TEST_PARAMS).unwrap();
+        assert_eq!(decrypt(&sealed, "hunter3"), Err(CryptoError::Authentication));
+    }
+
+    #[test]
+    fn test_tampering_is_detected() {
+        let mut sealed = encrypt_with_params(b"meet at noon", "hunter2", &TEST_PARAMS).unwrap();
+        let last = sealed.len() - 1;
+        sealed[last] ^= 1;
+        assert_eq!(decrypt(&sealed, "hunter2"), Err(CryptoError::Authentication));
+
+        // raising the stored cost parameters changes the derived key
+        let mut sealed = encrypt_with_params(b"meet at noon", "hunter2", &TEST_PARAMS).unwrap();
+        sealed[12] += 1;
+        assert_eq!(decrypt(&sealed, "hunter2"), Err(CryptoError::Authentication));
+    }
+
+    #[test]
+    fn test_fresh_salt_and_nonce() {
+        let first = encrypt_with_params(b"same", "same", &TEST_PARAMS).unwrap();
+        let second = encrypt_with_params(b"same", "same", &TEST_PARAMS).unwrap();
+        assert_ne!(first[17..HEADER_LEN], second[17..HEADER_LEN]);
+    }
+
+    #[test]
+    fn test_invalid_header() {
+        assert_eq!(decrypt(b"plain text message", "pw"), Err(CryptoError::InvalidHeader));
+        let mut sealed = encrypt_with_params(b"x", "pw", &TEST_PARAMS).unwrap();
+        sealed[4] = 9;
+        assert_eq!(decrypt(&sealed, "pw"), Err(CryptoError::UnsupportedVersion(9)));
+    }
+
+    #[test]
+    fn test_cost_limits() {
+        let sealed = encrypt_with_params(b"x", "pw", &TEST_PARAMS).unwrap();
+        for (at, value) in [(5, (1 << 20) + 1), (9, 11), (13, 17), (5, u32::MAX)] {
+            let mut forged = sealed.clone();
+            forged[at..at + 4].copy_from_slice(&u32::to_be_bytes(value));
+            assert!(matches!(decrypt(&forged, "pw"), Err(CryptoError::CostTooHigh(_))));
+        }
+        let too_slow = KdfParams { iterations: 11, ..TEST_PARAMS };
+        assert_eq!(encrypt_with_params(b"x", "pw", &too_slow), Err(CryptoError::CostTooHigh(too_slow)));
+    }
+}
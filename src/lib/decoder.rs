@@ -0,0 +1,265 @@
+use std::io::Read;
+
+use flate2::read::ZlibDecoder;
+
+use super::adam7;
+use super::filter::{unfilter_rows, FilterError};
+use super::ihdr::{ColorType, Ihdr, IhdrError, InterlaceMethod};
+use super::image::Image;
+use super::png::Png;
+
+// the inflated IDAT stream, which can be no longer than IHDR says
+pub fn idat_stream(png: &Png, ihdr: &Ihdr) -> Result<Vec<u8>, DecodeError> {
+    let mut compressed = Vec::new();
+    for chunk in png.chunks().iter().filter(|chunk| &chunk.chunk_type().bytes() == b"IDAT") {
+        compressed.extend_from_slice(chunk.data());
+    }
+    if compressed.is_empty() {
+        return Err(DecodeError::MissingIdat);
+    }
+    inflate_scanlines(ihdr, &compressed)
+}
+
+// bytes of filtered scanlines an image of this header inflates to, None
+// when a crafted size doesn't fit in memory at all
+fn scanlines_len(ihdr: &Ihdr) -> Option<usize> {
+    let pass_len = |width: u32, height: u32| match width == 0 || height == 0 {
+        // empty passes carry no filter bytes
+        true => Some(0),
+        false => (width as usize).checked_mul(ihdr.bits_per_pixel())?.div_ceil(8).checked_add(1)?.checked_mul(height as usize),
+    };
+    match ihdr.interlace_method {
+        InterlaceMethod::None => pass_len(ihdr.width, ihdr.height),
+        InterlaceMethod::Adam7 => adam7::PASSES.iter().try_fold(0usize, |total, pass| {
+            let (width, height) = pass.size(ihdr.width, ihdr.height);
+            total.checked_add(pass_len(width, height)?)
+        }),
+    }
+}
+
+fn inflate_scanlines(ihdr: &Ihdr, compressed: &[u8]) -> Result<Vec<u8>, DecodeError> {
+    let expected = scanlines_len(ihdr).ok_or(DecodeError::SizeOverflow)?;
+    inflate_limited(compressed, expected)
+}
+
+pub fn inflate(compressed: &[u8]) -> Result<Vec<u8>, DecodeError> {
+    let mut inflated = Vec::new();
+    ZlibDecoder::new(compressed)
+        .read_to_end(&mut inflated)
+        .map_err(|err| DecodeError::Inflate(err.to_string()))?;
+    Ok(inflated)
+}
+
+// stops as soon as the output passes `limit`, so a few bytes of crafted
+// input can't inflate into gigabytes
+pub fn inflate_limited(compressed: &[u8], limit: usize) -> Result<Vec<u8>, DecodeError> {
+    let mut inflated = Vec::new();
+    ZlibDecoder::new(compressed)
+        .take((limit as u64).saturating_add(1))
+        .read_to_end(&mut inflated)
+        .map_err(|err| DecodeError::Inflate(err.to_string()))?;
+    match inflated.len() > limit {
+        true => Err(DecodeError::TooLarge(limit)),
+        false => Ok(inflated),
+    }
+}
+
+pub fn decode(png: &Png) -> Result<Image, DecodeError> {
+    let ihdr = png.ihdr().map_err(DecodeError::Ihdr)?;
+    let data = idat_stream(png, &ihdr)?;
+    decode_scanlines(png, &ihdr, data)
+}
+
+// an image stored outside of IDAT, such as an APNG frame, sized by `ihdr`
+// and sharing the palette and transparency of `png`
+pub fn decode_subimage(png: &Png, ihdr: &Ihdr, compressed: &[u8]) -> Result<Image, DecodeError> {
+    decode_scanlines(png, ihdr, inflate_scanlines(ihdr, compressed)?)
+}
+
+fn decode_scanlines(png: &Png, ihdr: &Ihdr, data: Vec<u8>) -> Result<Image, DecodeError> {
+    let pixels = match ihdr.interlace_method {
+        InterlaceMethod::None => unfilter_rows(&data, ihdr.filter_bpp(), ihdr.row_bytes(ihdr.width), ihdr.height as usize)
+            .map_err(DecodeError::Filter)?,
+        InterlaceMethod::Adam7 => adam7::deinterlace(&data, ihdr).map_err(DecodeError::Filter)?,
+    };
+    image_from_parts(png, ihdr, pixels)
+}
+
+// one image per Adam7 pass, each showing everything received so far;
+// a non interlaced image has a single "pass"
+pub fn decode_progressive(png: &Png) -> Result<Vec<Image>, DecodeError> {
+    let ihdr = png.ihdr().map_err(DecodeError::Ihdr)?;
+    if ihdr.interlace_method == InterlaceMethod::None {
+        return Ok(vec![decode(png)?]);
+    }
+    let data = idat_stream(png, &ihdr)?;
+    adam7::progressive_previews(&data, &ihdr)
+        .map_err(DecodeError::Filter)?
+        .into_iter()
+        .map(|pixels| image_from_parts(png, &ihdr, pixels))
+        .collect()
+}
+
+fn image_from_parts(png: &Png, ihdr: &Ihdr, pixels: Vec<u8>) -> Result<Image, DecodeError> {
+    let palette = png.chunk_by_type("PLTE").map(|chunk| chunk.data().to_vec());
+    if ihdr.color_type == ColorType::Indexed && palette.is_none() {
+        return Err(DecodeError::MissingPalette);
+    }
+    Ok(Image {
+        width: ihdr.width,
+        height: ihdr.height,
+        color_type: ihdr.color_type,
+        bit_depth: ihdr.bit_depth,
+        pixels,
+        palette,
+        transparency: png.chunk_by_type("tRNS").map(|chunk| chunk.data().to_vec()),
+    })
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum DecodeError {
+    Ihdr(IhdrError),
+    MissingIdat,
+    MissingPalette,
+    Inflate(String),
+    TooLarge(usize),
+    SizeOverflow,
+    Filter(FilterError),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk::Chunk;
+    use crate::chunk_type::ChunkType;
+    use flate2::write::ZlibEncoder;
+    use flate2::Compression;
+    use std::io::Write;
+    use std::str::FromStr;
+
+    fn chunk(chunk_type: &str, data: Vec<u8>) -> Chunk {
+        Chunk::new(ChunkType::from_str(chunk_type).unwrap(), data)
+    }
+
+    fn zlib(data: &[u8]) -> Vec<u8> {
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    fn png_from(ihdr: Ihdr, extra: Vec<Chunk>, scanlines: &[u8]) -> Png {
+        let compressed = zlib(scanlines);
+        let (first, second) = compressed.split_at(compressed.len() / 2);
+        let mut chunks = vec![ihdr.to_chunk()];
+        chunks.extend(extra);
+        chunks.push(chunk("IDAT", first.to_vec()));
+        chunks.push(chunk("IDAT", second.to_vec()));
+        chunks.push(chunk("IEND", Vec::new()));
+        Png::from_chunks(chunks).unwrap()
+    }
+
+    #[test]
+    fn test_decode_rgb_with_all_filters() {
+        let ihdr = Ihdr::new(2, 5, 8, ColorType::Rgb).unwrap();
+        #[rustfmt::skip]
+        let scanlines = [
+            0, 10, 20, 30, 40, 50, 60,      // None
+            1, 11, 21, 31, 1, 1, 1,         // Sub
+            2, 1, 1, 1, 1, 1, 1,            // Up
+            3, 6, 11, 16, 7, 7, 7,          // Average
+            4, 1, 1, 1, 1, 1, 1,            // Paeth
+        ];
+        let image = decode(&png_from(ihdr, Vec::new(), &scanlines)).unwrap();
+        assert_eq!((image.width, image.height), (2, 5));
+        assert_eq!(
+            image.pixels,
+            vec![
+                10, 20, 30, 40, 50, 60,
+                11, 21, 31, 12, 22, 32,
+                12, 22, 32, 13, 23, 33,
+                12, 22, 32, 19, 29, 39,
+                13, 23, 33, 20, 30, 40,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_decode_palette_image() {
+        let ihdr = Ihdr::new(3, 1, 2, ColorType::Indexed).unwrap();
+        let plte = chunk("PLTE", vec![255, 0, 0, 0, 255, 0, 0, 0, 255]);
+        let trns = chunk("tRNS", vec![0]);
+        let image = decode(&png_from(ihdr, vec![plte, trns], &[0, 0b00_01_10_00])).unwrap();
+        assert_eq!(
+            image.to_rgba8().unwrap(),
+            vec![255, 0, 0, 0, 0, 255, 0, 255, 0, 0, 255, 255]
+        );
+    }
+
+    #[test]
+    fn test_decode_interlaced_image() {
+        let mut ihdr = Ihdr::new(3, 3, 8, ColorType::GrayscaleAlpha).unwrap();
+        ihdr.interlace_method = InterlaceMethod::Adam7;
+        // pixel (x, y) has gray 10 * y + x and alpha 255; pass 7 uses the Sub filter
+        #[rustfmt::skip]
+        let scanlines = [
+            0, 0, 255,
+            0, 2, 255,
+            0, 20, 255, 22, 255,
+            0, 1, 255,
+            0, 21, 255,
+            1, 10, 255, 1, 0, 1, 0,
+        ];
+        let png = png_from(ihdr, Vec::new(), &scanlines);
+        let image = decode(&png).unwrap();
+        assert_eq!(
+            image.pixels,
+            vec![0, 255, 1, 255, 2, 255, 10, 255, 11, 255, 12, 255, 20, 255, 21, 255, 22, 255]
+        );
+
+        let previews = decode_progressive(&png).unwrap();
+        assert_eq!(previews.len(), 7);
+        assert_eq!(previews[6], image);
+        assert_eq!(previews[0].pixels, [0, 255].repeat(9));
+    }
+
+    #[test]
+    fn test_decode_progressive_non_interlaced() {
+        let ihdr = Ihdr::new(1, 1, 8, ColorType::Grayscale).unwrap();
+        let previews = decode_progressive(&png_from(ihdr, Vec::new(), &[0, 42])).unwrap();
+        assert_eq!(previews.len(), 1);
+        assert_eq!(previews[0].pixels, vec![42]);
+    }
+
+    #[test]
+    fn test_decode_errors() {
+        let ihdr = Ihdr::new(2, 2, 8, ColorType::Grayscale).unwrap();
+        let png = png_from(ihdr.clone(), Vec::new(), &[0, 1, 2]);
+        assert_eq!(decode(&png), Err(DecodeError::Filter(FilterError::TruncatedData)));
+
+        let png = png_from(ihdr, Vec::new(), &[9, 1, 2, 0, 1, 2]);
+        assert_eq!(decode(&png), Err(DecodeError::Filter(FilterError::InvalidFilterType(9))));
+
+        let ihdr = Ihdr::new(1, 1, 8, ColorType::Indexed).unwrap();
+        let png = png_from(ihdr, Vec::new(), &[0, 0]);
+        assert_eq!(decode(&png), Err(DecodeError::MissingPalette));
+
+        let png = Png::from_chunks_unchecked(vec![Ihdr::new(1, 1, 8, ColorType::Rgb).unwrap().to_chunk()]);
+        assert_eq!(decode(&png), Err(DecodeError::MissingIdat));
+    }
+
+    #[test]
+    fn test_inflation_is_bounded_by_ihdr() {
+        // 1x1 gray is two bytes of scanlines, however much the stream holds
+        let ihdr = Ihdr::new(1, 1, 8, ColorType::Grayscale).unwrap();
+        let png = png_from(ihdr, Vec::new(), &[0; 1000]);
+        assert_eq!(decode(&png), Err(DecodeError::TooLarge(2)));
+
+        let mut ihdr = Ihdr::new(3, 3, 8, ColorType::GrayscaleAlpha).unwrap();
+        ihdr.interlace_method = InterlaceMethod::Adam7;
+        assert_eq!(scanlines_len(&ihdr), Some(24));
+
+        let huge = Ihdr::new(0x7fff_ffff, 0x7fff_ffff, 16, ColorType::Rgba).unwrap();
+        let png = png_from(huge, Vec::new(), &[0; 4]);
+        assert_eq!(decode(&png), Err(DecodeError::SizeOverflow));
+    }
+}
@@ -0,0 +1,231 @@
+use std::io::Write;
+
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
+
+use super::chunk::{chunk, Chunk};
+use super::filter::{filter_rows, filter_scanline, FilterType};
+use super::ihdr::{ColorType, Ihdr, IhdrError};
+use super::image::Image;
+use super::png::{Png, PngError};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FilterStrategy {
+    // the same filter on every scanline
+    Fixed(FilterType),
+    // per scanline, the filter whose output has the smallest sum of
+    // absolute values when read as signed bytes
+    MinSumAbs,
+    // per scanline, the filter whose output deflates smallest
+    BruteForce,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EncodeOptions {
+    pub filter: FilterStrategy,
+    // zlib level, 0 (store) to 9 (best)
+    pub compression_level: u32,
+    pub idat_size: usize,
+}
+
+impl Default for EncodeOptions {
+    fn default() -> Self {
+        EncodeOptions { filter: FilterStrategy::MinSumAbs, compression_level: 6, idat_size: 8192 }
+    }
+}
+
+pub fn deflate(data: &[u8], level: u32) -> Vec<u8> {
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::new(level.min(9)));
+    encoder.write_all(data).expect("writing to a Vec can't fail");
+    encoder.finish().expect("writing to a Vec can't fail")
+}
+
+fn sum_abs(filtered: &[u8]) -> u64 {
+    filtered.iter().map(|&byte| (byte as i8).unsigned_abs() as u64).sum()
+}
+
+pub fn filter_with_strategy(pixels: &[u8], bpp: usize, row_bytes: usize, strategy: FilterStrategy, level: u32) -> Vec<u8> {
+    if let FilterStrategy::Fixed(filter) = strategy {
+        return filter_rows(pixels, bpp, row_bytes, filter);
+    }
+    let height = pixels.len().checked_div(row_bytes).unwrap_or(0);
+    let mut out = Vec::with_capacity((row_bytes + 1) * height);
+    let zero_row = vec![0; row_bytes];
+    let mut candidates: Vec<Vec<u8>> = vec![Vec::with_capacity(row_bytes); FilterType::ALL.len()];
+    for row in 0..height {
+        let prev = if row == 0 { &zero_row[..] } else { &pixels[(row - 1) * row_bytes..row * row_bytes] };
+        let current = &pixels[row * row_bytes..(row + 1) * row_bytes];
+        for (filter, candidate) in FilterType::ALL.iter().zip(candidates.iter_mut()) {
+            candidate.clear();
+            filter_scanline(*filter, bpp, prev, current, candidate);
+        }
+        let best = match strategy {
+            FilterStrategy::BruteForce => (0..candidates.len())
+                .min_by_key(|&i| (deflate(&candidates[i], level).len(), sum_abs(&candidates[i])))
+                .unwrap(),
+            _ => (0..candidates.len()).min_by_key(|&i| sum_abs(&candidates[i])).unwrap(),
+        };
+        out.push(FilterType::ALL[best].as_byte());
+        out.extend_from_slice(&candidates[best]);
+    }
+    out
+}
+
+// filtered and deflated image data, split into IDAT chunks
+pub fn idat_chunks(image: &Image, ihdr: &Ihdr, options: &EncodeOptions) -> Vec<Chunk> {
+    let filtered = filter_with_strategy(
+        &image.pixels,
+        ihdr.filter_bpp(),
+        image.row_bytes(),
+        options.filter,
+        options.compression_level,
+    );
+    deflate(&filtered, options.compression_level)
+        .chunks(options.idat_size.max(1))
+        .map(|data| chunk("IDAT", data.to_vec()))
+        .collect()
+}
+
+pub fn encode(image: &Image, options: &EncodeOptions) -> Result<Png, EncodeError> {
+    let ihdr = Ihdr::new(image.width, image.height, image.bit_depth, image.color_type).map_err(EncodeError::Ihdr)?;
+    if image.pixels.len() != image.row_bytes() * image.height as usize {
+        return Err(EncodeError::PixelBufferLength);
+    }
+
+    let mut chunks = vec![ihdr.to_chunk()];
+    match (&image.palette, image.color_type) {
+        (Some(palette), ColorType::Indexed | ColorType::Rgb | ColorType::Rgba) => {
+            if palette.is_empty() || palette.len() % 3 != 0 || palette.len() > 256 * 3 {
+                return Err(EncodeError::InvalidPalette);
+            }
+            chunks.push(chunk("PLTE", palette.clone()));
+        }
+        (None, ColorType::Indexed) => return Err(EncodeError::MissingPalette),
+        _ => {}
+    }
+    if let Some(transparency) = &image.transparency {
+        chunks.push(chunk("tRNS", transparency.clone()));
+    }
+    chunks.extend(idat_chunks(image, &ihdr, options));
+    chunks.push(chunk("IEND", Vec::new()));
+    Png::from_chunks(chunks).map_err(EncodeError::Png)
+}
+
+#[derive(Debug)]
+pub enum EncodeError {
+    Ihdr(IhdrError),
+    PixelBufferLength,
+    MissingPalette,
+    InvalidPalette,
+    Png(PngError),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn gradient(width: u32, height: u32, color_type: ColorType, bit_depth: u8) -> Image {
+        let row_bytes = (width as usize * color_type.channels() * bit_depth as usize).div_ceil(8);
+        let pixels = (0..row_bytes * height as usize).map(|i| (i * 7 % 256) as u8).collect();
+        Image::new(width, height, color_type, bit_depth, pixels)
+    }
+
+    fn round_trip(image: &Image, options: &EncodeOptions) -> Image {
+        let png = encode(image, options).unwrap();
+        let bytes = png.as_bytes();
+        Png::try_from(&bytes[..]).unwrap().decode_image().unwrap()
+    }
+
+    #[test]
+    fn test_encode_color_types() {
+        for color_type in [ColorType::Grayscale, ColorType::Rgb, ColorType::Rgba, ColorType::GrayscaleAlpha] {
+            for bit_depth in [8, 16] {
+                let image = gradient(13, 7, color_type, bit_depth);
+                assert_eq!(round_trip(&image, &EncodeOptions::default()), image);
+            }
+        }
+    }
+
+    #[test]
+    fn test_encode_palette() {
+        let mut image = gradient(5, 4, ColorType::Indexed, 8);
+        image.pixels.iter_mut().for_each(|index| *index %= 3);
+        image.palette = Some(vec![255, 0, 0, 0, 255, 0, 0, 0, 255]);
+        image.transparency = Some(vec![0, 128]);
+        assert_eq!(round_trip(&image, &EncodeOptions::default()), image);
+
+        let mut image = gradient(9, 3, ColorType::Indexed, 2);
+        image.palette = Some(vec![0; 12]);
+        assert_eq!(round_trip(&image, &EncodeOptions::default()), image);
+    }
+
+    fn idat_length(png: &Png) -> usize {
+        png.chunks().iter().filter(|c| &c.chunk_type().bytes() == b"IDAT").map(|c| c.data().len()).sum()
+    }
+
+    #[test]
+    fn test_encode_with_each_filter() {
+        let image = gradient(10, 10, ColorType::Rgb, 8);
+        for filter in FilterType::ALL {
+            let options = EncodeOptions { filter: FilterStrategy::Fixed(filter), ..EncodeOptions::default() };
+            assert_eq!(round_trip(&image, &options), image);
+        }
+    }
+
+    #[test]
+    fn test_encode_with_adaptive_strategies() {
+        let image = gradient(24, 24, ColorType::Rgba, 16);
+        for filter in [FilterStrategy::MinSumAbs, FilterStrategy::BruteForce] {
+            let options = EncodeOptions { filter, ..EncodeOptions::default() };
+            assert_eq!(round_trip(&image, &options), image);
+        }
+    }
+
+    #[test]
+    fn test_min_sum_abs_selection() {
+        // a horizontal ramp is all ones under Sub, and a copy of the row above is all zeroes under Up
+        let pixels: Vec<u8> = (0..16).chain(0..16).collect();
+        let filtered = filter_with_strategy(&pixels, 1, 16, FilterStrategy::MinSumAbs, 6);
+        assert_eq!(filtered[0], FilterType::Sub.as_byte());
+        assert_eq!(filtered[17], FilterType::Up.as_byte());
+        assert!(filtered[18..].iter().all(|&byte| byte == 0));
+    }
+
+    #[test]
+    fn test_brute_force_is_not_larger_than_no_filter() {
+        let pixels: Vec<u8> = (0..64 * 64 * 3).map(|i| ((i / 3) % 64 + (i / 192) * 2) as u8).collect();
+        let image = Image::new(64, 64, ColorType::Rgb, 8, pixels);
+        let size_with = |filter| {
+            idat_length(&encode(&image, &EncodeOptions { filter, ..EncodeOptions::default() }).unwrap())
+        };
+        assert!(size_with(FilterStrategy::BruteForce) <= size_with(FilterStrategy::Fixed(FilterType::None)));
+    }
+
+    #[test]
+    fn test_encode_splits_idat() {
+        let image = gradient(32, 32, ColorType::Rgba, 8);
+        let options = EncodeOptions { compression_level: 0, idat_size: 1000, ..EncodeOptions::default() };
+        let png = encode(&image, &options).unwrap();
+        let idats: Vec<&Chunk> = png.chunks().iter().filter(|c| &c.chunk_type().bytes() == b"IDAT").collect();
+        assert!(idats.len() > 4);
+        assert!(idats.iter().all(|c| c.length() <= 1000));
+        assert!(png.ordering_violations().is_empty());
+        assert_eq!(png.decode_image().unwrap(), image);
+    }
+
+    #[test]
+    fn test_encode_errors() {
+        let mut image = gradient(4, 4, ColorType::Rgb, 8);
+        image.pixels.pop();
+        assert!(matches!(encode(&image, &EncodeOptions::default()), Err(EncodeError::PixelBufferLength)));
+
+        let image = gradient(4, 4, ColorType::Indexed, 8);
+        assert!(matches!(encode(&image, &EncodeOptions::default()), Err(EncodeError::MissingPalette)));
+
+        let image = gradient(4, 4, ColorType::Rgb, 4);
+        assert!(matches!(
+            encode(&image, &EncodeOptions::default()),
+            Err(EncodeError::Ihdr(IhdrError::InvalidBitDepth(ColorType::Rgb, 4)))
+        ));
+    }
+}
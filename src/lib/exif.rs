@@ -0,0 +1,679 @@
+use std::fmt::Display;
+use std::str::FromStr;
+
+use super::chunk::{chunk, Chunk};
+use super::png::{ChunkPlacement, Png};
+
+pub const EXIF_CHUNK: &str = "eXIf";
+
+// IFD0 tags
+pub const TAG_MAKE: u16 = 0x010f;
+pub const TAG_MODEL: u16 = 0x0110;
+pub const TAG_ORIENTATION: u16 = 0x0112;
+pub const TAG_DATE_TIME: u16 = 0x0132;
+// pointers to the sub-IFDs
+pub const TAG_EXIF_IFD: u16 = 0x8769;
+pub const TAG_GPS_IFD: u16 = 0x8825;
+pub const TAG_INTEROP_IFD: u16 = 0xa005;
+// Exif IFD tags
+pub const TAG_DATE_TIME_ORIGINAL: u16 = 0x9003;
+pub const TAG_DATE_TIME_DIGITIZED: u16 = 0x9004;
+// GPS IFD tags
+pub const TAG_GPS_LATITUDE_REF: u16 = 0x0001;
+pub const TAG_GPS_LATITUDE: u16 = 0x0002;
+pub const TAG_GPS_LONGITUDE_REF: u16 = 0x0003;
+pub const TAG_GPS_LONGITUDE: u16 = 0x0004;
+pub const TAG_GPS_ALTITUDE_REF: u16 = 0x0005;
+pub const TAG_GPS_ALTITUDE: u16 = 0x0006;
+
+const POINTER_TAGS: [u16; 3] = [TAG_EXIF_IFD, TAG_GPS_IFD, TAG_INTEROP_IFD];
+// IFD0 -> Exif -> Interop is as deep as EXIF goes
+const MAX_DEPTH: usize = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ByteOrder {
+    LittleEndian,
+    BigEndian,
+}
+
+impl ByteOrder {
+    fn u16(&self, bytes: &[u8]) -> u16 {
+        let bytes = [bytes[0], bytes[1]];
+        match self {
+            ByteOrder::LittleEndian => u16::from_le_bytes(bytes),
+            ByteOrder::BigEndian => u16::from_be_bytes(bytes),
+        }
+    }
+
+    fn u32(&self, bytes: &[u8]) -> u32 {
+        let bytes = [bytes[0], bytes[1], bytes[2], bytes[3]];
+        match self {
+            ByteOrder::LittleEndian => u32::from_le_bytes(bytes),
+            ByteOrder::BigEndian => u32::from_be_bytes(bytes),
+        }
+    }
+
+    fn u16_bytes(&self, value: u16) -> [u8; 2] {
+        match self {
+            ByteOrder::LittleEndian => value.to_le_bytes(),
+            ByteOrder::BigEndian => value.to_be_bytes(),
+        }
+    }
+
+    fn u32_bytes(&self, value: u32) -> [u8; 4] {
+        match self {
+            ByteOrder::LittleEndian => value.to_le_bytes(),
+            ByteOrder::BigEndian => value.to_be_bytes(),
+        }
+    }
+}
+
+// TIFF field values; types without a variant of their own are kept as raw
+// bytes in the file's byte order
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Value {
+    Byte(Vec<u8>),
+    Ascii(String),
+    Short(Vec<u16>),
+    Long(Vec<u32>),
+    Rational(Vec<(u32, u32)>),
+    Undefined(Vec<u8>),
+    SLong(Vec<i32>),
+    SRational(Vec<(i32, i32)>),
+    Other { type_id: u16, count: u32, data: Vec<u8> },
+    Ifd(Vec<Entry>),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+    pub tag: u16,
+    pub value: Value,
+}
+
+fn type_size(type_id: u16) -> Option<usize> {
+    match type_id {
+        1 | 2 | 6 | 7 => Some(1),
+        3 | 8 => Some(2),
+        4 | 9 | 11 | 13 => Some(4),
+        5 | 10 | 12 => Some(8),
+        _ => None,
+    }
+}
+
+impl Value {
+    fn type_id(&self) -> u16 {
+        match self {
+            Value::Byte(_) => 1,
+            Value::Ascii(_) => 2,
+            Value::Short(_) => 3,
+            Value::Long(_) | Value::Ifd(_) => 4,
+            Value::Rational(_) => 5,
+            Value::Undefined(_) => 7,
+            Value::SLong(_) => 9,
+            Value::SRational(_) => 10,
+            Value::Other { type_id, .. } => *type_id,
+        }
+    }
+
+    // count and data as written; sub-IFDs get their offset patched in later
+    fn encode(&self, order: ByteOrder) -> (u32, Vec<u8>) {
+        match self {
+            Value::Byte(bytes) | Value::Undefined(bytes) => (bytes.len() as u32, bytes.clone()),
+            Value::Ascii(s) => {
+                let mut bytes = s.as_bytes().to_vec();
+                bytes.push(0);
+                (bytes.len() as u32, bytes)
+            }
+            Value::Short(values) => (values.len() as u32, values.iter().flat_map(|&v| order.u16_bytes(v)).collect()),
+            Value::Long(values) => (values.len() as u32, values.iter().flat_map(|&v| order.u32_bytes(v)).collect()),
+            Value::SLong(values) => {
+                (values.len() as u32, values.iter().flat_map(|&v| order.u32_bytes(v as u32)).collect())
+            }
+            Value::Rational(values) => (
+                values.len() as u32,
+                values.iter().flat_map(|&(n, d)| order.u32_bytes(n).into_iter().chain(order.u32_bytes(d))).collect(),
+            ),
+            Value::SRational(values) => (
+                values.len() as u32,
+                values
+                    .iter()
+                    .flat_map(|&(n, d)| order.u32_bytes(n as u32).into_iter().chain(order.u32_bytes(d as u32)))
+                    .collect(),
+            ),
+            Value::Other { count, data, .. } => (*count, data.clone()),
+            Value::Ifd(_) => (1, vec![0; 4]),
+        }
+    }
+
+    fn decode(type_id: u16, count: u32, data: &[u8], order: ByteOrder) -> Value {
+        let words = |size: usize| data.chunks_exact(size);
+        match type_id {
+            1 => Value::Byte(data.to_vec()),
+            2 => {
+                let end = data.iter().position(|&byte| byte == 0).unwrap_or(data.len());
+                Value::Ascii(String::from_utf8_lossy(&data[..end]).into_owned())
+            }
+            3 => Value::Short(words(2).map(|w| order.u16(w)).collect()),
+            4 => Value::Long(words(4).map(|w| order.u32(w)).collect()),
+            5 => Value::Rational(words(8).map(|w| (order.u32(w), order.u32(&w[4..]))).collect()),
+            7 => Value::Undefined(data.to_vec()),
+            9 => Value::SLong(words(4).map(|w| order.u32(w) as i32).collect()),
+            10 => Value::SRational(words(8).map(|w| (order.u32(w) as i32, order.u32(&w[4..]) as i32)).collect()),
+            _ => Value::Other { type_id, count, data: data.to_vec() },
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Orientation {
+    Normal,
+    FlipHorizontal,
+    Rotate180,
+    FlipVertical,
+    Transpose,
+    Rotate90,
+    Transverse,
+    Rotate270,
+}
+
+impl Orientation {
+    pub fn from_value(value: u16) -> Option<Orientation> {
+        match value {
+            1 => Some(Orientation::Normal),
+            2 => Some(Orientation::FlipHorizontal),
+            3 => Some(Orientation::Rotate180),
+            4 => Some(Orientation::FlipVertical),
+            5 => Some(Orientation::Transpose),
+            6 => Some(Orientation::Rotate90),
+            7 => Some(Orientation::Transverse),
+            8 => Some(Orientation::Rotate270),
+            _ => None,
+        }
+    }
+
+    pub fn as_value(&self) -> u16 {
+        match self {
+            Orientation::Normal => 1,
+            Orientation::FlipHorizontal => 2,
+            Orientation::Rotate180 => 3,
+            Orientation::FlipVertical => 4,
+            Orientation::Transpose => 5,
+            Orientation::Rotate90 => 6,
+            Orientation::Transverse => 7,
+            Orientation::Rotate270 => 8,
+        }
+    }
+}
+
+// EXIF timestamps are local time without a zone, "YYYY:MM:DD HH:MM:SS"
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DateTime {
+    pub year: u16,
+    pub month: u8,
+    pub day: u8,
+    pub hour: u8,
+    pub minute: u8,
+    pub second: u8,
+}
+
+impl FromStr for DateTime {
+    type Err = ExifError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let bytes = s.as_bytes();
+        let separators = [(4, b':'), (7, b':'), (10, b' '), (13, b':'), (16, b':')];
+        if bytes.len() != 19 || separators.iter().any(|&(at, sep)| bytes[at] != sep) {
+            return Err(ExifError::InvalidDateTime);
+        }
+        let field = |from: usize, to: usize| s[from..to].parse::<u16>().map_err(|_| ExifError::InvalidDateTime);
+        let date_time = DateTime {
+            year: field(0, 4)?,
+            month: field(5, 7)? as u8,
+            day: field(8, 10)? as u8,
+            hour: field(11, 13)? as u8,
+            minute: field(14, 16)? as u8,
+            second: field(17, 19)? as u8,
+        };
+        let valid = (1..=12).contains(&date_time.month) && (1..=31).contains(&date_time.day)
+            && date_time.hour < 24 && date_time.minute < 60 && date_time.second < 61;
+        match valid {
+            true => Ok(date_time),
+            false => Err(ExifError::InvalidDateTime),
+        }
+    }
+}
+
+impl Display for DateTime {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:04}:{:02}:{:02} {:02}:{:02}:{:02}", self.year, self.month, self.day, self.hour, self.minute, self.second)
+    }
+}
+
+// decimal degrees, negative for south and west
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GpsPosition {
+    pub latitude: f64,
+    pub longitude: f64,
+    pub altitude: Option<f64>,
+}
+
+fn degrees(value: &Value, reference: Option<&Value>, negative: &str) -> Option<f64> {
+    let Value::Rational(parts) = value else { return None };
+    if parts.len() != 3 || parts.iter().any(|&(_, d)| d == 0) {
+        return None;
+    }
+    let [d, m, s] = [0, 1, 2].map(|i| parts[i].0 as f64 / parts[i].1 as f64);
+    let degrees = d + m / 60.0 + s / 3600.0;
+    match reference {
+        Some(Value::Ascii(r)) if r == negative => Some(-degrees),
+        _ => Some(degrees),
+    }
+}
+
+fn find(entries: &[Entry], tag: u16) -> Option<&Value> {
+    entries.iter().find(|entry| entry.tag == tag).map(|entry| &entry.value)
+}
+
+fn set(entries: &mut Vec<Entry>, tag: u16, value: Value) {
+    match entries.iter_mut().find(|entry| entry.tag == tag) {
+        Some(entry) => entry.value = value,
+        None => entries.push(Entry { tag, value }),
+    }
+}
+
+// IFD1 (the thumbnail) isn't kept, its JPEG data is referenced by offsets
+// that wouldn't survive a rewrite anyway
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Exif {
+    pub byte_order: ByteOrder,
+    pub entries: Vec<Entry>,
+}
+
+impl Exif {
+    pub fn new(byte_order: ByteOrder) -> Exif {
+        Exif { byte_order, entries: Vec::new() }
+    }
+
+    fn sub_ifd(&self, tag: u16) -> Option<&Vec<Entry>> {
+        match find(&self.entries, tag) {
+            Some(Value::Ifd(entries)) => Some(entries),
+            _ => None,
+        }
+    }
+
+    fn sub_ifd_mut(&mut self, tag: u16) -> &mut Vec<Entry> {
+        if !matches!(find(&self.entries, tag), Some(Value::Ifd(_))) {
+            set(&mut self.entries, tag, Value::Ifd(Vec::new()));
+        }
+        match self.entries.iter_mut().find(|entry| entry.tag == tag) {
+            Some(Entry { value: Value::Ifd(entries), .. }) => entries,
+            _ => unreachable!("the sub-IFD was just added"),
+        }
+    }
+
+    fn ascii(entries: Option<&Vec<Entry>>, tag: u16) -> Option<&str> {
+        match find(entries?, tag) {
+            Some(Value::Ascii(s)) => Some(s.trim_end()),
+            _ => None,
+        }
+    }
+
+    pub fn get(&self, tag: u16) -> Option<&Value> {
+        find(&self.entries, tag)
+    }
+
+    pub fn set(&mut self, tag: u16, value: Value) {
+        set(&mut self.entries, tag, value)
+    }
+
+    pub fn set_in_exif_ifd(&mut self, tag: u16, value: Value) {
+        set(self.sub_ifd_mut(TAG_EXIF_IFD), tag, value)
+    }
+
+    pub fn remove(&mut self, tag: u16) -> Option<Value> {
+        let pos = self.entries.iter().position(|entry| entry.tag == tag)?;
+        Some(self.entries.remove(pos).value)
+    }
+
+    pub fn orientation(&self) -> Option<Orientation> {
+        match self.get(TAG_ORIENTATION) {
+            Some(Value::Short(values)) => values.first().and_then(|&v| Orientation::from_value(v)),
+            _ => None,
+        }
+    }
+
+    pub fn set_orientation(&mut self, orientation: Orientation) {
+        self.set(TAG_ORIENTATION, Value::Short(vec![orientation.as_value()]))
+    }
+
+    pub fn make(&self) -> Option<&str> {
+        Exif::ascii(Some(&self.entries), TAG_MAKE)
+    }
+
+    pub fn model(&self) -> Option<&str> {
+        Exif::ascii(Some(&self.entries), TAG_MODEL)
+    }
+
+    pub fn set_model(&mut self, model: &str) {
+        self.set(TAG_MODEL, Value::Ascii(model.to_string()))
+    }
+
+    pub fn date_time(&self) -> Option<DateTime> {
+        Exif::ascii(Some(&self.entries), TAG_DATE_TIME)?.parse().ok()
+    }
+
+    pub fn date_time_original(&self) -> Option<DateTime> {
+        Exif::ascii(self.sub_ifd(TAG_EXIF_IFD), TAG_DATE_TIME_ORIGINAL)?.parse().ok()
+    }
+
+    pub fn date_time_digitized(&self) -> Option<DateTime> {
+        Exif::ascii(self.sub_ifd(TAG_EXIF_IFD), TAG_DATE_TIME_DIGITIZED)?.parse().ok()
+    }
+
+    pub fn set_date_time(&mut self, date_time: DateTime) {
+        self.set(TAG_DATE_TIME, Value::Ascii(date_time.to_string()))
+    }
+
+    pub fn set_date_time_original(&mut self, date_time: DateTime) {
+        self.set_in_exif_ifd(TAG_DATE_TIME_ORIGINAL, Value::Ascii(date_time.to_string()))
+    }
+
+    pub fn gps(&self) -> Option<GpsPosition> {
+        let gps = self.sub_ifd(TAG_GPS_IFD)?;
+        let latitude = degrees(find(gps, TAG_GPS_LATITUDE)?, find(gps, TAG_GPS_LATITUDE_REF), "S")?;
+        let longitude = degrees(find(gps, TAG_GPS_LONGITUDE)?, find(gps, TAG_GPS_LONGITUDE_REF), "W")?;
+        let altitude = match find(gps, TAG_GPS_ALTITUDE) {
+            Some(Value::Rational(parts)) if parts.len() == 1 && parts[0].1 != 0 => {
+                let meters = parts[0].0 as f64 / parts[0].1 as f64;
+                // reference 1 means below sea level
+                match find(gps, TAG_GPS_ALTITUDE_REF) {
+                    Some(Value::Byte(r)) if r.first() == Some(&1) => Some(-meters),
+                    _ => Some(meters),
+                }
+            }
+            _ => None,
+        };
+        Some(GpsPosition { latitude, longitude, altitude })
+    }
+
+    // returns whether there was any GPS data to remove
+    pub fn strip_gps(&mut self) -> bool {
+        self.remove(TAG_GPS_IFD).is_some()
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let order = self.byte_order;
+        let mut bytes = match order {
+            ByteOrder::LittleEndian => b"II".to_vec(),
+            ByteOrder::BigEndian => b"MM".to_vec(),
+        };
+        bytes.extend_from_slice(&order.u16_bytes(42));
+        bytes.extend_from_slice(&order.u32_bytes(8));
+        write_ifd(&mut bytes, &self.entries, order);
+        bytes
+    }
+}
+
+// entries, then the values that don't fit in four bytes, then sub-IFDs
+fn write_ifd(bytes: &mut Vec<u8>, entries: &[Entry], order: ByteOrder) {
+    let mut entries: Vec<&Entry> = entries.iter().collect();
+    entries.sort_by_key(|entry| entry.tag);
+    let start = bytes.len();
+    let mut data_at = start + 2 + entries.len() * 12 + 4;
+    let mut data = Vec::new();
+    let mut pointers = Vec::new();
+
+    bytes.extend_from_slice(&order.u16_bytes(entries.len() as u16));
+    for entry in entries.iter() {
+        let (count, value) = entry.value.encode(order);
+        bytes.extend_from_slice(&order.u16_bytes(entry.tag));
+        bytes.extend_from_slice(&order.u16_bytes(entry.value.type_id()));
+        bytes.extend_from_slice(&order.u32_bytes(count));
+        if let Value::Ifd(sub) = &entry.value {
+            pointers.push((bytes.len(), sub));
+        }
+        if value.len() <= 4 {
+            let mut field = value;
+            field.resize(4, 0);
+            bytes.extend(field);
+        } else {
+            bytes.extend_from_slice(&order.u32_bytes(data_at as u32));
+            data_at += value.len() + value.len() % 2;
+            data.extend(&value);
+            // values start on word boundaries
+            if value.len() % 2 == 1 {
+                data.push(0);
+            }
+        }
+    }
+    // no next IFD
+    bytes.extend_from_slice(&[0; 4]);
+    bytes.extend(data);
+
+    for (field_at, sub) in pointers {
+        let sub_at = bytes.len() as u32;
+        bytes[field_at..field_at + 4].copy_from_slice(&order.u32_bytes(sub_at));
+        write_ifd(bytes, sub, order);
+    }
+}
+
+fn read_ifd(data: &[u8], offset: usize, order: ByteOrder, depth: usize) -> Result<Vec<Entry>, ExifError> {
+    if depth > MAX_DEPTH {
+        return Err(ExifError::TooDeep);
+    }
+    let count_bytes = data.get(offset..offset + 2).ok_or(ExifError::Truncated)?;
+    let count = order.u16(count_bytes) as usize;
+    let table = data.get(offset + 2..offset + 2 + count * 12).ok_or(ExifError::Truncated)?;
+
+    let mut entries = Vec::with_capacity(count);
+    for field in table.chunks_exact(12) {
+        let tag = order.u16(field);
+        let type_id = order.u16(&field[2..]);
+        let value_count = order.u32(&field[4..]);
+        let size = type_size(type_id).ok_or(ExifError::UnknownType(type_id))?;
+        let len = size.checked_mul(value_count as usize).ok_or(ExifError::Truncated)?;
+        let value_bytes = match len <= 4 {
+            true => &field[8..8 + len],
+            false => {
+                let at = order.u32(&field[8..]) as usize;
+                data.get(at..at.checked_add(len).ok_or(ExifError::Truncated)?).ok_or(ExifError::Truncated)?
+            }
+        };
+        let value = match POINTER_TAGS.contains(&tag) && len == 4 {
+            true => Value::Ifd(read_ifd(data, order.u32(value_bytes) as usize, order, depth + 1)?),
+            false => Value::decode(type_id, value_count, value_bytes, order),
+        };
+        entries.push(Entry { tag, value });
+    }
+    Ok(entries)
+}
+
+impl TryFrom<&[u8]> for Exif {
+    type Error = ExifError;
+
+    fn try_from(data: &[u8]) -> Result<Self, Self::Error> {
+        let order = match data.get(..2) {
+            Some(b"II") => ByteOrder::LittleEndian,
+            Some(b"MM") => ByteOrder::BigEndian,
+            _ => return Err(ExifError::InvalidHeader),
+        };
+        if data.len() < 8 || order.u16(&data[2..]) != 42 {
+            return Err(ExifError::InvalidHeader);
+        }
+        let entries = read_ifd(data, order.u32(&data[4..]) as usize, order, 0)?;
+        Ok(Exif { byte_order: order, entries })
+    }
+}
+
+impl TryFrom<&Chunk> for Exif {
+    type Error = ExifError;
+
+    fn try_from(chunk: &Chunk) -> Result<Self, Self::Error> {
+        if !chunk.is_type(EXIF_CHUNK) {
+            return Err(ExifError::NotExif);
+        }
+        Exif::try_from(chunk.data())
+    }
+}
+
+impl Exif {
+    pub fn to_chunk(&self) -> Chunk {
+        chunk(EXIF_CHUNK, self.to_bytes())
+    }
+
+    pub fn from_png(png: &Png) -> Result<Option<Exif>, ExifError> {
+        png.chunk_by_type(EXIF_CHUNK).map(Exif::try_from).transpose()
+    }
+
+    // replaces the eXIf chunk in place, or adds one before IEND; the old
+    // chunk is only dropped once the new one is in
+    pub fn write_to_png(&self, png: &mut Png) -> Result<(), ExifError> {
+        let result = match png.chunks().iter().position(|chunk| chunk.is_type(EXIF_CHUNK)) {
+            Some(pos) => png.replace_chunk(pos, self.to_chunk()).map(|_| ()),
+            None => png.insert_chunk(self.to_chunk(), ChunkPlacement::BeforeIend).map(|_| ()),
+        };
+        result.map_err(|err| ExifError::Png(format!("{:?}", err)))
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum ExifError {
+    NotExif,
+    InvalidHeader,
+    Truncated,
+    UnknownType(u16),
+    TooDeep,
+    InvalidDateTime,
+    Png(String),
+}
+
+impl Display for ExifError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ExifError::NotExif => write!(f, "not an {} chunk", EXIF_CHUNK),
+            ExifError::InvalidHeader => write!(f, "EXIF data doesn't start with a TIFF header"),
+            ExifError::Truncated => write!(f, "EXIF data points past its end"),
+            ExifError::UnknownType(type_id) => write!(f, "unknown EXIF field type {}", type_id),
+            ExifError::TooDeep => write!(f, "EXIF IFDs are nested too deeply"),
+            ExifError::InvalidDateTime => write!(f, "timestamps look like YYYY:MM:DD HH:MM:SS"),
+            ExifError::Png(reason) => write!(f, "{}", reason),
+        }
+    }
+}
+
+impl std::error::Error for ExifError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn photo_exif(byte_order: ByteOrder) -> Exif {
+        let mut exif = Exif::new(byte_order);
+        exif.set(TAG_MAKE, Value::Ascii("Pentax".to_string()));
+        exif.set_model("K-1 Mark II");
+        exif.set_orientation(Orientation::Rotate90);
+        exif.set_date_time("2021:06:01 10:20:30".parse().unwrap());
+        exif.set_date_time_original("2021:05:31 09:00:00".parse().unwrap());
+        exif.set(
+            TAG_GPS_IFD,
+            Value::Ifd(vec![
+                Entry { tag: TAG_GPS_LATITUDE_REF, value: Value::Ascii("S".to_string()) },
+                Entry { tag: TAG_GPS_LATITUDE, value: Value::Rational(vec![(33, 1), (51, 1), (36, 1)]) },
+                Entry { tag: TAG_GPS_LONGITUDE_REF, value: Value::Ascii("E".to_string()) },
+                Entry { tag: TAG_GPS_LONGITUDE, value: Value::Rational(vec![(151, 1), (12, 1), (0, 1)]) },
+                Entry { tag: TAG_GPS_ALTITUDE, value: Value::Rational(vec![(105, 2)]) },
+            ]),
+        );
+        exif
+    }
+
+    #[test]
+    fn test_typed_tags() {
+        for byte_order in [ByteOrder::LittleEndian, ByteOrder::BigEndian] {
+            let exif = Exif::try_from(photo_exif(byte_order).to_bytes().as_slice()).unwrap();
+            assert_eq!(exif.byte_order, byte_order);
+            assert_eq!(exif.make(), Some("Pentax"));
+            assert_eq!(exif.model(), Some("K-1 Mark II"));
+            assert_eq!(exif.orientation(), Some(Orientation::Rotate90));
+            assert_eq!(exif.date_time().unwrap().to_string(), "2021:06:01 10:20:30");
+            assert_eq!(exif.date_time_original().unwrap().day, 31);
+            assert_eq!(exif.date_time_digitized(), None);
+
+            let gps = exif.gps().unwrap();
+            assert!((gps.latitude + 33.86).abs() < 1e-9);
+            assert!((gps.longitude - 151.2).abs() < 1e-9);
+            assert_eq!(gps.altitude, Some(52.5));
+        }
+    }
+
+    #[test]
+    fn test_round_trip_keeps_unknown_tags() {
+        let mut exif = photo_exif(ByteOrder::BigEndian);
+        exif.set(0x9286, Value::Undefined(b"ASCII\0\0\0user comment".to_vec()));
+        exif.set(0xa20e, Value::Other { type_id: 12, count: 1, data: 1.5f64.to_be_bytes().to_vec() });
+        let parsed = Exif::try_from(exif.to_bytes().as_slice()).unwrap();
+        assert_eq!(parsed.get(0x9286), exif.get(0x9286));
+        assert_eq!(parsed.get(0xa20e), exif.get(0xa20e));
+        assert_eq!(parsed.to_bytes(), exif.to_bytes());
+    }
+
+    #[test]
+    fn test_parse_handmade_tiff() {
+        // big endian, one IFD with the orientation stored inline
+        let bytes = [
+            b'M', b'M', 0, 42, 0, 0, 0, 8,
+            0, 1,
+            0x01, 0x12, 0, 3, 0, 0, 0, 1, 0, 3, 0, 0,
+            0, 0, 0, 0,
+        ];
+        let exif = Exif::try_from(&bytes[..]).unwrap();
+        assert_eq!(exif.orientation(), Some(Orientation::Rotate180));
+        assert_eq!(exif.to_bytes(), bytes);
+    }
+
+    #[test]
+    fn test_rewrite_tags() {
+        let mut exif = photo_exif(ByteOrder::LittleEndian);
+        exif.set_orientation(Orientation::Normal);
+        exif.set_model("K-3");
+        let exif = Exif::try_from(exif.to_bytes().as_slice()).unwrap();
+        assert_eq!(exif.orientation(), Some(Orientation::Normal));
+        assert_eq!(exif.model(), Some("K-3"));
+    }
+
+    #[test]
+    fn test_strip_gps() {
+        let mut exif = photo_exif(ByteOrder::LittleEndian);
+        assert!(exif.strip_gps());
+        assert!(!exif.strip_gps());
+        let exif = Exif::try_from(exif.to_bytes().as_slice()).unwrap();
+        assert_eq!(exif.gps(), None);
+        assert_eq!(exif.model(), Some("K-1 Mark II"));
+    }
+
+    #[test]
+    fn test_invalid_exif() {
+        assert_eq!(Exif::try_from(&b"JFIF"[..]), Err(ExifError::InvalidHeader));
+        assert_eq!(Exif::try_from(&b"II\x2a\x00\x40\x00\x00\x00"[..]), Err(ExifError::Truncated));
+        let mut bytes = photo_exif(ByteOrder::BigEndian).to_bytes();
+        bytes.truncate(40);
+        assert_eq!(Exif::try_from(bytes.as_slice()), Err(ExifError::Truncated));
+        assert_eq!("2021-06-01 10:20:30".parse::<DateTime>(), Err(ExifError::InvalidDateTime));
+        assert_eq!("2021:13:01 10:20:30".parse::<DateTime>(), Err(ExifError::InvalidDateTime));
+    }
+
+    #[test]
+    fn test_png_round_trip() {
+        let chunk = |chunk_type: &str| chunk(chunk_type, Vec::new());
+        let mut png = Png::from_chunks(vec![chunk("IHDR"), chunk("IDAT"), chunk("IEND")]).unwrap();
+        assert_eq!(Exif::from_png(&png), Ok(None));
+
+        let mut exif = photo_exif(ByteOrder::LittleEndian);
+        exif.write_to_png(&mut png).unwrap();
+        exif.strip_gps();
+        exif.write_to_png(&mut png).unwrap();
+        assert_eq!(png.chunks_by_type(EXIF_CHUNK).len(), 1);
+        assert_eq!(png.chunks()[2].chunk_type().to_string(), EXIF_CHUNK);
+        assert_eq!(Exif::from_png(&png).unwrap().unwrap(), exif);
+    }
+}
@@ -0,0 +1,195 @@
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FilterType {
+    None,
+    Sub,
+    Up,
+    Average,
+    Paeth,
+}
+
+impl FilterType {
+    pub const ALL: [FilterType; 5] = [
+        FilterType::None,
+        FilterType::Sub,
+        FilterType::Up,
+        FilterType::Average,
+        FilterType::Paeth,
+    ];
+
+    pub fn from_byte(byte: u8) -> Option<FilterType> {
+        FilterType::ALL.get(byte as usize).copied()
+    }
+
+    pub fn as_byte(&self) -> u8 {
+        *self as u8
+    }
+}
+
+fn paeth_predictor(a: u8, b: u8, c: u8) -> u8 {
+    let p = a as i16 + b as i16 - c as i16;
+    let pa = (p - a as i16).abs();
+    let pb = (p - b as i16).abs();
+    let pc = (p - c as i16).abs();
+    if pa <= pb && pa <= pc {
+        a
+    } else if pb <= pc {
+        b
+    } else {
+        c
+    }
+}
+
+// reverses the filter in place; `prev` is the already unfiltered previous
+// scanline, or all zeroes for the first scanline of an image or pass
+pub fn unfilter_scanline(filter: FilterType, bpp: usize, prev: &[u8], current: &mut [u8]) {
+    match filter {
+        FilterType::None => {}
+        FilterType::Sub => {
+            for i in bpp..current.len() {
+                current[i] = current[i].wrapping_add(current[i - bpp]);
+            }
+        }
+        FilterType::Up => {
+            for i in 0..current.len() {
+                current[i] = current[i].wrapping_add(prev[i]);
+            }
+        }
+        FilterType::Average => {
+            for i in 0..current.len() {
+                let left = if i >= bpp { current[i - bpp] as u16 } else { 0 };
+                current[i] = current[i].wrapping_add(((left + prev[i] as u16) / 2) as u8);
+            }
+        }
+        FilterType::Paeth => {
+            for i in 0..current.len() {
+                let (left, upper_left) = if i >= bpp { (current[i - bpp], prev[i - bpp]) } else { (0, 0) };
+                current[i] = current[i].wrapping_add(paeth_predictor(left, prev[i], upper_left));
+            }
+        }
+    }
+}
+
+// appends `current` filtered against `prev` to `out`, without the filter type byte
+pub fn filter_scanline(filter: FilterType, bpp: usize, prev: &[u8], current: &[u8], out: &mut Vec<u8>) {
+    for i in 0..current.len() {
+        let (left, upper_left) = if i >= bpp { (current[i - bpp], prev[i - bpp]) } else { (0, 0) };
+        let predicted = match filter {
+            FilterType::None => 0,
+            FilterType::Sub => left,
+            FilterType::Up => prev[i],
+            FilterType::Average => ((left as u16 + prev[i] as u16) / 2) as u8,
+            FilterType::Paeth => paeth_predictor(left, prev[i], upper_left),
+        };
+        out.push(current[i].wrapping_sub(predicted));
+    }
+}
+
+// filters every scanline with `filter`, prefixing each with its filter type byte
+pub fn filter_rows(pixels: &[u8], bpp: usize, row_bytes: usize, filter: FilterType) -> Vec<u8> {
+    let height = pixels.len().checked_div(row_bytes).unwrap_or(0);
+    let mut out = Vec::with_capacity(pixels.len() + height);
+    let zero_row = vec![0; row_bytes];
+    for row in 0..height {
+        let prev = if row == 0 { &zero_row[..] } else { &pixels[(row - 1) * row_bytes..row * row_bytes] };
+        out.push(filter.as_byte());
+        filter_scanline(filter, bpp, prev, &pixels[row * row_bytes..(row + 1) * row_bytes], &mut out);
+    }
+    out
+}
+
+// unfilters `height` scanlines of `row_bytes` each, every one prefixed by its filter type byte
+pub fn unfilter_rows(data: &[u8], bpp: usize, row_bytes: usize, height: usize) -> Result<Vec<u8>, FilterError> {
+    // checked before anything is allocated; the pixels take less than this
+    let length = row_bytes.checked_add(1).and_then(|n| n.checked_mul(height)).ok_or(FilterError::SizeOverflow)?;
+    if data.len() < length {
+        return Err(FilterError::TruncatedData);
+    }
+    let mut pixels = vec![0; row_bytes * height];
+    let zero_row = vec![0; row_bytes];
+    for row in 0..height {
+        let line = &data[row * (row_bytes + 1)..(row + 1) * (row_bytes + 1)];
+        let filter = FilterType::from_byte(line[0]).ok_or(FilterError::InvalidFilterType(line[0]))?;
+        let (done, rest) = pixels.split_at_mut(row * row_bytes);
+        let prev = if row == 0 { &zero_row[..] } else { &done[(row - 1) * row_bytes..] };
+        let current = &mut rest[..row_bytes];
+        current.copy_from_slice(&line[1..]);
+        unfilter_scanline(filter, bpp, prev, current);
+    }
+    Ok(pixels)
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum FilterError {
+    InvalidFilterType(u8),
+    TruncatedData,
+    SizeOverflow,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_filter_type_from_byte() {
+        assert_eq!(FilterType::from_byte(0), Some(FilterType::None));
+        assert_eq!(FilterType::from_byte(4), Some(FilterType::Paeth));
+        assert_eq!(FilterType::from_byte(5), None);
+        assert_eq!(FilterType::Average.as_byte(), 3);
+    }
+
+    #[test]
+    fn test_paeth_predictor() {
+        assert_eq!(paeth_predictor(10, 20, 10), 20);
+        assert_eq!(paeth_predictor(20, 10, 10), 20);
+        assert_eq!(paeth_predictor(10, 10, 20), 10);
+    }
+
+    #[test]
+    fn test_unfilter_sub_and_up() {
+        let mut row = [1, 2, 3, 4];
+        unfilter_scanline(FilterType::Sub, 1, &[0; 4], &mut row);
+        assert_eq!(row, [1, 3, 6, 10]);
+
+        let mut row = [1, 1, 1, 255];
+        unfilter_scanline(FilterType::Up, 1, &[1, 3, 6, 10], &mut row);
+        assert_eq!(row, [2, 4, 7, 9]);
+    }
+
+    #[test]
+    fn test_unfilter_average_and_paeth() {
+        let mut row = [10, 10, 10, 10];
+        unfilter_scanline(FilterType::Average, 2, &[4, 6, 8, 10], &mut row);
+        assert_eq!(row, [12, 13, 20, 21]);
+
+        let mut row = [1, 1, 1, 1];
+        unfilter_scanline(FilterType::Paeth, 2, &[5, 5, 9, 9], &mut row);
+        assert_eq!(row, [6, 6, 10, 10]);
+    }
+
+    #[test]
+    fn test_filter_round_trip() {
+        let pixels: Vec<u8> = (0..48).map(|i| (i * 37 % 251) as u8).collect();
+        for filter in FilterType::ALL {
+            let filtered = filter_rows(&pixels, 3, 12, filter);
+            assert_eq!(filtered.len(), 52);
+            assert_eq!(filtered[13], filter.as_byte());
+            assert_eq!(unfilter_rows(&filtered, 3, 12, 4).unwrap(), pixels);
+        }
+    }
+
+    #[test]
+    fn test_filter_sub() {
+        let mut out = Vec::new();
+        filter_scanline(FilterType::Sub, 1, &[0; 4], &[1, 3, 6, 10], &mut out);
+        assert_eq!(out, [1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn test_unfilter_rows() {
+        let data = [1, 1, 2, 2, 0, 2, 4, 6];
+        assert_eq!(unfilter_rows(&data, 1, 3, 2).unwrap(), [1, 3, 5, 2, 4, 6]);
+        assert_eq!(unfilter_rows(&data[..5], 1, 3, 2), Err(FilterError::TruncatedData));
+        assert_eq!(unfilter_rows(&data, 1, usize::MAX, 2), Err(FilterError::SizeOverflow));
+        assert_eq!(unfilter_rows(&[7, 0, 0, 0], 1, 3, 1), Err(FilterError::InvalidFilterType(7)));
+    }
+}
@@ -0,0 +1,56 @@
+pub fn encode(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+pub fn decode(s: &str) -> Result<Vec<u8>, HexError> {
+    let s = s.trim();
+    if !s.bytes().all(|byte| byte.is_ascii_hexdigit()) {
+        return Err(HexError::InvalidDigit);
+    }
+    if !s.len().is_multiple_of(2) {
+        return Err(HexError::OddLength);
+    }
+    Ok((0..s.len()).step_by(2).map(|i| u8::from_str_radix(&s[i..i + 2], 16).unwrap()).collect())
+}
+
+pub fn decode_array<const N: usize>(s: &str) -> Result<[u8; N], HexError> {
+    decode(s)?.try_into().map_err(|_| HexError::WrongLength(N))
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum HexError {
+    OddLength,
+    InvalidDigit,
+    WrongLength(usize),
+}
+
+impl std::fmt::Display for HexError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            HexError::OddLength => write!(f, "hex string has an odd number of digits"),
+            HexError::InvalidDigit => write!(f, "hex string contains a non hex digit"),
+            HexError::WrongLength(bytes) => write!(f, "expected {} hex encoded bytes", bytes),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hex_round_trip() {
+        assert_eq!(encode(&[0, 15, 171, 255]), "000fabff");
+        assert_eq!(decode("000fABff\n").unwrap(), vec![0, 15, 171, 255]);
+    }
+
+    #[test]
+    fn test_hex_errors() {
+        assert_eq!(decode("abc"), Err(HexError::OddLength));
+        assert_eq!(decode("zz"), Err(HexError::InvalidDigit));
+        assert_eq!(decode("é0"), Err(HexError::InvalidDigit));
+        assert_eq!(decode("+f"), Err(HexError::InvalidDigit));
+        assert_eq!(decode_array::<2>("aabbcc"), Err(HexError::WrongLength(2)));
+        assert_eq!(decode_array::<2>("aabb"), Ok([0xaa, 0xbb]));
+    }
+}
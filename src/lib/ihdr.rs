@@ -0,0 +1,254 @@
+use std::convert::TryFrom;
+
+use super::chunk::{chunk, Chunk};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorType {
+    Grayscale,
+    Rgb,
+    Indexed,
+    GrayscaleAlpha,
+    Rgba,
+}
+
+impl ColorType {
+    pub fn from_byte(byte: u8) -> Option<ColorType> {
+        match byte {
+            0 => Some(ColorType::Grayscale),
+            2 => Some(ColorType::Rgb),
+            3 => Some(ColorType::Indexed),
+            4 => Some(ColorType::GrayscaleAlpha),
+            6 => Some(ColorType::Rgba),
+            _ => None,
+        }
+    }
+
+    pub fn as_byte(&self) -> u8 {
+        match self {
+            ColorType::Grayscale => 0,
+            ColorType::Rgb => 2,
+            ColorType::Indexed => 3,
+            ColorType::GrayscaleAlpha => 4,
+            ColorType::Rgba => 6,
+        }
+    }
+
+    pub fn channels(&self) -> usize {
+        match self {
+            ColorType::Grayscale | ColorType::Indexed => 1,
+            ColorType::GrayscaleAlpha => 2,
+            ColorType::Rgb => 3,
+            ColorType::Rgba => 4,
+        }
+    }
+
+    pub fn allowed_bit_depths(&self) -> &'static [u8] {
+        match self {
+            ColorType::Grayscale => &[1, 2, 4, 8, 16],
+            ColorType::Indexed => &[1, 2, 4, 8],
+            ColorType::Rgb | ColorType::GrayscaleAlpha | ColorType::Rgba => &[8, 16],
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InterlaceMethod {
+    None,
+    Adam7,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Ihdr {
+    pub width: u32,
+    pub height: u32,
+    pub bit_depth: u8,
+    pub color_type: ColorType,
+    pub compression_method: u8,
+    pub filter_method: u8,
+    pub interlace_method: InterlaceMethod,
+}
+
+impl Ihdr {
+    const MAX_DIMENSION: u32 = (1 << 31) - 1;
+
+    pub fn new(width: u32, height: u32, bit_depth: u8, color_type: ColorType) -> Result<Ihdr, IhdrError> {
+        let ihdr = Ihdr {
+            width,
+            height,
+            bit_depth,
+            color_type,
+            compression_method: 0,
+            filter_method: 0,
+            interlace_method: InterlaceMethod::None,
+        };
+        ihdr.validate()?;
+        Ok(ihdr)
+    }
+
+    pub fn validate(&self) -> Result<(), IhdrError> {
+        if self.width == 0 || self.height == 0 {
+            return Err(IhdrError::ZeroDimension);
+        }
+        if self.width > Self::MAX_DIMENSION || self.height > Self::MAX_DIMENSION {
+            return Err(IhdrError::DimensionTooLarge);
+        }
+        if !self.color_type.allowed_bit_depths().contains(&self.bit_depth) {
+            return Err(IhdrError::InvalidBitDepth(self.color_type, self.bit_depth));
+        }
+        if self.compression_method != 0 {
+            return Err(IhdrError::UnknownCompressionMethod(self.compression_method));
+        }
+        if self.filter_method != 0 {
+            return Err(IhdrError::UnknownFilterMethod(self.filter_method));
+        }
+        Ok(())
+    }
+
+    pub fn bits_per_pixel(&self) -> usize {
+        self.color_type.channels() * self.bit_depth as usize
+    }
+
+    // filter byte distance: whole bytes per pixel, rounded up to at least one
+    pub fn filter_bpp(&self) -> usize {
+        self.bits_per_pixel().div_ceil(8)
+    }
+
+    pub fn row_bytes(&self, width: u32) -> usize {
+        (width as usize * self.bits_per_pixel()).div_ceil(8)
+    }
+
+    pub fn to_chunk(&self) -> Chunk {
+        let interlace = match self.interlace_method {
+            InterlaceMethod::None => 0,
+            InterlaceMethod::Adam7 => 1,
+        };
+        let data: Vec<u8> = self.width.to_be_bytes()
+            .iter()
+            .chain(self.height.to_be_bytes().iter())
+            .cloned()
+            .chain([
+                self.bit_depth,
+                self.color_type.as_byte(),
+                self.compression_method,
+                self.filter_method,
+                interlace,
+            ])
+            .collect();
+        chunk("IHDR", data)
+    }
+}
+
+impl TryFrom<&Chunk> for Ihdr {
+    type Error = IhdrError;
+
+    fn try_from(chunk: &Chunk) -> Result<Self, Self::Error> {
+        if &chunk.chunk_type().bytes() != b"IHDR" {
+            return Err(IhdrError::NotIhdr);
+        }
+        let data = chunk.data();
+        if data.len() != 13 {
+            return Err(IhdrError::InvalidLength);
+        }
+        let color_type = ColorType::from_byte(data[9]).ok_or(IhdrError::InvalidColorType(data[9]))?;
+        let interlace_method = match data[12] {
+            0 => InterlaceMethod::None,
+            1 => InterlaceMethod::Adam7,
+            other => return Err(IhdrError::UnknownInterlaceMethod(other)),
+        };
+        let ihdr = Ihdr {
+            width: u32::from_be_bytes([data[0], data[1], data[2], data[3]]),
+            height: u32::from_be_bytes([data[4], data[5], data[6], data[7]]),
+            bit_depth: data[8],
+            color_type,
+            compression_method: data[10],
+            filter_method: data[11],
+            interlace_method,
+        };
+        ihdr.validate()?;
+        Ok(ihdr)
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum IhdrError {
+    Missing,
+    NotIhdr,
+    InvalidLength,
+    ZeroDimension,
+    DimensionTooLarge,
+    InvalidColorType(u8),
+    InvalidBitDepth(ColorType, u8),
+    UnknownCompressionMethod(u8),
+    UnknownFilterMethod(u8),
+    UnknownInterlaceMethod(u8),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ihdr_chunk(data: &[u8]) -> Chunk {
+        chunk("IHDR", data.to_vec())
+    }
+
+    #[test]
+    fn test_ihdr_from_chunk() {
+        let chunk = ihdr_chunk(&[0, 0, 0, 50, 0, 0, 0, 40, 8, 6, 0, 0, 1]);
+        let ihdr = Ihdr::try_from(&chunk).unwrap();
+        assert_eq!(ihdr.width, 50);
+        assert_eq!(ihdr.height, 40);
+        assert_eq!(ihdr.bit_depth, 8);
+        assert_eq!(ihdr.color_type, ColorType::Rgba);
+        assert_eq!(ihdr.interlace_method, InterlaceMethod::Adam7);
+    }
+
+    #[test]
+    fn test_ihdr_round_trip() {
+        let ihdr = Ihdr::new(640, 480, 16, ColorType::Rgb).unwrap();
+        let chunk = ihdr.to_chunk();
+        assert_eq!(chunk.chunk_type().to_string(), "IHDR");
+        assert_eq!(chunk.length(), 13);
+        assert_eq!(Ihdr::try_from(&chunk).unwrap(), ihdr);
+    }
+
+    #[test]
+    fn test_ihdr_rejects_illegal_bit_depth() {
+        assert_eq!(
+            Ihdr::new(1, 1, 16, ColorType::Indexed),
+            Err(IhdrError::InvalidBitDepth(ColorType::Indexed, 16))
+        );
+        assert_eq!(
+            Ihdr::new(1, 1, 4, ColorType::Rgba),
+            Err(IhdrError::InvalidBitDepth(ColorType::Rgba, 4))
+        );
+        assert!(Ihdr::new(1, 1, 2, ColorType::Grayscale).is_ok());
+    }
+
+    #[test]
+    fn test_ihdr_rejects_bad_fields() {
+        assert_eq!(Ihdr::new(0, 1, 8, ColorType::Rgb), Err(IhdrError::ZeroDimension));
+        assert_eq!(
+            Ihdr::try_from(&ihdr_chunk(&[0, 0, 0, 1, 0, 0, 0, 1, 8, 5, 0, 0, 0])),
+            Err(IhdrError::InvalidColorType(5))
+        );
+        assert_eq!(
+            Ihdr::try_from(&ihdr_chunk(&[0, 0, 0, 1, 0, 0, 0, 1, 8, 2, 1, 0, 0])),
+            Err(IhdrError::UnknownCompressionMethod(1))
+        );
+        assert_eq!(
+            Ihdr::try_from(&ihdr_chunk(&[0, 0, 0, 1, 0, 0, 0, 1, 8, 2, 0, 0, 2])),
+            Err(IhdrError::UnknownInterlaceMethod(2))
+        );
+        assert_eq!(Ihdr::try_from(&ihdr_chunk(&[0, 0, 0, 1])), Err(IhdrError::InvalidLength));
+    }
+
+    #[test]
+    fn test_ihdr_row_bytes() {
+        let ihdr = Ihdr::new(10, 1, 1, ColorType::Grayscale).unwrap();
+        assert_eq!(ihdr.row_bytes(10), 2);
+        assert_eq!(ihdr.filter_bpp(), 1);
+        let ihdr = Ihdr::new(10, 1, 16, ColorType::Rgba).unwrap();
+        assert_eq!(ihdr.row_bytes(10), 80);
+        assert_eq!(ihdr.filter_bpp(), 8);
+    }
+}
@@ -0,0 +1,198 @@
+use super::ihdr::ColorType;
+
+// pixels are stored as unfiltered scanlines in the PNG sample layout: big
+// endian 16 bit samples, sub-byte samples packed and each row byte aligned
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Image {
+    pub width: u32,
+    pub height: u32,
+    pub color_type: ColorType,
+    pub bit_depth: u8,
+    pub pixels: Vec<u8>,
+    pub palette: Option<Vec<u8>>,
+    pub transparency: Option<Vec<u8>>,
+}
+
+impl Image {
+    pub fn new(width: u32, height: u32, color_type: ColorType, bit_depth: u8, pixels: Vec<u8>) -> Image {
+        Image { width, height, color_type, bit_depth, pixels, palette: None, transparency: None }
+    }
+
+    pub fn row_bytes(&self) -> usize {
+        (self.width as usize * self.color_type.channels() * self.bit_depth as usize).div_ceil(8)
+    }
+
+    pub fn row(&self, y: u32) -> &[u8] {
+        let row_bytes = self.row_bytes();
+        &self.pixels[y as usize * row_bytes..(y as usize + 1) * row_bytes]
+    }
+
+    // samples of one row at their native depth, `channels` per pixel
+    pub fn row_samples(&self, y: u32) -> Vec<u16> {
+        let row = self.row(y);
+        let count = self.width as usize * self.color_type.channels();
+        match self.bit_depth {
+            16 => row.chunks_exact(2).map(|pair| u16::from_be_bytes([pair[0], pair[1]])).collect(),
+            8 => row.iter().map(|&byte| byte as u16).collect(),
+            depth => {
+                let per_byte = 8 / depth as usize;
+                let mask = (1u16 << depth) - 1;
+                (0..count)
+                    .map(|i| {
+                        let shift = 8 - depth as usize * (i % per_byte + 1);
+                        (row[i / per_byte] as u16 >> shift) & mask
+                    })
+                    .collect()
+            }
+        }
+    }
+
+    fn scale_to_u8(&self, sample: u16) -> u8 {
+        match self.bit_depth {
+            16 => (sample >> 8) as u8,
+            depth => (sample as u32 * 255 / ((1u32 << depth) - 1)) as u8,
+        }
+    }
+
+    fn transparent_key(&self, at: usize) -> Option<u16> {
+        self.transparency
+            .as_ref()
+            .filter(|trns| trns.len() >= at + 2)
+            .map(|trns| u16::from_be_bytes([trns[at], trns[at + 1]]))
+    }
+
+    pub fn to_rgba8(&self) -> Result<Vec<u8>, ImageError> {
+        let mut rgba = Vec::with_capacity(self.width as usize * self.height as usize * 4);
+        let channels = self.color_type.channels();
+        for y in 0..self.height {
+            for pixel in self.row_samples(y).chunks_exact(channels) {
+                match self.color_type {
+                    ColorType::Grayscale => {
+                        let gray = self.scale_to_u8(pixel[0]);
+                        let alpha = if self.transparent_key(0) == Some(pixel[0]) { 0 } else { 255 };
+                        rgba.extend_from_slice(&[gray, gray, gray, alpha]);
+                    }
+                    ColorType::Rgb => {
+                        let keys = [self.transparent_key(0), self.transparent_key(2), self.transparent_key(4)];
+                        let transparent = keys.iter().zip(pixel).all(|(key, &sample)| *key == Some(sample));
+                        rgba.extend(pixel.iter().map(|&sample| self.scale_to_u8(sample)));
+                        rgba.push(if transparent { 0 } else { 255 });
+                    }
+                    ColorType::Indexed => {
+                        let palette = self.palette.as_ref().ok_or(ImageError::MissingPalette)?;
+                        let index = pixel[0] as usize;
+                        let entry = palette
+                            .get(index * 3..index * 3 + 3)
+                            .ok_or(ImageError::PaletteIndexOutOfRange(pixel[0] as u8))?;
+                        let alpha = self.transparency.as_ref().and_then(|trns| trns.get(index)).copied();
+                        rgba.extend_from_slice(entry);
+                        rgba.push(alpha.unwrap_or(255));
+                    }
+                    ColorType::GrayscaleAlpha => {
+                        let gray = self.scale_to_u8(pixel[0]);
+                        rgba.extend_from_slice(&[gray, gray, gray, self.scale_to_u8(pixel[1])]);
+                    }
+                    ColorType::Rgba => {
+                        rgba.extend(pixel.iter().map(|&sample| self.scale_to_u8(sample)));
+                    }
+                }
+            }
+        }
+        Ok(rgba)
+    }
+
+    // the region must lie inside the image
+    pub fn crop(&self, x: u32, y: u32, width: u32, height: u32) -> Image {
+        let channels = self.color_type.channels();
+        let (start, end) = (x as usize * channels, (x + width) as usize * channels);
+        let mut pixels = Vec::with_capacity((width as usize * channels * self.bit_depth as usize).div_ceil(8) * height as usize);
+        for row in y..y + height {
+            pack_samples(&self.row_samples(row)[start..end], self.bit_depth, &mut pixels);
+        }
+        Image { width, height, pixels, palette: self.palette.clone(), transparency: self.transparency.clone(), ..*self }
+    }
+}
+
+// one row of samples back into the packed scanline layout
+fn pack_samples(samples: &[u16], bit_depth: u8, out: &mut Vec<u8>) {
+    match bit_depth {
+        16 => samples.iter().for_each(|sample| out.extend_from_slice(&sample.to_be_bytes())),
+        8 => out.extend(samples.iter().map(|&sample| sample as u8)),
+        depth => {
+            let per_byte = 8 / depth as usize;
+            for group in samples.chunks(per_byte) {
+                let byte = group.iter().enumerate().fold(0u8, |byte, (i, &sample)| {
+                    byte | (sample as u8) << (8 - depth as usize * (i + 1))
+                });
+                out.push(byte);
+            }
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum ImageError {
+    MissingPalette,
+    PaletteIndexOutOfRange(u8),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn image(color_type: ColorType, bit_depth: u8, width: u32, pixels: Vec<u8>) -> Image {
+        let height = (pixels.len() / (width as usize * color_type.channels() * bit_depth as usize).div_ceil(8)) as u32;
+        Image::new(width, height, color_type, bit_depth, pixels)
+    }
+
+    #[test]
+    fn test_row_samples_sub_byte() {
+        let img = image(ColorType::Grayscale, 2, 5, vec![0b00_01_10_11, 0b11_000000]);
+        assert_eq!(img.row_samples(0), vec![0, 1, 2, 3, 3]);
+        assert_eq!(img.to_rgba8().unwrap()[..8], [0, 0, 0, 255, 85, 85, 85, 255]);
+    }
+
+    #[test]
+    fn test_rgba8_from_16_bit() {
+        let img = image(ColorType::Rgb, 16, 1, vec![0x12, 0x34, 0xab, 0xcd, 0xff, 0x00]);
+        assert_eq!(img.row_samples(0), vec![0x1234, 0xabcd, 0xff00]);
+        assert_eq!(img.to_rgba8().unwrap(), vec![0x12, 0xab, 0xff, 255]);
+    }
+
+    #[test]
+    fn test_rgba8_with_transparency_key() {
+        let mut img = image(ColorType::Rgb, 8, 2, vec![1, 2, 3, 4, 5, 6]);
+        img.transparency = Some(vec![0, 4, 0, 5, 0, 6]);
+        assert_eq!(img.to_rgba8().unwrap(), vec![1, 2, 3, 255, 4, 5, 6, 0]);
+    }
+
+    #[test]
+    fn test_rgba8_from_palette() {
+        let mut img = image(ColorType::Indexed, 1, 2, vec![0b01_000000]);
+        assert_eq!(img.to_rgba8(), Err(ImageError::MissingPalette));
+
+        img.palette = Some(vec![10, 20, 30, 40, 50, 60]);
+        img.transparency = Some(vec![128]);
+        assert_eq!(img.to_rgba8().unwrap(), vec![10, 20, 30, 128, 40, 50, 60, 255]);
+
+        img.palette = Some(vec![10, 20, 30]);
+        assert_eq!(img.to_rgba8(), Err(ImageError::PaletteIndexOutOfRange(1)));
+    }
+
+    #[test]
+    fn test_crop() {
+        let img = image(ColorType::Grayscale, 2, 5, vec![0b00_01_10_11, 0b11_000000, 0b11_10_01_00, 0b00_000000]);
+        let cropped = img.crop(1, 0, 4, 2);
+        assert_eq!((cropped.width, cropped.height), (4, 2));
+        assert_eq!(cropped.pixels, vec![0b01_10_11_11, 0b10_01_00_00]);
+
+        let img = image(ColorType::Rgb, 16, 2, (0..24).collect());
+        assert_eq!(img.crop(1, 1, 1, 1).pixels, (18..24).collect::<Vec<u8>>());
+    }
+
+    #[test]
+    fn test_rgba8_from_gray_alpha() {
+        let img = image(ColorType::GrayscaleAlpha, 8, 1, vec![7, 9]);
+        assert_eq!(img.to_rgba8().unwrap(), vec![7, 7, 7, 9]);
+    }
+}
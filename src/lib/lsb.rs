@@ -0,0 +1,256 @@
+extern crate crc;
+use crc::{Crc, CRC_32_ISO_HDLC};
+use std::fmt::Display;
+
+use super::ihdr::ColorType;
+use super::image::Image;
+
+// payload length (u32) and CRC-32 of the payload (u32), stored in the same
+// bit stream right before the payload
+const HEADER_LEN: usize = 8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Channel {
+    Red,
+    Green,
+    Blue,
+    Gray,
+    Alpha,
+}
+
+impl Channel {
+    pub fn from_char(c: char) -> Option<Channel> {
+        match c.to_ascii_lowercase() {
+            'r' => Some(Channel::Red),
+            'g' => Some(Channel::Green),
+            'b' => Some(Channel::Blue),
+            'y' => Some(Channel::Gray),
+            'a' => Some(Channel::Alpha),
+            _ => None,
+        }
+    }
+
+    fn index_in(&self, color_type: ColorType) -> Option<usize> {
+        match (self, color_type) {
+            (Channel::Red, ColorType::Rgb | ColorType::Rgba) => Some(0),
+            (Channel::Green, ColorType::Rgb | ColorType::Rgba) => Some(1),
+            (Channel::Blue, ColorType::Rgb | ColorType::Rgba) => Some(2),
+            (Channel::Alpha, ColorType::Rgba) => Some(3),
+            (Channel::Gray, ColorType::Grayscale | ColorType::GrayscaleAlpha) => Some(0),
+            (Channel::Alpha, ColorType::GrayscaleAlpha) => Some(1),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LsbOptions {
+    pub bits_per_channel: u8,
+    // channels the image doesn't have are skipped
+    pub channels: Vec<Channel>,
+}
+
+impl Default for LsbOptions {
+    fn default() -> Self {
+        LsbOptions {
+            bits_per_channel: 1,
+            channels: vec![Channel::Red, Channel::Green, Channel::Blue, Channel::Gray],
+        }
+    }
+}
+
+// byte offsets of every sample that carries payload bits, in embedding
+// order; for 16 bit images only the low byte of a sample is used
+fn carrier_offsets(image: &Image, options: &LsbOptions) -> Result<Vec<usize>, LsbError> {
+    if image.color_type == ColorType::Indexed || image.bit_depth < 8 {
+        return Err(LsbError::UnsupportedImage);
+    }
+    if options.bits_per_channel == 0 || options.bits_per_channel > 8 {
+        return Err(LsbError::InvalidBitsPerChannel(options.bits_per_channel));
+    }
+    let mut indices: Vec<usize> = options.channels.iter().filter_map(|c| c.index_in(image.color_type)).collect();
+    indices.sort_unstable();
+    indices.dedup();
+    if indices.is_empty() {
+        return Err(LsbError::NoUsableChannels);
+    }
+    let sample_bytes = image.bit_depth as usize / 8;
+    let channels = image.color_type.channels();
+    let pixel_count = image.width as usize * image.height as usize;
+    Ok((0..pixel_count)
+        .flat_map(|pixel| indices.iter().map(move |&index| ((pixel * channels + index) + 1) * sample_bytes - 1))
+        .collect())
+}
+
+pub fn capacity(image: &Image, options: &LsbOptions) -> Result<usize, LsbError> {
+    let bits = carrier_offsets(image, options)?.len() * options.bits_per_channel as usize;
+    Ok((bits / 8).saturating_sub(HEADER_LEN))
+}
+
+pub fn embed(image: &mut Image, payload: &[u8], options: &LsbOptions) -> Result<(), LsbError> {
+    let offsets = carrier_offsets(image, options)?;
+    let available = (offsets.len() * options.bits_per_channel as usize / 8).saturating_sub(HEADER_LEN);
+    if payload.len() > available || payload.len() > u32::MAX as usize {
+        return Err(LsbError::PayloadTooLarge { needed: payload.len(), available });
+    }
+    let checksum = Crc::<u32>::new(&CRC_32_ISO_HDLC).checksum(payload);
+    let stream: Vec<u8> = (payload.len() as u32)
+        .to_be_bytes()
+        .iter()
+        .chain(checksum.to_be_bytes().iter())
+        .chain(payload.iter())
+        .copied()
+        .collect();
+
+    let bits = options.bits_per_channel as usize;
+    let mask = ((1u16 << bits) - 1) as u8;
+    let mut stream_bits = stream.iter().flat_map(|&byte| (0..8).rev().map(move |i| (byte >> i) & 1));
+    for &offset in offsets.iter() {
+        let mut value = 0;
+        let mut taken = 0;
+        for bit in stream_bits.by_ref().take(bits) {
+            value = (value << 1) | bit;
+            taken += 1;
+        }
+        if taken == 0 {
+            break;
+        }
+        // a partially filled last sample keeps its remaining low bits
+        let shift = bits - taken;
+        let keep = !(mask >> shift << shift);
+        image.pixels[offset] = (image.pixels[offset] & keep) | (value << shift);
+    }
+    Ok(())
+}
+
+fn read_bytes(image: &Image, offsets: &[usize], bits: usize, start: usize, count: usize) -> Vec<u8> {
+    let bit_at = |n: usize| (image.pixels[offsets[n / bits]] >> (bits - 1 - n % bits)) & 1;
+    (start..start + count)
+        .map(|byte| (0..8).fold(0, |acc, i| (acc << 1) | bit_at(byte * 8 + i)))
+        .collect()
+}
+
+pub fn extract(image: &Image, options: &LsbOptions) -> Result<Vec<u8>, LsbError> {
+    let offsets = carrier_offsets(image, options)?;
+    let bits = options.bits_per_channel as usize;
+    let total_bytes = offsets.len() * bits / 8;
+    if total_bytes < HEADER_LEN {
+        return Err(LsbError::InvalidHeader);
+    }
+    let header = read_bytes(image, &offsets, bits, 0, HEADER_LEN);
+    let length = u32::from_be_bytes([header[0], header[1], header[2], header[3]]) as usize;
+    let checksum = u32::from_be_bytes([header[4], header[5], header[6], header[7]]);
+    if length > total_bytes - HEADER_LEN {
+        return Err(LsbError::InvalidHeader);
+    }
+    let payload = read_bytes(image, &offsets, bits, HEADER_LEN, length);
+    match Crc::<u32>::new(&CRC_32_ISO_HDLC).checksum(&payload) == checksum {
+        true => Ok(payload),
+        false => Err(LsbError::ChecksumMismatch),
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum LsbError {
+    UnsupportedImage,
+    InvalidBitsPerChannel(u8),
+    NoUsableChannels,
+    PayloadTooLarge { needed: usize, available: usize },
+    InvalidHeader,
+    ChecksumMismatch,
+}
+
+impl Display for LsbError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LsbError::UnsupportedImage => write!(f, "indexed images and bit depths below 8 can't carry a message"),
+            LsbError::InvalidBitsPerChannel(bits) => write!(f, "bits per channel must be 1 to 8, not {}", bits),
+            LsbError::NoUsableChannels => write!(f, "none of the chosen channels are in the image"),
+            LsbError::PayloadTooLarge { needed, available } => {
+                write!(f, "payload needs {} bytes, the pixels only hold {}", needed, available)
+            }
+            LsbError::InvalidHeader => write!(f, "no message header in the pixel data"),
+            LsbError::ChecksumMismatch => write!(f, "message in the pixel data fails its checksum, it was damaged or read with other --bits/--channels"),
+        }
+    }
+}
+
+impl std::error::Error for LsbError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn carrier(color_type: ColorType, bit_depth: u8) -> Image {
+        let len = 16 * 16 * color_type.channels() * bit_depth as usize / 8;
+        Image::new(16, 16, color_type, bit_depth, (0..len).map(|i| (i * 31 % 256) as u8).collect())
+    }
+
+    #[test]
+    fn test_embed_and_extract() {
+        let mut image = carrier(ColorType::Rgb, 8);
+        let original = image.clone();
+        embed(&mut image, b"hidden in plain sight", &LsbOptions::default()).unwrap();
+        assert_eq!(extract(&image, &LsbOptions::default()).unwrap(), b"hidden in plain sight");
+        assert!(image.pixels.iter().zip(original.pixels.iter()).all(|(a, b)| (a ^ b) <= 1));
+    }
+
+    #[test]
+    fn test_embed_with_options() {
+        let options = LsbOptions { bits_per_channel: 3, channels: vec![Channel::Alpha, Channel::Blue] };
+        for bit_depth in [8, 16] {
+            let mut image = carrier(ColorType::Rgba, bit_depth);
+            let original = image.clone();
+            embed(&mut image, b"three bits at a time", &options).unwrap();
+            assert_eq!(extract(&image, &options).unwrap(), b"three bits at a time");
+            // red and green samples are untouched
+            let step = 4 * bit_depth as usize / 8;
+            for pixel in (0..image.pixels.len()).step_by(step) {
+                assert_eq!(image.pixels[pixel..pixel + step / 2], original.pixels[pixel..pixel + step / 2]);
+            }
+        }
+    }
+
+    #[test]
+    fn test_grayscale_channels() {
+        let mut image = carrier(ColorType::GrayscaleAlpha, 8);
+        embed(&mut image, b"gray", &LsbOptions::default()).unwrap();
+        assert_eq!(extract(&image, &LsbOptions::default()).unwrap(), b"gray");
+
+        let options = LsbOptions { bits_per_channel: 1, channels: vec![Channel::Red] };
+        assert_eq!(embed(&mut image, b"gray", &options), Err(LsbError::NoUsableChannels));
+    }
+
+    #[test]
+    fn test_capacity() {
+        let image = carrier(ColorType::Rgb, 8);
+        assert_eq!(capacity(&image, &LsbOptions::default()).unwrap(), 16 * 16 * 3 / 8 - 8);
+        let options = LsbOptions { bits_per_channel: 2, channels: vec![Channel::Red] };
+        assert_eq!(capacity(&image, &options).unwrap(), 16 * 16 * 2 / 8 - 8);
+
+        let mut image = image;
+        let payload = vec![0; 89];
+        assert_eq!(
+            embed(&mut image, &payload, &LsbOptions::default()),
+            Err(LsbError::PayloadTooLarge { needed: 89, available: 88 })
+        );
+    }
+
+    #[test]
+    fn test_checksum_mismatch() {
+        let mut image = carrier(ColorType::Rgb, 8);
+        embed(&mut image, b"fragile", &LsbOptions::default()).unwrap();
+        // the first payload bit lives in the 65th carrier sample
+        image.pixels[64] ^= 1;
+        assert_eq!(extract(&image, &LsbOptions::default()), Err(LsbError::ChecksumMismatch));
+    }
+
+    #[test]
+    fn test_unsupported_images() {
+        let mut image = carrier(ColorType::Indexed, 8);
+        assert_eq!(embed(&mut image, b"x", &LsbOptions::default()), Err(LsbError::UnsupportedImage));
+        let image = carrier(ColorType::Grayscale, 8);
+        let options = LsbOptions { bits_per_channel: 9, channels: vec![Channel::Gray] };
+        assert_eq!(extract(&image, &options), Err(LsbError::InvalidBitsPerChannel(9)));
+    }
+}
@@ -1,11 +1,35 @@
+pub mod adam7;
+pub mod apng;
 pub mod cli;
 pub mod chunk;
 pub mod chunk_type;
+pub mod color;
 pub mod commands;
+pub mod compression;
+pub mod crypto;
+pub mod decoder;
+pub mod encoder;
+pub mod exif;
+pub mod filter;
+pub mod hex;
+pub mod ihdr;
+pub mod image;
+pub mod lsb;
+pub mod ordering;
+pub mod palette;
+pub mod payload;
+pub mod physical;
 pub mod png;
+pub mod reader;
+pub mod sealed_box;
+pub mod signature;
+pub mod split;
+pub mod text;
+pub mod writer;
 
-pub use commands::Command::{Encd, Decd, Remv, Prnt};
-pub use cli::{Encode, Decode, Remove, Print};
+pub use commands::Command::{Encd, Decd, Remv, Prnt, Kgen, Sgnt, Vrfy, Txts, Exfs, Sgps, Pltt, Sdpi, Tuch, Frms, Anim};
+pub use cli::{Encode, Decode, Remove, Print, Keygen, Sign, Verify, HidingMethod, PayloadSource, Selection, Text, TextAction, ShowExif, StripGps, PrintPalette, SetDpi, Touch, Frames, FramesAction, BuildAnimation};
 
 pub use chunk::Chunk;
-pub use png::Png;
\ No newline at end of file
+pub use encoder::EncodeOptions;
+pub use png::{Png, ChunkPlacement};
\ No newline at end of file
@@ -0,0 +1,201 @@
+use std::fmt::Display;
+
+use super::chunk::Chunk;
+
+// chunks that may appear at most once in a PNG datastream
+const UNIQUE_CHUNKS: [&str; 21] = [
+    "IHDR", "PLTE", "IEND", "cHRM", "gAMA", "iCCP", "sBIT", "sRGB", "cICP", "mDCV", "cLLI",
+    "bKGD", "hIST", "tRNS", "pHYs", "tIME", "eXIf", "acTL", "oFFs", "sCAL", "pCAL",
+];
+
+// chunks that must appear before both PLTE and the first IDAT
+const BEFORE_PLTE: [&str; 8] = ["cHRM", "gAMA", "iCCP", "sBIT", "sRGB", "cICP", "mDCV", "cLLI"];
+
+// chunks that must appear before the first IDAT
+const BEFORE_IDAT: [&str; 10] = [
+    "PLTE", "tRNS", "bKGD", "hIST", "pHYs", "sPLT", "oFFs", "sCAL", "pCAL", "acTL",
+];
+
+// chunks that must appear after PLTE, when a PLTE is present
+const AFTER_PLTE: [&str; 3] = ["tRNS", "bKGD", "hIST"];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ViolationKind {
+    MissingIhdr,
+    IhdrNotFirst,
+    MissingIdat,
+    NonConsecutiveIdat,
+    MissingIend,
+    IendNotLast,
+    Duplicate,
+    MustPrecede(&'static str),
+    MustFollow(&'static str),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OrderingViolation {
+    pub index: usize,
+    pub chunk_type: String,
+    pub kind: ViolationKind,
+}
+
+impl OrderingViolation {
+    fn new(index: usize, chunk_type: &str, kind: ViolationKind) -> OrderingViolation {
+        OrderingViolation { index, chunk_type: chunk_type.to_string(), kind }
+    }
+}
+
+impl Display for OrderingViolation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "chunk {} ({}): ", self.index, self.chunk_type)?;
+        match self.kind {
+            ViolationKind::MissingIhdr => write!(f, "missing IHDR chunk"),
+            ViolationKind::IhdrNotFirst => write!(f, "IHDR must be the first chunk"),
+            ViolationKind::MissingIdat => write!(f, "missing IDAT chunk"),
+            ViolationKind::NonConsecutiveIdat => write!(f, "IDAT chunks must be consecutive"),
+            ViolationKind::MissingIend => write!(f, "missing IEND chunk"),
+            ViolationKind::IendNotLast => write!(f, "IEND must be the last chunk"),
+            ViolationKind::Duplicate => write!(f, "chunk may only appear once"),
+            ViolationKind::MustPrecede(other) => write!(f, "must appear before {}", other),
+            ViolationKind::MustFollow(other) => write!(f, "must appear after {}", other),
+        }
+    }
+}
+
+pub fn validate_order(chunks: &[Chunk]) -> Vec<OrderingViolation> {
+    let types: Vec<String> = chunks.iter().map(|chunk| chunk.chunk_type().to_string()).collect();
+    let first_plte = types.iter().position(|t| t == "PLTE");
+    let first_idat = types.iter().position(|t| t == "IDAT");
+    let mut violations = Vec::new();
+
+    if !types.iter().any(|t| t == "IHDR") {
+        violations.push(OrderingViolation::new(0, "IHDR", ViolationKind::MissingIhdr));
+    }
+    if first_idat.is_none() {
+        violations.push(OrderingViolation::new(types.len(), "IDAT", ViolationKind::MissingIdat));
+    }
+
+    let mut idat_run_ended = false;
+    for (index, chunk_type) in types.iter().enumerate() {
+        let chunk_type = chunk_type.as_str();
+        let mut push = |kind| violations.push(OrderingViolation::new(index, chunk_type, kind));
+
+        if UNIQUE_CHUNKS.contains(&chunk_type) && types[..index].iter().any(|t| t == chunk_type) {
+            push(ViolationKind::Duplicate);
+        }
+        match chunk_type {
+            "IHDR" if index != 0 => push(ViolationKind::IhdrNotFirst),
+            "IEND" if index != types.len() - 1 => push(ViolationKind::IendNotLast),
+            "IDAT" if idat_run_ended => push(ViolationKind::NonConsecutiveIdat),
+            _ => {}
+        }
+        if first_idat.is_some_and(|idat| idat < index) && types[index - 1] == "IDAT" && chunk_type != "IDAT" {
+            idat_run_ended = true;
+        }
+        if BEFORE_PLTE.contains(&chunk_type) && first_plte.is_some_and(|plte| plte < index) {
+            push(ViolationKind::MustPrecede("PLTE"));
+        }
+        if (BEFORE_PLTE.contains(&chunk_type) || BEFORE_IDAT.contains(&chunk_type))
+            && first_idat.is_some_and(|idat| idat < index)
+        {
+            push(ViolationKind::MustPrecede("IDAT"));
+        }
+        if AFTER_PLTE.contains(&chunk_type) && first_plte.is_some_and(|plte| plte > index) {
+            push(ViolationKind::MustFollow("PLTE"));
+        }
+    }
+
+    if !types.iter().any(|t| t == "IEND") {
+        violations.push(OrderingViolation::new(types.len(), "IEND", ViolationKind::MissingIend));
+    }
+    violations
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk_type::ChunkType;
+    use std::str::FromStr;
+
+    fn chunks_of(types: &[&str]) -> Vec<Chunk> {
+        types
+            .iter()
+            .map(|t| Chunk::new(ChunkType::from_str(t).unwrap(), Vec::new()))
+            .collect()
+    }
+
+    fn kinds_at(violations: &[OrderingViolation]) -> Vec<(usize, ViolationKind)> {
+        violations.iter().map(|v| (v.index, v.kind.clone())).collect()
+    }
+
+    #[test]
+    fn test_valid_order() {
+        let chunks = chunks_of(&["IHDR", "gAMA", "PLTE", "tRNS", "IDAT", "IDAT", "tEXt", "IEND"]);
+        assert!(validate_order(&chunks).is_empty());
+    }
+
+    #[test]
+    fn test_missing_critical_chunks() {
+        let chunks = chunks_of(&["tEXt"]);
+        assert_eq!(
+            kinds_at(&validate_order(&chunks)),
+            vec![
+                (0, ViolationKind::MissingIhdr),
+                (1, ViolationKind::MissingIdat),
+                (1, ViolationKind::MissingIend),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_ihdr_and_iend_position() {
+        let chunks = chunks_of(&["tEXt", "IHDR", "IDAT", "IEND", "ruSt"]);
+        assert_eq!(
+            kinds_at(&validate_order(&chunks)),
+            vec![(1, ViolationKind::IhdrNotFirst), (3, ViolationKind::IendNotLast)]
+        );
+    }
+
+    #[test]
+    fn test_duplicate_unique_chunks() {
+        let chunks = chunks_of(&["IHDR", "IDAT", "IEND", "IEND"]);
+        assert_eq!(
+            kinds_at(&validate_order(&chunks)),
+            vec![(2, ViolationKind::IendNotLast), (3, ViolationKind::Duplicate)]
+        );
+    }
+
+    #[test]
+    fn test_plte_after_idat() {
+        let chunks = chunks_of(&["IHDR", "IDAT", "PLTE", "IEND"]);
+        assert_eq!(
+            kinds_at(&validate_order(&chunks)),
+            vec![(2, ViolationKind::MustPrecede("IDAT"))]
+        );
+    }
+
+    #[test]
+    fn test_non_consecutive_idat() {
+        let chunks = chunks_of(&["IHDR", "IDAT", "tEXt", "IDAT", "IDAT", "IEND"]);
+        assert_eq!(
+            kinds_at(&validate_order(&chunks)),
+            vec![(3, ViolationKind::NonConsecutiveIdat), (4, ViolationKind::NonConsecutiveIdat)]
+        );
+    }
+
+    #[test]
+    fn test_palette_relative_order() {
+        let chunks = chunks_of(&["IHDR", "tRNS", "PLTE", "gAMA", "IDAT", "IEND"]);
+        assert_eq!(
+            kinds_at(&validate_order(&chunks)),
+            vec![(1, ViolationKind::MustFollow("PLTE")), (3, ViolationKind::MustPrecede("PLTE"))]
+        );
+    }
+
+    #[test]
+    fn test_violation_display() {
+        let chunks = chunks_of(&["IHDR", "IDAT", "PLTE", "IEND"]);
+        let violations = validate_order(&chunks);
+        assert_eq!(violations[0].to_string(), "chunk 2 (PLTE): must appear before IDAT");
+    }
+}
@@ -0,0 +1,441 @@
+use std::fmt::Display;
+
+use super::chunk::{chunk, read_u16, Chunk};
+use super::ihdr::{ColorType, Ihdr};
+use super::png::Png;
+use super::text;
+
+fn check_type(chunk: &Chunk, expected: &'static str) -> Result<(), PaletteError> {
+    match chunk.is_type(expected) {
+        true => Ok(()),
+        false => Err(PaletteError::WrongChunkType(expected)),
+    }
+}
+
+// gray and RGB samples in tRNS and bKGD must fit the image bit depth
+fn check_sample(sample: u16, ihdr: &Ihdr) -> Result<u16, PaletteError> {
+    match (sample as u32) < (1u32 << ihdr.bit_depth) {
+        true => Ok(sample),
+        false => Err(PaletteError::SampleOutOfRange(sample)),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Plte {
+    pub entries: Vec<[u8; 3]>,
+}
+
+impl Plte {
+    // grayscale images can't carry a palette, and indexed ones can't have
+    // more entries than their bit depth can address
+    pub fn validate(&self, ihdr: &Ihdr) -> Result<(), PaletteError> {
+        if self.entries.is_empty() || self.entries.len() > 256 {
+            return Err(PaletteError::InvalidLength("PLTE"));
+        }
+        match ihdr.color_type {
+            ColorType::Grayscale | ColorType::GrayscaleAlpha => Err(PaletteError::NotAllowed("PLTE", ihdr.color_type)),
+            ColorType::Indexed if self.entries.len() > 1 << ihdr.bit_depth => {
+                Err(PaletteError::TooManyEntries(self.entries.len()))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    pub fn to_chunk(&self) -> Result<Chunk, PaletteError> {
+        if self.entries.is_empty() || self.entries.len() > 256 {
+            return Err(PaletteError::InvalidLength("PLTE"));
+        }
+        Ok(chunk("PLTE", self.entries.concat()))
+    }
+}
+
+impl TryFrom<&Chunk> for Plte {
+    type Error = PaletteError;
+
+    fn try_from(chunk: &Chunk) -> Result<Self, Self::Error> {
+        check_type(chunk, "PLTE")?;
+        let data = chunk.data();
+        if data.is_empty() || !data.len().is_multiple_of(3) || data.len() > 256 * 3 {
+            return Err(PaletteError::InvalidLength("PLTE"));
+        }
+        Ok(Plte { entries: data.chunks_exact(3).map(|rgb| [rgb[0], rgb[1], rgb[2]]).collect() })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Trns {
+    // pixels of exactly this gray or RGB value are fully transparent
+    Gray(u16),
+    Rgb(u16, u16, u16),
+    // alpha for the first palette entries, the rest stay opaque
+    Indexed(Vec<u8>),
+}
+
+impl Trns {
+    pub fn parse(chunk: &Chunk, ihdr: &Ihdr, plte: Option<&Plte>) -> Result<Trns, PaletteError> {
+        check_type(chunk, "tRNS")?;
+        let data = chunk.data();
+        match ihdr.color_type {
+            ColorType::Grayscale if data.len() == 2 => Ok(Trns::Gray(check_sample(read_u16(data, 0), ihdr)?)),
+            ColorType::Rgb if data.len() == 6 => Ok(Trns::Rgb(
+                check_sample(read_u16(data, 0), ihdr)?,
+                check_sample(read_u16(data, 2), ihdr)?,
+                check_sample(read_u16(data, 4), ihdr)?,
+            )),
+            ColorType::Indexed => {
+                let entries = plte.ok_or(PaletteError::MissingPalette)?.entries.len();
+                match data.len() <= entries {
+                    true => Ok(Trns::Indexed(data.to_vec())),
+                    false => Err(PaletteError::TooManyEntries(data.len())),
+                }
+            }
+            ColorType::GrayscaleAlpha | ColorType::Rgba => Err(PaletteError::NotAllowed("tRNS", ihdr.color_type)),
+            _ => Err(PaletteError::InvalidLength("tRNS")),
+        }
+    }
+
+    pub fn alpha(&self, index: usize) -> u8 {
+        match self {
+            Trns::Indexed(alphas) => alphas.get(index).copied().unwrap_or(255),
+            _ => 255,
+        }
+    }
+
+    // indexed alphas are checked against the palette they go with
+    pub fn to_chunk(&self, plte: Option<&Plte>) -> Result<Chunk, PaletteError> {
+        let data = match self {
+            Trns::Gray(gray) => gray.to_be_bytes().to_vec(),
+            Trns::Rgb(r, g, b) => [r, g, b].iter().flat_map(|s| s.to_be_bytes()).collect(),
+            Trns::Indexed(alphas) => {
+                let entries = plte.ok_or(PaletteError::MissingPalette)?.entries.len();
+                if alphas.len() > entries {
+                    return Err(PaletteError::TooManyEntries(alphas.len()));
+                }
+                alphas.clone()
+            }
+        };
+        Ok(chunk("tRNS", data))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Bkgd {
+    Gray(u16),
+    Rgb(u16, u16, u16),
+    PaletteIndex(u8),
+}
+
+impl Bkgd {
+    pub fn parse(chunk: &Chunk, ihdr: &Ihdr, plte: Option<&Plte>) -> Result<Bkgd, PaletteError> {
+        check_type(chunk, "bKGD")?;
+        let data = chunk.data();
+        match ihdr.color_type {
+            ColorType::Grayscale | ColorType::GrayscaleAlpha if data.len() == 2 => {
+                Ok(Bkgd::Gray(check_sample(read_u16(data, 0), ihdr)?))
+            }
+            ColorType::Rgb | ColorType::Rgba if data.len() == 6 => Ok(Bkgd::Rgb(
+                check_sample(read_u16(data, 0), ihdr)?,
+                check_sample(read_u16(data, 2), ihdr)?,
+                check_sample(read_u16(data, 4), ihdr)?,
+            )),
+            ColorType::Indexed if data.len() == 1 => {
+                let entries = plte.ok_or(PaletteError::MissingPalette)?.entries.len();
+                match (data[0] as usize) < entries {
+                    true => Ok(Bkgd::PaletteIndex(data[0])),
+                    false => Err(PaletteError::IndexOutOfRange(data[0])),
+                }
+            }
+            _ => Err(PaletteError::InvalidLength("bKGD")),
+        }
+    }
+
+    // a palette index is checked against the palette it goes with
+    pub fn to_chunk(self, plte: Option<&Plte>) -> Result<Chunk, PaletteError> {
+        let data = match self {
+            Bkgd::Gray(gray) => gray.to_be_bytes().to_vec(),
+            Bkgd::Rgb(r, g, b) => [r, g, b].iter().flat_map(|s| s.to_be_bytes()).collect(),
+            Bkgd::PaletteIndex(index) => {
+                let entries = plte.ok_or(PaletteError::MissingPalette)?.entries.len();
+                if index as usize >= entries {
+                    return Err(PaletteError::IndexOutOfRange(index));
+                }
+                vec![index]
+            }
+        };
+        Ok(chunk("bKGD", data))
+    }
+}
+
+// approximate usage frequency of every palette entry
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Hist {
+    pub frequencies: Vec<u16>,
+}
+
+impl Hist {
+    pub fn parse(chunk: &Chunk, plte: &Plte) -> Result<Hist, PaletteError> {
+        check_type(chunk, "hIST")?;
+        if chunk.data().len() != plte.entries.len() * 2 {
+            return Err(PaletteError::InvalidLength("hIST"));
+        }
+        Ok(Hist { frequencies: (0..plte.entries.len()).map(|i| read_u16(chunk.data(), i * 2)).collect() })
+    }
+
+    // one frequency for every palette entry
+    pub fn to_chunk(&self, plte: &Plte) -> Result<Chunk, PaletteError> {
+        if self.frequencies.len() != plte.entries.len() {
+            return Err(PaletteError::InvalidLength("hIST"));
+        }
+        Ok(chunk("hIST", self.frequencies.iter().flat_map(|f| f.to_be_bytes()).collect()))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SpltEntry {
+    pub red: u16,
+    pub green: u16,
+    pub blue: u16,
+    pub alpha: u16,
+    pub frequency: u16,
+}
+
+// a suggested palette, independent of the image's own color type
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Splt {
+    pub name: String,
+    pub sample_depth: u8,
+    pub entries: Vec<SpltEntry>,
+}
+
+impl Splt {
+    // the fields are public, so nothing is truncated to fit: the name must be
+    // a keyword and 8 bit samples must stay below 256
+    pub fn to_chunk(&self) -> Result<Chunk, PaletteError> {
+        let mut data = text::validate_keyword(&self.name).map_err(|_| PaletteError::InvalidName)?;
+        data.push(0);
+        data.push(self.sample_depth);
+        for entry in self.entries.iter() {
+            for sample in [entry.red, entry.green, entry.blue, entry.alpha] {
+                match self.sample_depth {
+                    8 => data.push(u8::try_from(sample).map_err(|_| PaletteError::SampleOutOfRange(sample))?),
+                    16 => data.extend_from_slice(&sample.to_be_bytes()),
+                    depth => return Err(PaletteError::InvalidSampleDepth(depth)),
+                }
+            }
+            data.extend_from_slice(&entry.frequency.to_be_bytes());
+        }
+        Ok(chunk("sPLT", data))
+    }
+}
+
+impl TryFrom<&Chunk> for Splt {
+    type Error = PaletteError;
+
+    fn try_from(chunk: &Chunk) -> Result<Self, Self::Error> {
+        check_type(chunk, "sPLT")?;
+        let data = chunk.data();
+        let name_end = data.iter().position(|&byte| byte == 0).ok_or(PaletteError::InvalidLength("sPLT"))?;
+        if name_end == 0 || name_end > 79 {
+            return Err(PaletteError::InvalidName);
+        }
+        let name = data[..name_end].iter().map(|&byte| byte as char).collect();
+        let sample_depth = *data.get(name_end + 1).ok_or(PaletteError::InvalidLength("sPLT"))?;
+        let entry_len = match sample_depth {
+            8 => 6,
+            16 => 10,
+            depth => return Err(PaletteError::InvalidSampleDepth(depth)),
+        };
+        let entries = &data[name_end + 2..];
+        if !entries.len().is_multiple_of(entry_len) {
+            return Err(PaletteError::InvalidLength("sPLT"));
+        }
+        let entries = entries
+            .chunks_exact(entry_len)
+            .map(|entry| {
+                let sample = |i: usize| match sample_depth {
+                    8 => entry[i] as u16,
+                    _ => read_u16(entry, i * 2),
+                };
+                SpltEntry { red: sample(0), green: sample(1), blue: sample(2), alpha: sample(3), frequency: read_u16(entry, entry_len - 2) }
+            })
+            .collect();
+        Ok(Splt { name, sample_depth, entries })
+    }
+}
+
+// everything palette related in a PNG, checked against its IHDR
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PaletteInfo {
+    pub plte: Option<Plte>,
+    pub trns: Option<Trns>,
+    pub bkgd: Option<Bkgd>,
+    pub hist: Option<Hist>,
+    pub suggested: Vec<Splt>,
+}
+
+impl PaletteInfo {
+    pub fn from_png(png: &Png) -> Result<PaletteInfo, PaletteError> {
+        let ihdr = png.ihdr().map_err(|err| PaletteError::Ihdr(format!("{:?}", err)))?;
+        let plte = png.chunk_by_type("PLTE").map(Plte::try_from).transpose()?;
+        if let Some(plte) = &plte {
+            plte.validate(&ihdr)?;
+        }
+        if ihdr.color_type == ColorType::Indexed && plte.is_none() {
+            return Err(PaletteError::MissingPalette);
+        }
+        let trns = png.chunk_by_type("tRNS").map(|chunk| Trns::parse(chunk, &ihdr, plte.as_ref())).transpose()?;
+        let bkgd = png.chunk_by_type("bKGD").map(|chunk| Bkgd::parse(chunk, &ihdr, plte.as_ref())).transpose()?;
+        let hist = match (png.chunk_by_type("hIST"), &plte) {
+            (Some(chunk), Some(plte)) => Some(Hist::parse(chunk, plte)?),
+            (Some(_), None) => return Err(PaletteError::MissingPalette),
+            (None, _) => None,
+        };
+        let suggested = png.chunks_by_type("sPLT").into_iter().map(Splt::try_from).collect::<Result<_, _>>()?;
+        Ok(PaletteInfo { plte, trns, bkgd, hist, suggested })
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum PaletteError {
+    WrongChunkType(&'static str),
+    InvalidLength(&'static str),
+    NotAllowed(&'static str, ColorType),
+    TooManyEntries(usize),
+    MissingPalette,
+    SampleOutOfRange(u16),
+    IndexOutOfRange(u8),
+    InvalidName,
+    InvalidSampleDepth(u8),
+    Ihdr(String),
+}
+
+impl Display for PaletteError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PaletteError::WrongChunkType(expected) => write!(f, "expected a {} chunk", expected),
+            PaletteError::InvalidLength(chunk_type) => write!(f, "{} chunk has an invalid length", chunk_type),
+            PaletteError::NotAllowed(chunk_type, color_type) => {
+                write!(f, "{} is not allowed for {:?} images", chunk_type, color_type)
+            }
+            PaletteError::TooManyEntries(entries) => write!(f, "{} entries don't fit the palette", entries),
+            PaletteError::MissingPalette => write!(f, "the image has no PLTE chunk"),
+            PaletteError::SampleOutOfRange(sample) => write!(f, "sample {} doesn't fit the bit depth", sample),
+            PaletteError::IndexOutOfRange(index) => write!(f, "palette index {} is out of range", index),
+            PaletteError::InvalidName => write!(f, "sPLT palette name must be 1 to 79 characters"),
+            PaletteError::InvalidSampleDepth(depth) => write!(f, "sPLT sample depth must be 8 or 16, not {}", depth),
+            PaletteError::Ihdr(reason) => write!(f, "invalid IHDR: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for PaletteError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ihdr(color_type: ColorType, bit_depth: u8) -> Ihdr {
+        Ihdr::new(4, 4, bit_depth, color_type).unwrap()
+    }
+
+    fn plte(entries: usize) -> Plte {
+        Plte { entries: (0..entries).map(|i| [i as u8, 0, 255 - i as u8]).collect() }
+    }
+
+    #[test]
+    fn test_plte() {
+        let palette = plte(3);
+        assert_eq!(Plte::try_from(&palette.to_chunk().unwrap()).unwrap(), palette);
+        assert!(palette.validate(&ihdr(ColorType::Indexed, 2)).is_ok());
+        assert!(palette.validate(&ihdr(ColorType::Rgb, 8)).is_ok());
+        assert_eq!(plte(5).validate(&ihdr(ColorType::Indexed, 2)), Err(PaletteError::TooManyEntries(5)));
+        assert_eq!(
+            palette.validate(&ihdr(ColorType::Grayscale, 8)),
+            Err(PaletteError::NotAllowed("PLTE", ColorType::Grayscale))
+        );
+        assert_eq!(Plte::try_from(&chunk("PLTE", vec![0; 4])), Err(PaletteError::InvalidLength("PLTE")));
+        assert_eq!(plte(0).to_chunk().err(), Some(PaletteError::InvalidLength("PLTE")));
+        assert_eq!(Plte { entries: vec![[0; 3]; 257] }.to_chunk().err(), Some(PaletteError::InvalidLength("PLTE")));
+    }
+
+    #[test]
+    fn test_trns() {
+        let palette = plte(3);
+        let indexed = ihdr(ColorType::Indexed, 8);
+        let trns = Trns::parse(&chunk("tRNS", vec![0, 128]), &indexed, Some(&palette)).unwrap();
+        assert_eq!((trns.alpha(0), trns.alpha(1), trns.alpha(2)), (0, 128, 255));
+        assert_eq!(Trns::parse(&trns.to_chunk(Some(&palette)).unwrap(), &indexed, Some(&palette)).unwrap(), trns);
+        assert_eq!(Trns::Indexed(vec![0; 4]).to_chunk(Some(&palette)).err(), Some(PaletteError::TooManyEntries(4)));
+        assert_eq!(trns.to_chunk(None).err(), Some(PaletteError::MissingPalette));
+        assert_eq!(
+            Trns::parse(&chunk("tRNS", vec![0; 4]), &indexed, Some(&palette)),
+            Err(PaletteError::TooManyEntries(4))
+        );
+
+        let gray = ihdr(ColorType::Grayscale, 4);
+        assert_eq!(Trns::parse(&chunk("tRNS", vec![0, 15]), &gray, None), Ok(Trns::Gray(15)));
+        assert_eq!(Trns::parse(&chunk("tRNS", vec![0, 16]), &gray, None), Err(PaletteError::SampleOutOfRange(16)));
+        let rgb = Trns::Rgb(1, 2, 3);
+        assert_eq!(Trns::parse(&rgb.to_chunk(None).unwrap(), &ihdr(ColorType::Rgb, 8), None), Ok(rgb));
+        assert_eq!(
+            Trns::parse(&chunk("tRNS", vec![0, 0]), &ihdr(ColorType::Rgba, 8), None),
+            Err(PaletteError::NotAllowed("tRNS", ColorType::Rgba))
+        );
+    }
+
+    #[test]
+    fn test_bkgd_and_hist() {
+        let palette = plte(3);
+        let indexed = ihdr(ColorType::Indexed, 8);
+        assert_eq!(Bkgd::parse(&chunk("bKGD", vec![2]), &indexed, Some(&palette)), Ok(Bkgd::PaletteIndex(2)));
+        assert_eq!(Bkgd::parse(&chunk("bKGD", vec![3]), &indexed, Some(&palette)), Err(PaletteError::IndexOutOfRange(3)));
+        let background = Bkgd::Rgb(0, 65535, 0);
+        assert_eq!(Bkgd::parse(&background.to_chunk(None).unwrap(), &ihdr(ColorType::Rgba, 16), None), Ok(background));
+        assert_eq!(Bkgd::PaletteIndex(3).to_chunk(Some(&palette)).err(), Some(PaletteError::IndexOutOfRange(3)));
+
+        let hist = Hist { frequencies: vec![10, 0, 65535] };
+        assert_eq!(Hist::parse(&hist.to_chunk(&palette).unwrap(), &palette), Ok(hist));
+        assert_eq!(Hist { frequencies: vec![1] }.to_chunk(&palette).err(), Some(PaletteError::InvalidLength("hIST")));
+        assert_eq!(Hist::parse(&chunk("hIST", vec![0; 4]), &palette), Err(PaletteError::InvalidLength("hIST")));
+    }
+
+    #[test]
+    fn test_splt() {
+        for sample_depth in [8, 16] {
+            let entry = SpltEntry { red: 200, green: 100, blue: 50, alpha: 255, frequency: 7 };
+            let splt = Splt { name: "web safe".to_string(), sample_depth, entries: vec![entry, entry] };
+            assert_eq!(Splt::try_from(&splt.to_chunk().unwrap()), Ok(splt));
+        }
+        let entry = SpltEntry { red: 256, green: 0, blue: 0, alpha: 0, frequency: 0 };
+        let mut splt = Splt { name: "wide".to_string(), sample_depth: 8, entries: vec![entry] };
+        assert_eq!(splt.to_chunk().err(), Some(PaletteError::SampleOutOfRange(256)));
+        splt.sample_depth = 12;
+        assert_eq!(splt.to_chunk().err(), Some(PaletteError::InvalidSampleDepth(12)));
+        splt.name = "\u{65e5}".to_string();
+        assert_eq!(splt.to_chunk().err(), Some(PaletteError::InvalidName));
+        assert_eq!(Splt::try_from(&chunk("sPLT", b"name\0\x04".to_vec())), Err(PaletteError::InvalidSampleDepth(4)));
+        assert_eq!(Splt::try_from(&chunk("sPLT", b"\0\x08".to_vec())), Err(PaletteError::InvalidName));
+    }
+
+    #[test]
+    fn test_palette_info() {
+        let header = ihdr(ColorType::Indexed, 8);
+        let png = Png::from_chunks(vec![
+            header.to_chunk(),
+            plte(2).to_chunk().unwrap(),
+            chunk("tRNS", vec![64]),
+            chunk("bKGD", vec![1]),
+            chunk("IDAT", Vec::new()),
+            chunk("IEND", Vec::new()),
+        ])
+        .unwrap();
+        let info = PaletteInfo::from_png(&png).unwrap();
+        assert_eq!(info.plte, Some(plte(2)));
+        assert_eq!(info.trns.unwrap().alpha(0), 64);
+        assert_eq!(info.bkgd, Some(Bkgd::PaletteIndex(1)));
+        assert!(info.hist.is_none() && info.suggested.is_empty());
+
+        let png = Png::from_chunks(vec![header.to_chunk(), chunk("IDAT", Vec::new()), chunk("IEND", Vec::new())]).unwrap();
+        assert_eq!(PaletteInfo::from_png(&png), Err(PaletteError::MissingPalette));
+    }
+}
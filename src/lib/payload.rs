@@ -0,0 +1,147 @@
+use std::ffi::OsStr;
+use std::fmt::Display;
+use std::path::Path;
+
+pub const MAGIC: [u8; 4] = *b"PNGf";
+const VERSION: u8 = 1;
+
+// Layout: magic | version | name length (u16) | UTF-8 name | size (u64) |
+// contents. An empty name marks data that came in without a file, e.g. stdin.
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FilePayload {
+    pub name: String,
+    pub contents: Vec<u8>,
+}
+
+impl FilePayload {
+    pub fn new(name: &str, contents: Vec<u8>) -> FilePayload {
+        FilePayload { name: name.to_string(), contents }
+    }
+
+    pub fn to_bytes(&self) -> Result<Vec<u8>, PayloadError> {
+        if self.name.len() > u16::MAX as usize {
+            return Err(PayloadError::NameTooLong);
+        }
+        let mut bytes = Vec::with_capacity(4 + 1 + 2 + self.name.len() + 8 + self.contents.len());
+        bytes.extend_from_slice(&MAGIC);
+        bytes.push(VERSION);
+        bytes.extend_from_slice(&(self.name.len() as u16).to_be_bytes());
+        bytes.extend_from_slice(self.name.as_bytes());
+        bytes.extend_from_slice(&(self.contents.len() as u64).to_be_bytes());
+        bytes.extend_from_slice(&self.contents);
+        Ok(bytes)
+    }
+
+    // the stored name comes from whoever made the image, so it is only used
+    // when it is a plain file name that can't point out of the target directory
+    pub fn file_name(&self) -> Result<&str, PayloadError> {
+        let name = self.name.as_str();
+        let plain = !name.contains(['/', '\\']) && Path::new(name).file_name() == Some(OsStr::new(name));
+        match plain && name != "." && name != ".." {
+            true => Ok(name),
+            false => Err(PayloadError::UnsafeName(self.name.clone())),
+        }
+    }
+}
+
+pub fn is_file(data: &[u8]) -> bool {
+    data.starts_with(&MAGIC)
+}
+
+impl TryFrom<&[u8]> for FilePayload {
+    type Error = PayloadError;
+
+    fn try_from(data: &[u8]) -> Result<Self, Self::Error> {
+        if !is_file(data) || data.len() < 7 {
+            return Err(PayloadError::InvalidHeader);
+        }
+        if data[4] != VERSION {
+            return Err(PayloadError::UnsupportedVersion(data[4]));
+        }
+        let name_len = u16::from_be_bytes([data[5], data[6]]) as usize;
+        let size_at = 7 + name_len;
+        if data.len() < size_at + 8 {
+            return Err(PayloadError::InvalidHeader);
+        }
+        let name = std::str::from_utf8(&data[7..size_at]).map_err(|_| PayloadError::InvalidName)?;
+        let size = u64::from_be_bytes(data[size_at..size_at + 8].try_into().unwrap());
+        let contents = &data[size_at + 8..];
+        if contents.len() as u64 != size {
+            return Err(PayloadError::SizeMismatch { expected: size, actual: contents.len() as u64 });
+        }
+        Ok(FilePayload::new(name, contents.to_vec()))
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum PayloadError {
+    NameTooLong,
+    InvalidHeader,
+    UnsupportedVersion(u8),
+    InvalidName,
+    UnsafeName(String),
+    SizeMismatch { expected: u64, actual: u64 },
+}
+
+impl Display for PayloadError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PayloadError::NameTooLong => write!(f, "file names are limited to 65535 bytes"),
+            PayloadError::InvalidHeader => write!(f, "payload is not a file or its header is damaged"),
+            PayloadError::UnsupportedVersion(version) => write!(f, "unsupported file payload version {}", version),
+            PayloadError::InvalidName => write!(f, "the stored file name is not valid UTF-8"),
+            PayloadError::UnsafeName(name) => write!(f, "refusing to use the stored file name {:?}", name),
+            PayloadError::SizeMismatch { expected, actual } => {
+                write!(f, "file is {} bytes, header says {}", actual, expected)
+            }
+        }
+    }
+}
+
+impl std::error::Error for PayloadError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_file_round_trip() {
+        let contents: Vec<u8> = (0..=255).collect();
+        let bytes = FilePayload::new("bytes.bin", contents.clone()).to_bytes().unwrap();
+        assert!(is_file(&bytes));
+        let file = FilePayload::try_from(bytes.as_slice()).unwrap();
+        assert_eq!(file.name, "bytes.bin");
+        assert_eq!(file.contents, contents);
+    }
+
+    #[test]
+    fn test_nameless_file() {
+        let bytes = FilePayload::new("", b"from stdin".to_vec()).to_bytes().unwrap();
+        assert_eq!(FilePayload::try_from(bytes.as_slice()).unwrap(), FilePayload::new("", b"from stdin".to_vec()));
+    }
+
+    #[test]
+    fn test_invalid_file_payloads() {
+        assert_eq!(FilePayload::try_from(&b"just a message"[..]), Err(PayloadError::InvalidHeader));
+
+        let mut bytes = FilePayload::new("a.txt", b"abc".to_vec()).to_bytes().unwrap();
+        bytes.pop();
+        assert_eq!(
+            FilePayload::try_from(bytes.as_slice()),
+            Err(PayloadError::SizeMismatch { expected: 3, actual: 2 })
+        );
+
+        let mut bytes = FilePayload::new("a.txt", b"abc".to_vec()).to_bytes().unwrap();
+        bytes[4] = 2;
+        assert_eq!(FilePayload::try_from(bytes.as_slice()), Err(PayloadError::UnsupportedVersion(2)));
+    }
+
+    #[test]
+    fn test_hostile_file_names() {
+        assert_eq!(FilePayload::new("notes.txt", vec![]).file_name(), Ok("notes.txt"));
+        for name in ["", ".", "..", "../../.bashrc", "/etc/x", "dir/file", "..\\evil.exe", "C:\\x"] {
+            assert_eq!(FilePayload::new(name, vec![]).file_name(), Err(PayloadError::UnsafeName(name.to_string())));
+        }
+    }
+}
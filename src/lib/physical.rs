@@ -0,0 +1,405 @@
+use std::fmt::Display;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use super::chunk::{chunk, read_u32, Chunk};
+use super::png::{ChunkPlacement, Png};
+
+const METERS_PER_INCH: f64 = 0.0254;
+
+fn check_chunk(chunk: &Chunk, expected: &'static str, len: Option<usize>) -> Result<(), PhysicalError> {
+    if !chunk.is_type(expected) {
+        return Err(PhysicalError::WrongChunkType(expected));
+    }
+    match len.is_none_or(|len| chunk.data().len() == len) {
+        true => Ok(()),
+        false => Err(PhysicalError::InvalidLength(expected)),
+    }
+}
+
+// these chunks can appear only once, so an existing one is replaced in
+// place, and only dropped once the new one is in
+pub fn set_chunk(png: &mut Png, chunk: Chunk, placement: ChunkPlacement) -> Result<(), PhysicalError> {
+    let result = match png.chunks().iter().position(|c| c.chunk_type() == chunk.chunk_type()) {
+        Some(pos) => png.replace_chunk(pos, chunk).map(|_| ()),
+        None => png.insert_chunk(chunk, placement).map(|_| ()),
+    };
+    result.map_err(|err| PhysicalError::Png(format!("{:?}", err)))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PhysUnit {
+    // only the aspect ratio is known
+    Unknown,
+    Meter,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Phys {
+    pub pixels_per_unit_x: u32,
+    pub pixels_per_unit_y: u32,
+    pub unit: PhysUnit,
+}
+
+impl Phys {
+    pub fn from_dpi(dpi_x: f64, dpi_y: f64) -> Result<Phys, PhysicalError> {
+        let per_meter = |dpi: f64| {
+            let ppm = (dpi / METERS_PER_INCH).round();
+            match ppm >= 1.0 && ppm <= u32::MAX as f64 {
+                true => Ok(ppm as u32),
+                false => Err(PhysicalError::InvalidDpi(dpi)),
+            }
+        };
+        Ok(Phys { pixels_per_unit_x: per_meter(dpi_x)?, pixels_per_unit_y: per_meter(dpi_y)?, unit: PhysUnit::Meter })
+    }
+
+    pub fn dpi(&self) -> Option<(f64, f64)> {
+        match self.unit {
+            PhysUnit::Meter => Some((
+                self.pixels_per_unit_x as f64 * METERS_PER_INCH,
+                self.pixels_per_unit_y as f64 * METERS_PER_INCH,
+            )),
+            PhysUnit::Unknown => None,
+        }
+    }
+
+    pub fn to_chunk(self) -> Chunk {
+        let mut data = self.pixels_per_unit_x.to_be_bytes().to_vec();
+        data.extend_from_slice(&self.pixels_per_unit_y.to_be_bytes());
+        data.push(match self.unit {
+            PhysUnit::Unknown => 0,
+            PhysUnit::Meter => 1,
+        });
+        chunk("pHYs", data)
+    }
+}
+
+impl TryFrom<&Chunk> for Phys {
+    type Error = PhysicalError;
+
+    fn try_from(chunk: &Chunk) -> Result<Self, Self::Error> {
+        check_chunk(chunk, "pHYs", Some(9))?;
+        let data = chunk.data();
+        let unit = match data[8] {
+            0 => PhysUnit::Unknown,
+            1 => PhysUnit::Meter,
+            unit => return Err(PhysicalError::InvalidUnit(unit)),
+        };
+        Ok(Phys { pixels_per_unit_x: read_u32(data, 0), pixels_per_unit_y: read_u32(data, 4), unit })
+    }
+}
+
+// days since 1970-01-01 for a proleptic Gregorian date
+fn days_from_civil(year: i64, month: u8, day: u8) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let month = month as i64;
+    let day_of_year = (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + day as i64 - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
+
+fn civil_from_days(days: i64) -> (i64, u8, u8) {
+    let days = days + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days - era * 146097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * mp + 2) / 5 + 1) as u8;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u8;
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+fn days_in_month(year: u16, month: u8) -> u8 {
+    match month {
+        2 if year.is_multiple_of(4) && (!year.is_multiple_of(100) || year.is_multiple_of(400)) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+// last modification time, always UTC
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Time {
+    pub year: u16,
+    pub month: u8,
+    pub day: u8,
+    pub hour: u8,
+    pub minute: u8,
+    // 60 allows for leap seconds
+    pub second: u8,
+}
+
+impl Time {
+    pub fn validate(&self) -> Result<(), PhysicalError> {
+        let valid = (1..=12).contains(&self.month)
+            && self.day >= 1
+            && self.day <= days_in_month(self.year, self.month)
+            && self.hour < 24
+            && self.minute < 60
+            && self.second <= 60;
+        match valid {
+            true => Ok(()),
+            false => Err(PhysicalError::InvalidTime),
+        }
+    }
+
+    pub fn from_unix(seconds: i64) -> Result<Time, PhysicalError> {
+        let (year, month, day) = civil_from_days(seconds.div_euclid(86400));
+        let time_of_day = seconds.rem_euclid(86400);
+        let year = u16::try_from(year).map_err(|_| PhysicalError::InvalidTime)?;
+        Ok(Time {
+            year,
+            month,
+            day,
+            hour: (time_of_day / 3600) as u8,
+            minute: (time_of_day / 60 % 60) as u8,
+            second: (time_of_day % 60) as u8,
+        })
+    }
+
+    pub fn to_unix(self) -> i64 {
+        days_from_civil(self.year as i64, self.month, self.day) * 86400
+            + self.hour as i64 * 3600
+            + self.minute as i64 * 60
+            + self.second as i64
+    }
+
+    pub fn now() -> Time {
+        let seconds = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |since| since.as_secs() as i64);
+        Time::from_unix(seconds).expect("the clock is within the range of tIME")
+    }
+
+    pub fn to_chunk(self) -> Chunk {
+        let mut data = self.year.to_be_bytes().to_vec();
+        data.extend_from_slice(&[self.month, self.day, self.hour, self.minute, self.second]);
+        chunk("tIME", data)
+    }
+}
+
+impl Display for Time {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z", self.year, self.month, self.day, self.hour, self.minute, self.second)
+    }
+}
+
+impl TryFrom<&Chunk> for Time {
+    type Error = PhysicalError;
+
+    fn try_from(chunk: &Chunk) -> Result<Self, Self::Error> {
+        check_chunk(chunk, "tIME", Some(7))?;
+        let data = chunk.data();
+        let time = Time {
+            year: u16::from_be_bytes([data[0], data[1]]),
+            month: data[2],
+            day: data[3],
+            hour: data[4],
+            minute: data[5],
+            second: data[6],
+        };
+        time.validate()?;
+        Ok(time)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OffsUnit {
+    Pixel,
+    Micrometer,
+}
+
+// position of the image on a page, may be negative
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Offs {
+    pub x: i32,
+    pub y: i32,
+    pub unit: OffsUnit,
+}
+
+impl Offs {
+    pub fn to_chunk(self) -> Chunk {
+        let mut data = self.x.to_be_bytes().to_vec();
+        data.extend_from_slice(&self.y.to_be_bytes());
+        data.push(match self.unit {
+            OffsUnit::Pixel => 0,
+            OffsUnit::Micrometer => 1,
+        });
+        chunk("oFFs", data)
+    }
+}
+
+impl TryFrom<&Chunk> for Offs {
+    type Error = PhysicalError;
+
+    fn try_from(chunk: &Chunk) -> Result<Self, Self::Error> {
+        check_chunk(chunk, "oFFs", Some(9))?;
+        let data = chunk.data();
+        let unit = match data[8] {
+            0 => OffsUnit::Pixel,
+            1 => OffsUnit::Micrometer,
+            unit => return Err(PhysicalError::InvalidUnit(unit)),
+        };
+        Ok(Offs { x: read_u32(data, 0) as i32, y: read_u32(data, 4) as i32, unit })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScalUnit {
+    Meter,
+    Radian,
+}
+
+// physical size of a pixel, kept as the ASCII floating point strings the
+// chunk stores so values round trip exactly
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Scal {
+    pub unit: ScalUnit,
+    pub width: String,
+    pub height: String,
+}
+
+fn check_scale(value: &str) -> Result<f64, PhysicalError> {
+    let allowed = value.bytes().all(|byte| byte.is_ascii_digit() || b".eE+-".contains(&byte));
+    match value.parse::<f64>() {
+        Ok(number) if allowed && number > 0.0 && number.is_finite() => Ok(number),
+        _ => Err(PhysicalError::InvalidScale(value.to_string())),
+    }
+}
+
+impl Scal {
+    pub fn new(unit: ScalUnit, width: &str, height: &str) -> Result<Scal, PhysicalError> {
+        check_scale(width)?;
+        check_scale(height)?;
+        Ok(Scal { unit, width: width.to_string(), height: height.to_string() })
+    }
+
+    pub fn size(&self) -> (f64, f64) {
+        (check_scale(&self.width).unwrap_or(0.0), check_scale(&self.height).unwrap_or(0.0))
+    }
+
+    pub fn to_chunk(&self) -> Chunk {
+        let mut data = vec![match self.unit {
+            ScalUnit::Meter => 1,
+            ScalUnit::Radian => 2,
+        }];
+        data.extend_from_slice(self.width.as_bytes());
+        data.push(0);
+        data.extend_from_slice(self.height.as_bytes());
+        chunk("sCAL", data)
+    }
+}
+
+impl TryFrom<&Chunk> for Scal {
+    type Error = PhysicalError;
+
+    fn try_from(chunk: &Chunk) -> Result<Self, Self::Error> {
+        check_chunk(chunk, "sCAL", None)?;
+        let (&unit, rest) = chunk.data().split_first().ok_or(PhysicalError::InvalidLength("sCAL"))?;
+        let unit = match unit {
+            1 => ScalUnit::Meter,
+            2 => ScalUnit::Radian,
+            unit => return Err(PhysicalError::InvalidUnit(unit)),
+        };
+        let separator = rest.iter().position(|&byte| byte == 0).ok_or(PhysicalError::InvalidLength("sCAL"))?;
+        let text = |bytes: &[u8]| String::from_utf8_lossy(bytes).into_owned();
+        Scal::new(unit, &text(&rest[..separator]), &text(&rest[separator + 1..]))
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum PhysicalError {
+    WrongChunkType(&'static str),
+    InvalidLength(&'static str),
+    InvalidUnit(u8),
+    InvalidDpi(f64),
+    InvalidTime,
+    InvalidScale(String),
+    Png(String),
+}
+
+impl Display for PhysicalError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PhysicalError::WrongChunkType(expected) => write!(f, "expected a {} chunk", expected),
+            PhysicalError::InvalidLength(chunk_type) => write!(f, "{} chunk has an invalid length", chunk_type),
+            PhysicalError::InvalidUnit(unit) => write!(f, "unknown unit specifier {}", unit),
+            PhysicalError::InvalidDpi(dpi) => write!(f, "{} DPI can't be stored in pHYs", dpi),
+            PhysicalError::InvalidTime => write!(f, "not a valid UTC date and time"),
+            PhysicalError::InvalidScale(value) => write!(f, "'{}' is not a positive floating point number", value),
+            PhysicalError::Png(reason) => write!(f, "{}", reason),
+        }
+    }
+}
+
+impl std::error::Error for PhysicalError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_phys_dpi() {
+        let phys = Phys::from_dpi(300.0, 72.0).unwrap();
+        assert_eq!((phys.pixels_per_unit_x, phys.pixels_per_unit_y), (11811, 2835));
+        let (x, y) = phys.dpi().unwrap();
+        assert!((x - 300.0).abs() < 0.01 && (y - 72.0).abs() < 0.01);
+        assert_eq!(Phys::try_from(&phys.to_chunk()), Ok(phys));
+
+        let aspect = Phys { pixels_per_unit_x: 2, pixels_per_unit_y: 1, unit: PhysUnit::Unknown };
+        assert_eq!(aspect.dpi(), None);
+        assert_eq!(Phys::from_dpi(0.0, 72.0), Err(PhysicalError::InvalidDpi(0.0)));
+        let mut data = phys.to_chunk().data().to_vec();
+        data[8] = 2;
+        assert_eq!(Phys::try_from(&chunk("pHYs", data)), Err(PhysicalError::InvalidUnit(2)));
+    }
+
+    #[test]
+    fn test_time_unix_conversion() {
+        let time = Time::from_unix(0).unwrap();
+        assert_eq!(time.to_string(), "1970-01-01T00:00:00Z");
+        // 2024 is a leap year
+        let time = Time::from_unix(1_709_210_096).unwrap();
+        assert_eq!(time.to_string(), "2024-02-29T12:34:56Z");
+        assert_eq!(time.to_unix(), 1_709_210_096);
+        assert_eq!(Time::try_from(&time.to_chunk()), Ok(time));
+        for seconds in [951_782_400, 4_102_444_799, 1_000_000_000] {
+            assert_eq!(Time::from_unix(seconds).unwrap().to_unix(), seconds);
+        }
+    }
+
+    #[test]
+    fn test_invalid_time() {
+        let time = Time { year: 2023, month: 2, day: 29, hour: 0, minute: 0, second: 0 };
+        assert_eq!(Time::try_from(&time.to_chunk()), Err(PhysicalError::InvalidTime));
+        let time = Time { year: 2023, month: 12, day: 31, hour: 23, minute: 59, second: 60 };
+        assert!(time.validate().is_ok());
+        assert_eq!(Time::try_from(&chunk("tIME", vec![0; 6])), Err(PhysicalError::InvalidLength("tIME")));
+    }
+
+    #[test]
+    fn test_offs_and_scal() {
+        let offs = Offs { x: -1200, y: 350, unit: OffsUnit::Micrometer };
+        assert_eq!(Offs::try_from(&offs.to_chunk()), Ok(offs));
+
+        let scal = Scal::new(ScalUnit::Meter, "0.0254", "2.54e-2").unwrap();
+        assert_eq!(scal.to_chunk().data(), b"\x010.0254\x002.54e-2");
+        assert_eq!(Scal::try_from(&scal.to_chunk()), Ok(scal.clone()));
+        assert_eq!(scal.size(), (0.0254, 0.0254));
+        assert_eq!(Scal::new(ScalUnit::Radian, "-1", "1"), Err(PhysicalError::InvalidScale("-1".to_string())));
+        assert_eq!(Scal::new(ScalUnit::Radian, "inf", "1"), Err(PhysicalError::InvalidScale("inf".to_string())));
+    }
+
+    #[test]
+    fn test_set_chunk_replaces_in_place() {
+        let mut png = Png::from_chunks(vec![chunk("IHDR", vec![]), chunk("IDAT", vec![]), chunk("IEND", vec![])]).unwrap();
+        set_chunk(&mut png, Phys::from_dpi(72.0, 72.0).unwrap().to_chunk(), ChunkPlacement::AfterIhdr).unwrap();
+        set_chunk(&mut png, Time::from_unix(0).unwrap().to_chunk(), ChunkPlacement::BeforeIend).unwrap();
+        set_chunk(&mut png, Phys::from_dpi(300.0, 300.0).unwrap().to_chunk(), ChunkPlacement::AfterIhdr).unwrap();
+        let types: Vec<String> = png.chunks().iter().map(|c| c.chunk_type().to_string()).collect();
+        assert_eq!(types, vec!["IHDR", "pHYs", "IDAT", "tIME", "IEND"]);
+        assert_eq!(Phys::try_from(png.chunk_by_type("pHYs").unwrap()).unwrap().pixels_per_unit_x, 11811);
+    }
+}
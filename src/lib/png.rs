@@ -1,28 +1,194 @@
-use std::{fmt::Display, io::{BufReader, Read}};
+use std::{fmt::Display, io::{self, Read, Write}};
+
+use super::apng::{self, Animation, ApngError};
+use super::chunk::{Chunk, ChunkRef, ChunkRefs};
+use super::color::{self, ResolvedColorSpace};
+use super::decoder::{self, DecodeError};
+use super::encoder::{self, EncodeError, EncodeOptions};
+use super::image::Image;
+use super::ihdr::{Ihdr, IhdrError};
+use super::ordering::{validate_order, OrderingViolation};
+use super::reader::{ChunkReader, ReadError};
+use super::writer::ChunkWriter;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ChunkPlacement {
+    #[default]
+    BeforeIend,
+    AfterIhdr,
+    BeforeFirstIdat,
+    Index(usize),
+}
 
-use super::chunk::Chunk;
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EditMode {
+    Checked,
+    Unchecked,
+}
 
 pub struct Png{
     header: [u8; 8],
-    chunks: Vec<Chunk>
+    chunks: Vec<Chunk>,
+    edit_mode: EditMode,
 }
 
 impl Png {
     const STANDARD_HEADER: [u8; 8] = [137, 80, 78, 71, 13, 10, 26, 10];
 
-    pub fn from_chunks(chunks: Vec<Chunk>) -> Png{
-        Png { header: Self::STANDARD_HEADER, chunks }
+    pub fn from_chunks(chunks: Vec<Chunk>) -> Result<Png, PngError>{
+        let violations = validate_order(&chunks);
+        if !violations.is_empty() {
+            return Err(PngError::InvalidOrdering(violations));
+        }
+        Ok(Png { header: Self::STANDARD_HEADER, chunks, edit_mode: EditMode::Checked })
+    }
+
+    // reads chunk by chunk instead of buffering the whole file first
+    pub fn from_reader<R: Read>(reader: R) -> Result<Png, ReadError>{
+        let chunks = ChunkReader::new(reader)?.collect::<Result<Vec<Chunk>, ReadError>>()?;
+        Ok(Png { header: Self::STANDARD_HEADER, chunks, edit_mode: EditMode::Checked })
+    }
+
+    pub fn from_image(image: &Image, options: &EncodeOptions) -> Result<Png, EncodeError>{
+        encoder::encode(image, options)
+    }
+
+    pub fn from_chunks_unchecked(chunks: Vec<Chunk>) -> Png{
+        Png { header: Self::STANDARD_HEADER, chunks, edit_mode: EditMode::Unchecked }
+    }
+
+    pub fn edit_mode(&self) -> EditMode{
+        self.edit_mode
+    }
+
+    pub fn set_edit_mode(&mut self, edit_mode: EditMode){
+        self.edit_mode = edit_mode;
+    }
+
+    pub fn ordering_violations(&self) -> Vec<OrderingViolation>{
+        validate_order(&self.chunks)
     }
 
-    pub fn append_chunk(&mut self, chunk: Chunk){
-        self.chunks.push(chunk);
+    pub fn append_chunk(&mut self, chunk: Chunk) -> Result<(), PngError>{
+        let index = self.chunks.len();
+        self.insert_chunk(chunk, ChunkPlacement::Index(index)).map(|_| ())
+    }
+
+    pub fn insert_chunk(&mut self, chunk: Chunk, placement: ChunkPlacement) -> Result<usize, PngError>{
+        let index = self.placement_index(placement)?;
+        let before = self.checked_violations();
+        self.chunks.insert(index, chunk);
+        if let Err(err) = self.check_edit(&before) {
+            self.chunks.remove(index);
+            return Err(err);
+        }
+        Ok(index)
+    }
+
+    // swaps `chunk` in for the one at `index`, so the old chunk is only gone
+    // once the new one is in; on an ordering error the old one stays
+    pub fn replace_chunk(&mut self, index: usize, chunk: Chunk) -> Result<Chunk, PngError>{
+        if index >= self.chunks.len() {
+            return Err(PngError::IndexOutOfBounds);
+        }
+        let before = self.checked_violations();
+        let old = std::mem::replace(&mut self.chunks[index], chunk);
+        if let Err(err) = self.check_edit(&before) {
+            self.chunks[index] = old;
+            return Err(err);
+        }
+        Ok(old)
+    }
+
+    fn checked_violations(&self) -> Vec<OrderingViolation>{
+        match self.edit_mode {
+            EditMode::Checked => validate_order(&self.chunks),
+            EditMode::Unchecked => Vec::new(),
+        }
+    }
+
+    // an edit is rejected if it brings in any violation that wasn't there
+    // before, so files that were already slightly off (e.g. data after IEND)
+    // can still be edited; violations are matched by chunk type and kind,
+    // since the edit shifts their indices
+    fn check_edit(&self, before: &[OrderingViolation]) -> Result<(), PngError>{
+        if self.edit_mode == EditMode::Unchecked {
+            return Ok(());
+        }
+        let mut existing: Vec<&OrderingViolation> = before.iter().collect();
+        let added: Vec<OrderingViolation> = validate_order(&self.chunks)
+            .into_iter()
+            .filter(|violation| {
+                let matching = existing.iter().position(|old| old.chunk_type == violation.chunk_type && old.kind == violation.kind);
+                match matching {
+                    Some(pos) => {
+                        existing.swap_remove(pos);
+                        false
+                    },
+                    None => true,
+                }
+            })
+            .collect();
+        match added.is_empty() {
+            true => Ok(()),
+            false => Err(PngError::InvalidOrdering(added)),
+        }
+    }
+
+    fn placement_index(&self, placement: ChunkPlacement) -> Result<usize, PngError>{
+        match placement {
+            // files without an IEND get the chunk at the end, same as append_chunk
+            ChunkPlacement::BeforeIend => Ok(self.position_of("IEND").unwrap_or(self.chunks.len())),
+            ChunkPlacement::AfterIhdr => self.position_of("IHDR")
+                .map(|pos| pos + 1)
+                .ok_or(PngError::MissingIhdr),
+            ChunkPlacement::BeforeFirstIdat => self.position_of("IDAT").ok_or(PngError::MissingIdat),
+            ChunkPlacement::Index(index) if index <= self.chunks.len() => Ok(index),
+            ChunkPlacement::Index(_) => Err(PngError::IndexOutOfBounds),
+        }
+    }
+
+    fn position_of(&self, chunk_type: &str) -> Option<usize>{
+        self.chunks.iter().position(|chunk| chunk.chunk_type().bytes() == chunk_type.as_bytes())
+    }
+
+    fn positions_of(&self, chunk_type: &str) -> Vec<usize>{
+        (0..self.chunks.len())
+            .filter(|&pos| self.chunks[pos].chunk_type().bytes() == chunk_type.as_bytes())
+            .collect()
     }
 
     pub fn remove_chunk(&mut self, chunk_type: &str) -> Result<Chunk, String>{
-        match self.chunks.iter().position(|chunk| chunk.chunk_type().bytes() == chunk_type.as_bytes()) {
-            Some(pos) => Ok(self.chunks.remove(pos)),
-            None => Err("couldn't find the chunk entry".to_string())
+        self.remove_nth_chunk(chunk_type, 0)
+    }
+
+    // `n` counts only chunks of `chunk_type`, starting at 0
+    pub fn remove_nth_chunk(&mut self, chunk_type: &str, n: usize) -> Result<Chunk, String>{
+        let pos = *self.positions_of(chunk_type).get(n).ok_or("couldn't find the chunk entry".to_string())?;
+        Ok(self.remove_positions(&[pos])?.remove(0))
+    }
+
+    pub fn remove_chunks_by_type(&mut self, chunk_type: &str) -> Result<Vec<Chunk>, String>{
+        let positions = self.positions_of(chunk_type);
+        if positions.is_empty() {
+            return Err("couldn't find the chunk entry".to_string());
         }
+        self.remove_positions(&positions)
+    }
+
+    // positions must be ascending; on an ordering error everything is put back
+    fn remove_positions(&mut self, positions: &[usize]) -> Result<Vec<Chunk>, String>{
+        let before = self.checked_violations();
+        let mut removed: Vec<Chunk> = positions.iter().rev().map(|&pos| self.chunks.remove(pos)).collect();
+        removed.reverse();
+        if let Err(PngError::InvalidOrdering(violations)) = self.check_edit(&before) {
+            for (&pos, chunk) in positions.iter().zip(removed) {
+                self.chunks.insert(pos, chunk);
+            }
+            let reasons: Vec<String> = violations.iter().map(|v| v.to_string()).collect();
+            return Err(format!("removing the chunk breaks chunk ordering: {}", reasons.join("; ")));
+        }
+        Ok(removed)
     }
 
     pub fn header(&self) -> &[u8; 8]{
@@ -37,6 +203,55 @@ impl Png {
         self.chunks.iter().find(|chunk| chunk.chunk_type().bytes() == chunk_type.as_bytes())
     }
 
+    pub fn chunks_by_type(&self, chunk_type: &str) -> Vec<&Chunk>{
+        self.chunks.iter().filter(|chunk| chunk.chunk_type().bytes() == chunk_type.as_bytes()).collect()
+    }
+
+    pub fn ihdr(&self) -> Result<Ihdr, IhdrError>{
+        let chunk = self.chunk_by_type("IHDR").ok_or(IhdrError::Missing)?;
+        Ihdr::try_from(chunk)
+    }
+
+    pub fn color_space(&self) -> ResolvedColorSpace{
+        color::color_space(self)
+    }
+
+    pub fn animation(&self) -> Result<Animation, ApngError>{
+        apng::Animation::from_png(self)
+    }
+
+    pub fn decode_image(&self) -> Result<Image, DecodeError>{
+        decoder::decode(self)
+    }
+
+    pub fn decode_progressive(&self) -> Result<Vec<Image>, DecodeError>{
+        decoder::decode_progressive(self)
+    }
+
+    // re-encodes the pixels of `image` in place of the current IHDR and IDAT
+    // chunks, keeping every other chunk where it is; there's no Adam7
+    // encoder, so the result is never interlaced
+    pub fn replace_image_data(&mut self, image: &Image, options: &EncodeOptions) -> Result<(), EncodeError>{
+        let ihdr = self.position_of("IHDR").ok_or(EncodeError::Png(PngError::MissingIhdr))?;
+        let first_idat = self.position_of("IDAT").ok_or(EncodeError::Png(PngError::MissingIdat))?;
+        let mut encoded: Vec<Chunk> = encoder::encode(image, options)?.chunks.into_iter()
+            .filter(|chunk| matches!(&chunk.chunk_type().bytes(), b"IHDR" | b"IDAT"))
+            .collect();
+        self.chunks[ihdr] = encoded.remove(0);
+        self.chunks.retain(|chunk| &chunk.chunk_type().bytes() != b"IDAT");
+        self.chunks.splice(first_idat..first_idat, encoded);
+        Ok(())
+    }
+
+    // writes chunk by chunk, without building the whole file in memory
+    pub fn write_to<W: Write>(&self, writer: W) -> io::Result<W>{
+        let mut chunk_writer = ChunkWriter::new(writer)?;
+        for chunk in &self.chunks {
+            chunk_writer.write_chunk(chunk)?;
+        }
+        chunk_writer.finish()
+    }
+
     pub fn as_bytes(&self) -> Vec<u8>{
         let mut byte_vec = self.header.to_vec();
         for chunk in self.chunks() {
@@ -53,34 +268,13 @@ impl TryFrom<&[u8]> for Png {
         match byte_stream.len() < 67 { // checking for minimum possible PNG file
             true => Err(PngError::InvalidPngStreamLength),
             false => {
-                //check for signature
-                let mut buf_reader = BufReader::new(byte_stream);
-                let mut png_signature: [u8; 8] = [0; 8];
-                buf_reader.read_exact(&mut png_signature).expect("buffer read error");
-                match png_signature {
-                    Self::STANDARD_HEADER => {
-                        let mut incoming_chunk_length_arr : [u8; 4] = [0; 4];
-                        let mut chunk_vec = Vec::new();
-                        while let Ok(()) = buf_reader.read_exact(&mut incoming_chunk_length_arr) {
-                            let incoming_chunk_length = u32::from_be_bytes(
-                                        [incoming_chunk_length_arr[0], incoming_chunk_length_arr[1], 
-                                        incoming_chunk_length_arr[2], incoming_chunk_length_arr[3]]
-                                    );
-                            let mut incoming_chunk_vec = incoming_chunk_length_arr.to_vec();
-                            incoming_chunk_vec.resize((incoming_chunk_length + 12) as usize, 0);
-                            buf_reader.read_exact(&mut incoming_chunk_vec[4..])
-                                                .expect("buffer read error while reading incoming chunk data");
-                            match Chunk::try_from(&incoming_chunk_vec) {
-                                Ok(incoming_chunk) => {
-                                    chunk_vec.push(incoming_chunk);
-                                },
-                                _ => {return Err(PngError::InvalidPngChunk);}
-                            }
-                        }
-                        Ok(Self { header: Self::STANDARD_HEADER, chunks: chunk_vec })
-                    },
-                    _ => Err(PngError::InvalidPngSignature),
-                }
+                let chunk_refs = ChunkRefs::new(byte_stream).map_err(|_| PngError::InvalidPngSignature)?;
+                // each chunk is borrowed from the stream and copied exactly once
+                let chunks = chunk_refs
+                    .map(|chunk| chunk.map(ChunkRef::to_chunk))
+                    .collect::<Result<Vec<Chunk>, _>>()
+                    .map_err(|_| PngError::InvalidPngChunk)?;
+                Ok(Self { header: Self::STANDARD_HEADER, chunks, edit_mode: EditMode::Checked })
             }
         }
     }
@@ -108,6 +302,10 @@ pub enum PngError {
     InvalidPngStreamLength,
     InvalidPngSignature,
     InvalidPngChunk,
+    MissingIhdr,
+    MissingIdat,
+    IndexOutOfBounds,
+    InvalidOrdering(Vec<OrderingViolation>),
 }
 
 #[allow(unused_variables)]
@@ -129,7 +327,15 @@ mod tests {
 
     fn testing_png() -> Png {
         let chunks = testing_chunks();
-        Png::from_chunks(chunks)
+        Png::from_chunks_unchecked(chunks)
+    }
+
+    fn ordered_chunks() -> Vec<Chunk> {
+        vec![
+            chunk_from_strings("IHDR", "header").unwrap(),
+            chunk_from_strings("IDAT", "pixels").unwrap(),
+            chunk_from_strings("IEND", "").unwrap(),
+        ]
     }
 
     fn chunk_from_strings(chunk_type: &str, data: &str) -> Result<Chunk, ChunkTypeError> {
@@ -141,12 +347,89 @@ mod tests {
         Ok(Chunk::new(chunk_type, data))
     }
 
+    #[test]
+    fn test_write_to_matches_as_bytes() {
+        let png = testing_png();
+        assert_eq!(png.write_to(Vec::new()).unwrap(), png.as_bytes());
+    }
+
     #[test]
     fn test_from_chunks() {
-        let chunks = testing_chunks();
-        let png = Png::from_chunks(chunks);
+        let chunks = ordered_chunks();
+        let png = Png::from_chunks(chunks).unwrap();
+
+        assert_eq!(png.chunks().len(), 3);
+    }
+
+    #[test]
+    fn test_from_chunks_rejects_bad_ordering() {
+        let result = Png::from_chunks(testing_chunks());
+        match result {
+            Err(PngError::InvalidOrdering(violations)) => assert_eq!(violations.len(), 3),
+            _ => panic!("expected an ordering error"),
+        }
+    }
+
+    #[test]
+    fn test_from_chunks_unchecked() {
+        let png = Png::from_chunks_unchecked(testing_chunks());
+        assert_eq!(png.chunks().len(), 3);
+        assert_eq!(png.edit_mode(), EditMode::Unchecked);
+        assert_eq!(png.ordering_violations().len(), 3);
+    }
 
+    #[test]
+    fn test_checked_edits_reject_bad_ordering() {
+        let mut png = Png::from_chunks(ordered_chunks()).unwrap();
+        assert!(matches!(
+            png.append_chunk(chunk_from_strings("TeSt", "Message").unwrap()),
+            Err(PngError::InvalidOrdering(_))
+        ));
+        assert!(matches!(
+            png.insert_chunk(chunk_from_strings("IHDR", "again").unwrap(), ChunkPlacement::AfterIhdr),
+            Err(PngError::InvalidOrdering(_))
+        ));
+        assert!(png.remove_chunk("IDAT").is_err());
         assert_eq!(png.chunks().len(), 3);
+
+        png.insert_chunk(chunk_from_strings("TeSt", "Message").unwrap(), ChunkPlacement::BeforeIend).unwrap();
+        png.remove_chunk("TeSt").unwrap();
+    }
+
+    #[test]
+    fn test_unchecked_edits_allow_bad_ordering() {
+        let mut png = Png::from_chunks(ordered_chunks()).unwrap();
+        png.set_edit_mode(EditMode::Unchecked);
+        png.append_chunk(chunk_from_strings("TeSt", "Message").unwrap()).unwrap();
+        png.remove_chunk("IDAT").unwrap();
+        assert_eq!(png.ordering_violations().len(), 2);
+    }
+
+    #[test]
+    fn test_checked_edits_on_already_invalid_file() {
+        let mut png = Png::try_from(&PNG_FILE[..]).unwrap();
+        let violations = png.ordering_violations().len();
+        png.insert_chunk(chunk_from_strings("TeSt", "Message").unwrap(), ChunkPlacement::BeforeIend).unwrap();
+        assert_eq!(png.ordering_violations().len(), violations);
+    }
+
+    #[test]
+    fn test_checked_edits_reject_swapped_violations() {
+        let mut png = Png::from_chunks_unchecked(vec![
+            chunk_from_strings("IHDR", "header").unwrap(),
+            chunk_from_strings("IDAT", "pixels").unwrap(),
+        ]);
+        png.set_edit_mode(EditMode::Checked);
+        assert_eq!(png.ordering_violations().len(), 1);
+        // fixes the missing IEND but puts it before IDAT, one violation either way
+        let result = png.insert_chunk(chunk_from_strings("IEND", "").unwrap(), ChunkPlacement::Index(1));
+        match result {
+            Err(PngError::InvalidOrdering(added)) => assert_eq!(added.len(), 1),
+            _ => panic!("expected an ordering error"),
+        }
+        assert_eq!(png.chunks().len(), 2);
+        png.insert_chunk(chunk_from_strings("IEND", "").unwrap(), ChunkPlacement::Index(2)).unwrap();
+        assert!(png.ordering_violations().is_empty());
     }
 
     #[test]
@@ -227,7 +510,7 @@ mod tests {
     #[test]
     fn test_append_chunk() {
         let mut png = testing_png();
-        png.append_chunk(chunk_from_strings("TeSt", "Message").unwrap());
+        png.append_chunk(chunk_from_strings("TeSt", "Message").unwrap()).unwrap();
         let chunk = png.chunk_by_type("TeSt").unwrap();
         assert_eq!(&chunk.chunk_type().to_string(), "TeSt");
         assert_eq!(&chunk.data_as_string().unwrap(), "Message");
@@ -236,12 +519,157 @@ mod tests {
     #[test]
     fn test_remove_chunk() {
         let mut png = testing_png();
-        png.append_chunk(chunk_from_strings("TeSt", "Message").unwrap());
+        png.append_chunk(chunk_from_strings("TeSt", "Message").unwrap()).unwrap();
         png.remove_chunk("TeSt").unwrap();
         let chunk = png.chunk_by_type("TeSt");
         assert!(chunk.is_none());
     }
 
+    fn repeated_png() -> Png {
+        let mut png = testing_png();
+        for message in ["one", "two", "three"] {
+            png.append_chunk(chunk_from_strings("TeSt", message).unwrap()).unwrap();
+        }
+        png
+    }
+
+    #[test]
+    fn test_chunks_by_type() {
+        let png = repeated_png();
+        let messages: Vec<String> = png.chunks_by_type("TeSt").iter().map(|c| c.data_as_string().unwrap()).collect();
+        assert_eq!(messages, vec!["one", "two", "three"]);
+        assert!(png.chunks_by_type("NoNe").is_empty());
+    }
+
+    #[test]
+    fn test_remove_nth_chunk() {
+        let mut png = repeated_png();
+        assert_eq!(png.remove_nth_chunk("TeSt", 1).unwrap().data_as_string().unwrap(), "two");
+        assert!(png.remove_nth_chunk("TeSt", 2).is_err());
+        let messages: Vec<String> = png.chunks_by_type("TeSt").iter().map(|c| c.data_as_string().unwrap()).collect();
+        assert_eq!(messages, vec!["one", "three"]);
+    }
+
+    #[test]
+    fn test_remove_chunks_by_type() {
+        let mut png = repeated_png();
+        assert_eq!(png.remove_chunks_by_type("TeSt").unwrap().len(), 3);
+        assert!(png.chunk_by_type("TeSt").is_none());
+        assert!(png.remove_chunks_by_type("TeSt").is_err());
+        assert_eq!(chunk_types(&png), vec!["FrSt", "miDl", "LASt"]);
+    }
+
+    #[test]
+    fn test_remove_all_restores_on_ordering_error() {
+        let mut png = Png::from_chunks(ordered_chunks()).unwrap();
+        let before = chunk_types(&png);
+        assert!(png.remove_chunks_by_type("IDAT").is_err());
+        assert_eq!(chunk_types(&png), before);
+    }
+
+    fn chunk_types(png: &Png) -> Vec<String> {
+        png.chunks().iter().map(|chunk| chunk.chunk_type().to_string()).collect()
+    }
+
+    #[test]
+    fn test_insert_chunk_before_iend() {
+        let mut png = Png::try_from(&PNG_FILE[..]).unwrap();
+        let index = png.insert_chunk(chunk_from_strings("TeSt", "Message").unwrap(), ChunkPlacement::default()).unwrap();
+        let types = chunk_types(&png);
+        assert_eq!(index, types.len() - 2);
+        assert_eq!(types[types.len() - 2], "TeSt");
+        assert_eq!(types[types.len() - 1], "IEND");
+    }
+
+    #[test]
+    fn test_insert_chunk_after_ihdr() {
+        let mut png = Png::try_from(&PNG_FILE[..]).unwrap();
+        let index = png.insert_chunk(chunk_from_strings("TeSt", "Message").unwrap(), ChunkPlacement::AfterIhdr).unwrap();
+        assert_eq!(index, 1);
+        assert_eq!(chunk_types(&png)[..2], ["IHDR", "TeSt"]);
+    }
+
+    #[test]
+    fn test_insert_chunk_before_first_idat() {
+        let mut png = Png::try_from(&PNG_FILE[..]).unwrap();
+        let index = png.insert_chunk(chunk_from_strings("TeSt", "Message").unwrap(), ChunkPlacement::BeforeFirstIdat).unwrap();
+        let types = chunk_types(&png);
+        assert_eq!(types[index], "TeSt");
+        assert_eq!(types[index + 1], "IDAT");
+        assert!(!types[..index].contains(&"IDAT".to_string()));
+    }
+
+    #[test]
+    fn test_insert_chunk_at_index() {
+        let mut png = testing_png();
+        png.insert_chunk(chunk_from_strings("TeSt", "Message").unwrap(), ChunkPlacement::Index(1)).unwrap();
+        assert_eq!(chunk_types(&png), ["FrSt", "TeSt", "miDl", "LASt"]);
+
+        let result = png.insert_chunk(chunk_from_strings("TeSt", "Message").unwrap(), ChunkPlacement::Index(9));
+        assert!(matches!(result, Err(PngError::IndexOutOfBounds)));
+    }
+
+    #[test]
+    fn test_insert_chunk_without_anchor() {
+        let mut png = testing_png();
+        png.insert_chunk(chunk_from_strings("TeSt", "Message").unwrap(), ChunkPlacement::BeforeIend).unwrap();
+        assert_eq!(chunk_types(&png).last().unwrap(), "TeSt");
+
+        let result = png.insert_chunk(chunk_from_strings("TeSt", "Message").unwrap(), ChunkPlacement::AfterIhdr);
+        assert!(matches!(result, Err(PngError::MissingIhdr)));
+        let result = png.insert_chunk(chunk_from_strings("TeSt", "Message").unwrap(), ChunkPlacement::BeforeFirstIdat);
+        assert!(matches!(result, Err(PngError::MissingIdat)));
+    }
+
+    #[test]
+    fn test_replace_chunk() {
+        let mut png = Png::from_chunks(ordered_chunks()).unwrap();
+        let old = png.replace_chunk(1, chunk_from_strings("IDAT", "new pixels").unwrap()).unwrap();
+        assert_eq!(old.data_as_string().unwrap(), "pixels");
+        assert_eq!(png.chunks()[1].data_as_string().unwrap(), "new pixels");
+
+        let result = png.replace_chunk(1, chunk_from_strings("TeSt", "Message").unwrap());
+        assert!(matches!(result, Err(PngError::InvalidOrdering(_))));
+        assert_eq!(png.chunks()[1].data_as_string().unwrap(), "new pixels");
+        assert!(matches!(png.replace_chunk(3, chunk_from_strings("TeSt", "").unwrap()), Err(PngError::IndexOutOfBounds)));
+    }
+
+    #[test]
+    fn test_ihdr() {
+        let png = Png::try_from(&PNG_FILE[..]).unwrap();
+        let ihdr = png.ihdr().unwrap();
+        assert_eq!((ihdr.width, ihdr.height), (50, 50));
+        assert_eq!(ihdr.bit_depth, 8);
+        assert_eq!(ihdr.color_type, crate::ihdr::ColorType::Rgba);
+
+        assert_eq!(testing_png().ihdr(), Err(IhdrError::Missing));
+    }
+
+    #[test]
+    fn test_decode_image() {
+        let png = Png::try_from(&PNG_FILE[..]).unwrap();
+        let image = png.decode_image().unwrap();
+        assert_eq!((image.width, image.height), (50, 50));
+        assert_eq!(image.pixels.len(), 50 * 50 * 4);
+        assert_eq!(image.to_rgba8().unwrap(), image.pixels);
+    }
+
+    #[test]
+    fn test_replace_image_data() {
+        let mut png = Png::try_from(&PNG_FILE[..]).unwrap();
+        let types_before = chunk_types(&png);
+        let mut image = png.decode_image().unwrap();
+        image.pixels.iter_mut().for_each(|byte| *byte = !*byte);
+        let options = EncodeOptions { idat_size: 1 << 20, ..EncodeOptions::default() };
+        png.replace_image_data(&image, &options).unwrap();
+
+        let types_after = chunk_types(&png);
+        let non_idat = |types: &[String]| types.iter().filter(|t| *t != "IDAT").cloned().collect::<Vec<_>>();
+        assert_eq!(non_idat(&types_before), non_idat(&types_after));
+        assert_eq!(types_after.iter().filter(|t| *t == "IDAT").count(), 1);
+        assert_eq!(png.decode_image().unwrap(), image);
+    }
+
     #[test]
     fn test_png_from_image_file() {
         let png = Png::try_from(&PNG_FILE[..]);
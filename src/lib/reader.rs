@@ -0,0 +1,276 @@
+use std::fmt::Display;
+use std::io::{self, Read};
+
+use super::chunk::Chunk;
+use super::chunk_type::ChunkType;
+
+pub const SIGNATURE: [u8; 8] = [137, 80, 78, 71, 13, 10, 26, 10];
+const MAX_CHUNK_LENGTH: u32 = (1 << 31) - 1;
+
+// where a chunk sits in the file, without its data
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChunkHeader {
+    // offset of the length field from the start of the file
+    pub offset: u64,
+    pub length: u32,
+    pub chunk_type: ChunkType,
+}
+
+// reads one chunk at a time, so only the current chunk is ever in memory;
+// iteration ends at a clean end of input rather than at IEND, since older
+// pngme versions hid chunks after it
+pub struct ChunkReader<R: Read> {
+    reader: R,
+    offset: u64,
+    done: bool,
+}
+
+impl<R: Read> ChunkReader<R> {
+    pub fn new(mut reader: R) -> Result<ChunkReader<R>, ReadError> {
+        let mut signature = [0; 8];
+        reader.read_exact(&mut signature).map_err(ReadError::from_io)?;
+        if signature != SIGNATURE {
+            return Err(ReadError::InvalidSignature);
+        }
+        Ok(ChunkReader { reader, offset: SIGNATURE.len() as u64, done: false })
+    }
+
+    // header only mode: chunk data and CRCs are skipped, not read or checked
+    pub fn headers(self) -> ChunkHeaders<R> {
+        ChunkHeaders(self)
+    }
+
+    pub fn next_header(&mut self) -> Option<Result<ChunkHeader, ReadError>> {
+        let header = self.read_header()?;
+        Some(header.and_then(|header| {
+            let skipped = io::copy(&mut (&mut self.reader).take(header.length as u64 + 4), &mut io::sink());
+            self.check_complete(skipped.map_err(ReadError::from_io)?, header.length as u64 + 4)?;
+            Ok(header)
+        }))
+    }
+
+    pub fn next_chunk(&mut self) -> Option<Result<Chunk, ReadError>> {
+        let header = self.read_header()?;
+        Some(header.and_then(|header| {
+            // grows with the data actually read, so a corrupt length can't
+            // allocate gigabytes up front
+            let mut data = Vec::new();
+            let read = (&mut self.reader).take(header.length as u64).read_to_end(&mut data);
+            self.check_complete(read.map_err(ReadError::from_io)? as u64, header.length as u64)?;
+            let mut crc = [0; 4];
+            self.reader.read_exact(&mut crc).map_err(|err| self.fail(ReadError::from_io(err)))?;
+            let chunk = Chunk::new(header.chunk_type, data);
+            if chunk.crc() != u32::from_be_bytes(crc) {
+                return Err(self.fail(ReadError::CrcMismatch { offset: header.offset }));
+            }
+            Ok(chunk)
+        }))
+    }
+
+    fn read_header(&mut self) -> Option<Result<ChunkHeader, ReadError>> {
+        if self.done {
+            return None;
+        }
+        let mut length = [0; 4];
+        match read_or_eof(&mut self.reader, &mut length) {
+            Ok(false) => {
+                self.done = true;
+                return None;
+            }
+            Ok(true) => (),
+            Err(err) => return Some(Err(self.fail(err))),
+        }
+        let mut chunk_type = [0; 4];
+        if let Err(err) = self.reader.read_exact(&mut chunk_type) {
+            return Some(Err(self.fail(ReadError::from_io(err))));
+        }
+        let offset = self.offset;
+        let length = u32::from_be_bytes(length);
+        if length > MAX_CHUNK_LENGTH {
+            return Some(Err(self.fail(ReadError::InvalidLength { offset, length })));
+        }
+        // ChunkType takes any four bytes, the PNG spec only letters
+        if !chunk_type.iter().all(u8::is_ascii_alphabetic) {
+            return Some(Err(self.fail(ReadError::InvalidChunkType { offset, bytes: chunk_type })));
+        }
+        let chunk_type = ChunkType::try_from(chunk_type).unwrap();
+        self.offset += length as u64 + 12;
+        Some(Ok(ChunkHeader { offset, length, chunk_type }))
+    }
+
+    fn check_complete(&mut self, read: u64, expected: u64) -> Result<(), ReadError> {
+        match read == expected {
+            true => Ok(()),
+            false => Err(self.fail(ReadError::Truncated)),
+        }
+    }
+
+    // nothing after a malformed chunk can be trusted
+    fn fail(&mut self, err: ReadError) -> ReadError {
+        self.done = true;
+        err
+    }
+}
+
+impl<R: Read> Iterator for ChunkReader<R> {
+    type Item = Result<Chunk, ReadError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_chunk()
+    }
+}
+
+pub struct ChunkHeaders<R: Read>(ChunkReader<R>);
+
+impl<R: Read> Iterator for ChunkHeaders<R> {
+    type Item = Result<ChunkHeader, ReadError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next_header()
+    }
+}
+
+// false at a clean end of input, before the first byte
+fn read_or_eof(reader: &mut impl Read, buf: &mut [u8]) -> Result<bool, ReadError> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match reader.read(&mut buf[filled..]) {
+            Ok(0) if filled == 0 => return Ok(false),
+            Ok(0) => return Err(ReadError::Truncated),
+            Ok(read) => filled += read,
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => (),
+            Err(err) => return Err(ReadError::from_io(err)),
+        }
+    }
+    Ok(true)
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum ReadError {
+    Io(String),
+    InvalidSignature,
+    Truncated,
+    InvalidLength { offset: u64, length: u32 },
+    InvalidChunkType { offset: u64, bytes: [u8; 4] },
+    CrcMismatch { offset: u64 },
+}
+
+impl ReadError {
+    fn from_io(err: io::Error) -> ReadError {
+        match err.kind() {
+            io::ErrorKind::UnexpectedEof => ReadError::Truncated,
+            _ => ReadError::Io(err.to_string()),
+        }
+    }
+}
+
+impl Display for ReadError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ReadError::Io(reason) => write!(f, "read failed: {}", reason),
+            ReadError::InvalidSignature => write!(f, "not a PNG file, the signature doesn't match"),
+            ReadError::Truncated => write!(f, "the file ends in the middle of a chunk"),
+            ReadError::InvalidLength { offset, length } => {
+                write!(f, "chunk at offset {} claims an invalid length of {} bytes", offset, length)
+            }
+            ReadError::InvalidChunkType { offset, bytes } => {
+                write!(f, "chunk at offset {} has an invalid type {:?}", offset, bytes)
+            }
+            ReadError::CrcMismatch { offset } => write!(f, "chunk at offset {} fails its CRC check", offset),
+        }
+    }
+}
+
+impl std::error::Error for ReadError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn chunk(chunk_type: &str, data: &[u8]) -> Chunk {
+        Chunk::new(ChunkType::from_str(chunk_type).unwrap(), data.to_vec())
+    }
+
+    fn stream(chunks: &[Chunk]) -> Vec<u8> {
+        let mut bytes = SIGNATURE.to_vec();
+        for chunk in chunks {
+            bytes.extend(chunk.as_bytes());
+        }
+        bytes
+    }
+
+    fn sample() -> Vec<u8> {
+        stream(&[chunk("IHDR", &[0; 13]), chunk("IDAT", b"pixels"), chunk("IEND", b"")])
+    }
+
+    #[test]
+    fn test_reads_chunks_lazily() {
+        let bytes = sample();
+        let chunks: Vec<Chunk> = ChunkReader::new(&bytes[..]).unwrap().collect::<Result<_, _>>().unwrap();
+        let types: Vec<String> = chunks.iter().map(|chunk| chunk.chunk_type().to_string()).collect();
+        assert_eq!(types, vec!["IHDR", "IDAT", "IEND"]);
+        assert_eq!(chunks[1].data(), b"pixels");
+    }
+
+    #[test]
+    fn test_headers_skip_data() {
+        let mut bytes = sample();
+        // header only mode doesn't look at the data, so a bad CRC goes unnoticed
+        bytes[8 + 25 + 12] ^= 0xff;
+        let headers: Vec<ChunkHeader> = ChunkReader::new(&bytes[..]).unwrap().headers().collect::<Result<_, _>>().unwrap();
+        let layout: Vec<(u64, u32)> = headers.iter().map(|header| (header.offset, header.length)).collect();
+        assert_eq!(layout, vec![(8, 13), (33, 6), (51, 0)]);
+
+        let mut reader = ChunkReader::new(&bytes[..]).unwrap();
+        assert!(reader.next().unwrap().is_ok());
+        assert_eq!(reader.next().unwrap().err(), Some(ReadError::CrcMismatch { offset: 33 }));
+        assert!(reader.next().is_none());
+    }
+
+    #[test]
+    fn test_reads_past_iend() {
+        let mut bytes = sample();
+        bytes.extend(chunk("ruSt", b"hidden").as_bytes());
+        let last = ChunkReader::new(&bytes[..]).unwrap().last().unwrap().unwrap();
+        assert_eq!(last.data(), b"hidden");
+    }
+
+    #[test]
+    fn test_malformed_streams() {
+        assert_eq!(ChunkReader::new(&b"GIF89a.."[..]).err(), Some(ReadError::InvalidSignature));
+        assert_eq!(ChunkReader::new(&SIGNATURE[..4]).err(), Some(ReadError::Truncated));
+
+        let bytes = sample();
+        let mut reader = ChunkReader::new(&bytes[..40]).unwrap();
+        assert!(reader.next().unwrap().is_ok());
+        assert_eq!(reader.next().unwrap().err(), Some(ReadError::Truncated));
+        let mut headers = ChunkReader::new(&bytes[..40]).unwrap().headers();
+        assert!(headers.next().unwrap().is_ok());
+        assert_eq!(headers.next().unwrap().err(), Some(ReadError::Truncated));
+
+        let mut bytes = SIGNATURE.to_vec();
+        bytes.extend_from_slice(&[0xff, 0xff, 0xff, 0xff]);
+        bytes.extend_from_slice(b"IDAT");
+        assert_eq!(
+            ChunkReader::new(&bytes[..]).unwrap().next().unwrap().err(),
+            Some(ReadError::InvalidLength { offset: 8, length: u32::MAX })
+        );
+
+        let mut bytes = sample();
+        bytes[8 + 25 + 6] = b'4';
+        let mut reader = ChunkReader::new(&bytes[..]).unwrap();
+        assert!(reader.next().unwrap().is_ok());
+        assert_eq!(reader.next().unwrap().err(), Some(ReadError::InvalidChunkType { offset: 33, bytes: *b"ID4T" }));
+        assert!(reader.next().is_none());
+        let mut headers = ChunkReader::new(&bytes[..]).unwrap().headers();
+        assert!(headers.next().unwrap().is_ok());
+        assert_eq!(headers.next().unwrap().err(), Some(ReadError::InvalidChunkType { offset: 33, bytes: *b"ID4T" }));
+    }
+
+    #[test]
+    fn test_png_from_reader() {
+        let png = crate::png::Png::from_reader(&sample()[..]).unwrap();
+        assert_eq!(png.chunks().len(), 3);
+    }
+}
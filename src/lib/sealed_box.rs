@@ -0,0 +1,197 @@
+use std::fmt::Display;
+
+use chacha20poly1305::aead::rand_core::RngCore;
+use chacha20poly1305::aead::{Aead, KeyInit, OsRng, Payload};
+use chacha20poly1305::{XChaCha20Poly1305, XNonce};
+use hkdf::Hkdf;
+use sha2::Sha256;
+use x25519_dalek::{PublicKey, StaticSecret};
+
+pub const MAGIC: [u8; 4] = *b"PNGk";
+const VERSION: u8 = 1;
+const KEY_LEN: usize = 32;
+const NONCE_LEN: usize = 24;
+const TAG_LEN: usize = 16;
+// recipient public key, nonce and the wrapped content key
+const SLOT_LEN: usize = KEY_LEN + NONCE_LEN + KEY_LEN + TAG_LEN;
+const WRAP_INFO: &[u8] = b"pngme sealed box v1 key wrap";
+
+// Layout: magic | version | sender public key | recipient count (u8) |
+// one slot per recipient | content nonce | ciphertext. The message is
+// encrypted once under a random content key, and that key is wrapped for
+// every recipient with a key derived from X25519(sender, recipient).
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct Opened {
+    pub message: Vec<u8>,
+    pub sender: PublicKey,
+}
+
+pub fn is_sealed(data: &[u8]) -> bool {
+    data.starts_with(&MAGIC)
+}
+
+pub fn generate_secret() -> StaticSecret {
+    StaticSecret::random_from_rng(OsRng)
+}
+
+fn random_bytes<const N: usize>() -> [u8; N] {
+    let mut bytes = [0; N];
+    OsRng.fill_bytes(&mut bytes);
+    bytes
+}
+
+fn wrapping_cipher(secret: &StaticSecret, other: &PublicKey, sender: &PublicKey, recipient: &PublicKey) -> XChaCha20Poly1305 {
+    let shared = secret.diffie_hellman(other);
+    let salt: Vec<u8> = sender.as_bytes().iter().chain(recipient.as_bytes().iter()).copied().collect();
+    let mut wrap_key = [0; KEY_LEN];
+    Hkdf::<Sha256>::new(Some(&salt), shared.as_bytes())
+        .expand(WRAP_INFO, &mut wrap_key)
+        .expect("32 bytes is a valid HKDF-SHA256 output length");
+    XChaCha20Poly1305::new(&wrap_key.into())
+}
+
+pub fn seal(message: &[u8], sender: &StaticSecret, recipients: &[PublicKey]) -> Result<Vec<u8>, SealError> {
+    if recipients.is_empty() {
+        return Err(SealError::NoRecipients);
+    }
+    if recipients.len() > u8::MAX as usize {
+        return Err(SealError::TooManyRecipients);
+    }
+    let sender_public = PublicKey::from(sender);
+    let content_key: [u8; KEY_LEN] = random_bytes();
+
+    let mut sealed = Vec::with_capacity(6 + KEY_LEN + recipients.len() * SLOT_LEN + NONCE_LEN + message.len() + TAG_LEN);
+    sealed.extend_from_slice(&MAGIC);
+    sealed.push(VERSION);
+    sealed.extend_from_slice(sender_public.as_bytes());
+    sealed.push(recipients.len() as u8);
+    for recipient in recipients {
+        let nonce: [u8; NONCE_LEN] = random_bytes();
+        let wrapped = wrapping_cipher(sender, recipient, &sender_public, recipient)
+            .encrypt(XNonce::from_slice(&nonce), &content_key[..])
+            .map_err(|_| SealError::Authentication)?;
+        sealed.extend_from_slice(recipient.as_bytes());
+        sealed.extend_from_slice(&nonce);
+        sealed.extend(wrapped);
+    }
+    let nonce: [u8; NONCE_LEN] = random_bytes();
+    sealed.extend_from_slice(&nonce);
+    let ciphertext = XChaCha20Poly1305::new(&content_key.into())
+        .encrypt(XNonce::from_slice(&nonce), Payload { msg: message, aad: &sealed })
+        .map_err(|_| SealError::Authentication)?;
+    sealed.extend(ciphertext);
+    Ok(sealed)
+}
+
+pub fn open(data: &[u8], recipient: &StaticSecret) -> Result<Opened, SealError> {
+    if !is_sealed(data) || data.len() < 6 + KEY_LEN {
+        return Err(SealError::InvalidHeader);
+    }
+    if data[4] != VERSION {
+        return Err(SealError::UnsupportedVersion(data[4]));
+    }
+    let sender = PublicKey::from(<[u8; KEY_LEN]>::try_from(&data[5..5 + KEY_LEN]).unwrap());
+    let count = data[5 + KEY_LEN] as usize;
+    let slots_start = 6 + KEY_LEN;
+    let header_len = slots_start + count * SLOT_LEN + NONCE_LEN;
+    if data.len() < header_len + TAG_LEN {
+        return Err(SealError::InvalidHeader);
+    }
+
+    let recipient_public = PublicKey::from(recipient);
+    let slot = data[slots_start..slots_start + count * SLOT_LEN]
+        .chunks_exact(SLOT_LEN)
+        .find(|slot| slot[..KEY_LEN] == recipient_public.as_bytes()[..])
+        .ok_or(SealError::NotARecipient)?;
+    let content_key = wrapping_cipher(recipient, &sender, &sender, &recipient_public)
+        .decrypt(XNonce::from_slice(&slot[KEY_LEN..KEY_LEN + NONCE_LEN]), &slot[KEY_LEN + NONCE_LEN..])
+        .map_err(|_| SealError::Authentication)?;
+    let content_key: [u8; KEY_LEN] = content_key.try_into().map_err(|_| SealError::Authentication)?;
+
+    let message = XChaCha20Poly1305::new(&content_key.into())
+        .decrypt(
+            XNonce::from_slice(&data[header_len - NONCE_LEN..header_len]),
+            Payload { msg: &data[header_len..], aad: &data[..header_len] },
+        )
+        .map_err(|_| SealError::Authentication)?;
+    Ok(Opened { message, sender })
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum SealError {
+    NoRecipients,
+    TooManyRecipients,
+    InvalidHeader,
+    UnsupportedVersion(u8),
+    NotARecipient,
+    Authentication,
+}
+
+impl Display for SealError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SealError::NoRecipients => write!(f, "at least one recipient is needed"),
+            SealError::TooManyRecipients => write!(f, "a message can have at most 255 recipients"),
+            SealError::InvalidHeader => write!(f, "message is not sealed or its header is damaged"),
+            SealError::UnsupportedVersion(version) => write!(f, "unsupported sealed message version {}", version),
+            SealError::NotARecipient => write!(f, "the message is not addressed to this private key"),
+            SealError::Authentication => write!(f, "the message was tampered with or the key is wrong"),
+        }
+    }
+}
+
+impl std::error::Error for SealError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_seal_and_open() {
+        let sender = generate_secret();
+        let recipient = generate_secret();
+        let sealed = seal(b"for your eyes only", &sender, &[PublicKey::from(&recipient)]).unwrap();
+        assert!(is_sealed(&sealed));
+
+        let opened = open(&sealed, &recipient).unwrap();
+        assert_eq!(opened.message, b"for your eyes only");
+        assert_eq!(opened.sender, PublicKey::from(&sender));
+    }
+
+    #[test]
+    fn test_multiple_recipients() {
+        let sender = generate_secret();
+        let alice = generate_secret();
+        let bob = generate_secret();
+        let eve = generate_secret();
+        let sealed = seal(b"team update", &sender, &[PublicKey::from(&alice), PublicKey::from(&bob)]).unwrap();
+
+        assert_eq!(open(&sealed, &alice).unwrap().message, b"team update");
+        assert_eq!(open(&sealed, &bob).unwrap().message, b"team update");
+        assert_eq!(open(&sealed, &eve), Err(SealError::NotARecipient));
+    }
+
+    #[test]
+    fn test_tampering_is_detected() {
+        let sender = generate_secret();
+        let recipient = generate_secret();
+        let mut sealed = seal(b"payload", &sender, &[PublicKey::from(&recipient)]).unwrap();
+        let last = sealed.len() - 1;
+        sealed[last] ^= 1;
+        assert_eq!(open(&sealed, &recipient), Err(SealError::Authentication));
+
+        // swapping in another sender key breaks the key wrap
+        let mut sealed = seal(b"payload", &sender, &[PublicKey::from(&recipient)]).unwrap();
+        sealed[5..5 + KEY_LEN].copy_from_slice(PublicKey::from(&generate_secret()).as_bytes());
+        assert_eq!(open(&sealed, &recipient), Err(SealError::Authentication));
+    }
+
+    #[test]
+    fn test_seal_errors() {
+        let sender = generate_secret();
+        assert_eq!(seal(b"x", &sender, &[]), Err(SealError::NoRecipients));
+        assert_eq!(open(b"PNGk", &sender), Err(SealError::InvalidHeader));
+        assert_eq!(open(b"not sealed at all, just text here", &sender), Err(SealError::InvalidHeader));
+    }
+}
@@ -0,0 +1,221 @@
+use std::fmt::Display;
+use std::str::FromStr;
+
+use chacha20poly1305::aead::OsRng;
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey, PUBLIC_KEY_LENGTH, SIGNATURE_LENGTH};
+
+use super::chunk::Chunk;
+use super::chunk_type::ChunkType;
+use super::png::{ChunkPlacement, Png, PngError};
+
+// ancillary, private, unsafe to copy: editors that don't know it drop it
+// once they modify critical chunks, which would break the signature anyway
+pub const SIGNATURE_CHUNK: &str = "siGN";
+const VERSION: u8 = 1;
+const DOMAIN: &[u8] = b"pngme signature v1";
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct CoveredChunk {
+    pub index: usize,
+    pub chunk_type: String,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct Verification {
+    pub signer: VerifyingKey,
+    pub covered: Vec<CoveredChunk>,
+}
+
+pub fn generate_signing_key() -> SigningKey {
+    SigningKey::generate(&mut OsRng)
+}
+
+fn is_signature_chunk(chunk: &Chunk) -> bool {
+    chunk.chunk_type().bytes() == SIGNATURE_CHUNK.as_bytes()
+}
+
+fn covered_chunks(png: &Png, ancillary: &[ChunkType]) -> Vec<CoveredChunk> {
+    png.chunks()
+        .iter()
+        .enumerate()
+        .filter(|(_, chunk)| !is_signature_chunk(chunk))
+        .filter(|(_, chunk)| chunk.chunk_type().is_critical() || ancillary.contains(chunk.chunk_type()))
+        .map(|(index, chunk)| CoveredChunk { index, chunk_type: chunk.chunk_type().to_string() })
+        .collect()
+}
+
+// the signed message also lists the included ancillary types, so they
+// can't be dropped from the signature chunk without notice
+fn signed_message(png: &Png, ancillary: &[ChunkType], covered: &[CoveredChunk]) -> Vec<u8> {
+    let mut message = DOMAIN.to_vec();
+    message.push(ancillary.len() as u8);
+    for chunk_type in ancillary {
+        message.extend_from_slice(&chunk_type.bytes());
+    }
+    for covered_chunk in covered {
+        message.extend(png.chunks()[covered_chunk.index].as_bytes());
+    }
+    message
+}
+
+pub fn sign(png: &mut Png, key: &SigningKey, ancillary: &[ChunkType]) -> Result<Vec<CoveredChunk>, SignatureError> {
+    if ancillary.len() > u8::MAX as usize {
+        return Err(SignatureError::TooManyChunkTypes);
+    }
+    while png.remove_chunk(SIGNATURE_CHUNK).is_ok() {}
+
+    let covered = covered_chunks(png, ancillary);
+    let signature = key.sign(&signed_message(png, ancillary, &covered));
+    let mut data = vec![VERSION];
+    data.extend_from_slice(key.verifying_key().as_bytes());
+    data.push(ancillary.len() as u8);
+    for chunk_type in ancillary {
+        data.extend_from_slice(&chunk_type.bytes());
+    }
+    data.extend_from_slice(&signature.to_bytes());
+
+    let chunk = Chunk::new(ChunkType::from_str(SIGNATURE_CHUNK).unwrap(), data);
+    png.insert_chunk(chunk, ChunkPlacement::BeforeIend).map_err(SignatureError::Png)?;
+    Ok(covered)
+}
+
+pub fn verify(png: &Png) -> Result<Verification, SignatureError> {
+    let chunk = png.chunk_by_type(SIGNATURE_CHUNK).ok_or(SignatureError::Unsigned)?;
+    let data = chunk.data();
+    if data.is_empty() {
+        return Err(SignatureError::InvalidSignatureChunk);
+    }
+    if data[0] != VERSION {
+        return Err(SignatureError::UnsupportedVersion(data[0]));
+    }
+    let count_at = 1 + PUBLIC_KEY_LENGTH;
+    let count = *data.get(count_at).ok_or(SignatureError::InvalidSignatureChunk)? as usize;
+    let signature_at = count_at + 1 + count * 4;
+    if data.len() != signature_at + SIGNATURE_LENGTH {
+        return Err(SignatureError::InvalidSignatureChunk);
+    }
+
+    let signer = VerifyingKey::from_bytes(&data[1..count_at].try_into().unwrap())
+        .map_err(|_| SignatureError::InvalidSignatureChunk)?;
+    let ancillary = data[count_at + 1..signature_at]
+        .chunks_exact(4)
+        .map(|bytes| ChunkType::try_from([bytes[0], bytes[1], bytes[2], bytes[3]]))
+        .collect::<Result<Vec<ChunkType>, _>>()
+        .map_err(|_| SignatureError::InvalidSignatureChunk)?;
+    let signature = Signature::from_bytes(&data[signature_at..].try_into().unwrap());
+
+    let covered = covered_chunks(png, &ancillary);
+    signer
+        .verify(&signed_message(png, &ancillary, &covered), &signature)
+        .map_err(|_| SignatureError::BadSignature)?;
+    Ok(Verification { signer, covered })
+}
+
+#[derive(Debug)]
+pub enum SignatureError {
+    Unsigned,
+    InvalidSignatureChunk,
+    UnsupportedVersion(u8),
+    TooManyChunkTypes,
+    BadSignature,
+    Png(PngError),
+}
+
+impl Display for SignatureError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SignatureError::Unsigned => write!(f, "the PNG has no {} signature chunk", SIGNATURE_CHUNK),
+            SignatureError::InvalidSignatureChunk => write!(f, "the signature chunk is malformed"),
+            SignatureError::UnsupportedVersion(version) => write!(f, "unsupported signature version {}", version),
+            SignatureError::TooManyChunkTypes => write!(f, "at most 255 ancillary chunk types can be signed"),
+            SignatureError::BadSignature => write!(f, "signature does not match, the PNG was altered"),
+            SignatureError::Png(err) => write!(f, "couldn't add the signature chunk: {:?}", err),
+        }
+    }
+}
+
+impl std::error::Error for SignatureError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chunk(chunk_type: &str, data: &str) -> Chunk {
+        Chunk::new(ChunkType::from_str(chunk_type).unwrap(), data.as_bytes().to_vec())
+    }
+
+    fn testing_png() -> Png {
+        Png::from_chunks(vec![
+            chunk("IHDR", "header"),
+            chunk("tEXt", "Author\0me"),
+            chunk("IDAT", "pixels"),
+            chunk("zTXt", "note"),
+            chunk("IEND", ""),
+        ])
+        .unwrap()
+    }
+
+    fn types(covered: &[CoveredChunk]) -> Vec<&str> {
+        covered.iter().map(|c| c.chunk_type.as_str()).collect()
+    }
+
+    #[test]
+    fn test_sign_and_verify() {
+        let key = generate_signing_key();
+        let mut png = testing_png();
+        sign(&mut png, &key, &[]).unwrap();
+        assert_eq!(png.chunks()[png.chunks().len() - 2].chunk_type().to_string(), SIGNATURE_CHUNK);
+
+        let verification = verify(&png).unwrap();
+        assert_eq!(verification.signer, key.verifying_key());
+        assert_eq!(types(&verification.covered), vec!["IHDR", "IDAT", "IEND"]);
+    }
+
+    #[test]
+    fn test_sign_with_ancillary_chunks() {
+        let key = generate_signing_key();
+        let mut png = testing_png();
+        sign(&mut png, &key, &[ChunkType::from_str("tEXt").unwrap()]).unwrap();
+        let verification = verify(&png).unwrap();
+        assert_eq!(types(&verification.covered), vec!["IHDR", "tEXt", "IDAT", "IEND"]);
+        assert_eq!(verification.covered[1].index, 1);
+    }
+
+    #[test]
+    fn test_unsigned_ancillary_changes_are_allowed() {
+        let key = generate_signing_key();
+        let mut png = testing_png();
+        sign(&mut png, &key, &[ChunkType::from_str("tEXt").unwrap()]).unwrap();
+        png.remove_chunk("zTXt").unwrap();
+        assert!(verify(&png).is_ok());
+
+        png.remove_chunk("tEXt").unwrap();
+        assert!(matches!(verify(&png), Err(SignatureError::BadSignature)));
+    }
+
+    #[test]
+    fn test_altered_critical_chunk_fails() {
+        let key = generate_signing_key();
+        let mut png = testing_png();
+        sign(&mut png, &key, &[]).unwrap();
+        assert!(png.remove_chunk("IDAT").is_err());
+        png.insert_chunk(chunk("IDAT", "more pixels"), ChunkPlacement::BeforeFirstIdat).unwrap();
+        assert!(matches!(verify(&png), Err(SignatureError::BadSignature)));
+    }
+
+    #[test]
+    fn test_resigning_replaces_signature() {
+        let mut png = testing_png();
+        sign(&mut png, &generate_signing_key(), &[]).unwrap();
+        let key = generate_signing_key();
+        sign(&mut png, &key, &[]).unwrap();
+        let signatures = png.chunks().iter().filter(|c| is_signature_chunk(c)).count();
+        assert_eq!(signatures, 1);
+        assert_eq!(verify(&png).unwrap().signer, key.verifying_key());
+    }
+
+    #[test]
+    fn test_unsigned_png() {
+        assert!(matches!(verify(&testing_png()), Err(SignatureError::Unsigned)));
+    }
+}
@@ -0,0 +1,237 @@
+extern crate crc;
+use crc::{Crc, CRC_32_ISO_HDLC};
+use std::fmt::Display;
+
+pub const MAGIC: [u8; 4] = *b"PNGs";
+const VERSION: u8 = 1;
+// magic, version, payload CRC-32, sequence number and piece count (u32 each)
+const HEADER_LEN: usize = 4 + 1 + 4 + 4 + 4;
+// chunk lengths are limited to 2^31 - 1 bytes
+pub const MAX_PIECE_LEN: usize = i32::MAX as usize - HEADER_LEN;
+// how many missing sequence numbers an error lists
+const MAX_REPORTED_MISSING: usize = 10;
+
+// The CRC of the whole payload doubles as its id, so pieces of two
+// different payloads stored under the same chunk type are told apart.
+
+#[derive(Debug, PartialEq, Eq)]
+struct Piece<'a> {
+    checksum: u32,
+    sequence: u32,
+    total: u32,
+    data: &'a [u8],
+}
+
+fn checksum(payload: &[u8]) -> u32 {
+    Crc::<u32>::new(&CRC_32_ISO_HDLC).checksum(payload)
+}
+
+pub fn is_piece(data: &[u8]) -> bool {
+    data.starts_with(&MAGIC)
+}
+
+fn parse_piece(data: &[u8]) -> Result<Piece<'_>, SplitError> {
+    if !is_piece(data) || data.len() < HEADER_LEN {
+        return Err(SplitError::InvalidHeader);
+    }
+    if data[4] != VERSION {
+        return Err(SplitError::UnsupportedVersion(data[4]));
+    }
+    let read_u32 = |at: usize| u32::from_be_bytes([data[at], data[at + 1], data[at + 2], data[at + 3]]);
+    let piece = Piece { checksum: read_u32(5), sequence: read_u32(9), total: read_u32(13), data: &data[HEADER_LEN..] };
+    if piece.total == 0 || piece.sequence >= piece.total {
+        return Err(SplitError::InvalidHeader);
+    }
+    Ok(piece)
+}
+
+pub fn split(payload: &[u8], piece_len: usize) -> Result<Vec<Vec<u8>>, SplitError> {
+    if piece_len == 0 || piece_len > MAX_PIECE_LEN {
+        return Err(SplitError::InvalidPieceLength(piece_len));
+    }
+    // an empty payload still needs one piece to be found again
+    let total = payload.len().div_ceil(piece_len).max(1);
+    if total > u32::MAX as usize {
+        return Err(SplitError::TooManyPieces);
+    }
+    let checksum = checksum(payload);
+    let pieces = (0..total)
+        .map(|sequence| {
+            let data = &payload[(sequence * piece_len).min(payload.len())..((sequence + 1) * piece_len).min(payload.len())];
+            let mut piece = Vec::with_capacity(HEADER_LEN + data.len());
+            piece.extend_from_slice(&MAGIC);
+            piece.push(VERSION);
+            piece.extend_from_slice(&checksum.to_be_bytes());
+            piece.extend_from_slice(&(sequence as u32).to_be_bytes());
+            piece.extend_from_slice(&(total as u32).to_be_bytes());
+            piece.extend_from_slice(data);
+            piece
+        })
+        .collect();
+    Ok(pieces)
+}
+
+// pieces may come in any order, they're put back by sequence number
+pub fn join(pieces: &[&[u8]]) -> Result<Vec<u8>, SplitError> {
+    let mut parsed = pieces.iter().map(|piece| parse_piece(piece)).collect::<Result<Vec<Piece>, _>>()?;
+    let first = parsed.first().ok_or(SplitError::Missing { count: 1, first: vec![0] })?;
+    let (id, total) = (first.checksum, first.total);
+    if parsed.iter().any(|piece| piece.checksum != id || piece.total != total) {
+        return Err(SplitError::MixedPayloads);
+    }
+    parsed.sort_by_key(|piece| piece.sequence);
+    if let Some(pair) = parsed.windows(2).find(|pair| pair[0].sequence == pair[1].sequence) {
+        return Err(SplitError::Duplicate(pair[0].sequence));
+    }
+    // the piece count comes from the file, so it's checked against the
+    // pieces that are actually there rather than counted up to
+    if parsed.len() != total as usize {
+        return Err(missing_pieces(&parsed, total));
+    }
+
+    let payload: Vec<u8> = parsed.iter().flat_map(|piece| piece.data.iter().copied()).collect();
+    match checksum(&payload) == id {
+        true => Ok(payload),
+        false => Err(SplitError::ChecksumMismatch),
+    }
+}
+
+// walks the gaps between sorted, distinct sequence numbers
+fn missing_pieces(sorted: &[Piece], total: u32) -> SplitError {
+    let mut first = Vec::new();
+    let mut expected = 0;
+    for sequence in sorted.iter().map(|piece| piece.sequence).chain([total]) {
+        first.extend((expected..sequence).take(MAX_REPORTED_MISSING - first.len()));
+        if first.len() == MAX_REPORTED_MISSING {
+            break;
+        }
+        expected = sequence.saturating_add(1);
+    }
+    SplitError::Missing { count: total - sorted.len() as u32, first }
+}
+
+// indices into `data` grouped by payload: pieces of one payload end up
+// together, anything else stands alone; groups keep their first appearance
+pub fn group_pieces(data: &[&[u8]]) -> Vec<Vec<usize>> {
+    // payload checksum and piece count, None for anything that isn't a piece
+    type Key = Option<(u32, u32)>;
+    let mut groups: Vec<(Key, Vec<usize>)> = Vec::new();
+    for (index, bytes) in data.iter().enumerate() {
+        let key = parse_piece(bytes).ok().map(|piece| (piece.checksum, piece.total));
+        match groups.iter_mut().find(|(group_key, _)| key.is_some() && *group_key == key) {
+            Some((_, indices)) => indices.push(index),
+            None => groups.push((key, vec![index])),
+        }
+    }
+    groups.into_iter().map(|(_, indices)| indices).collect()
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum SplitError {
+    InvalidPieceLength(usize),
+    TooManyPieces,
+    InvalidHeader,
+    UnsupportedVersion(u8),
+    MixedPayloads,
+    // how many pieces are missing and the first few of them
+    Missing { count: u32, first: Vec<u32> },
+    Duplicate(u32),
+    ChecksumMismatch,
+}
+
+impl Display for SplitError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SplitError::InvalidPieceLength(len) => write!(f, "pieces must be 1 to {} bytes, not {}", MAX_PIECE_LEN, len),
+            SplitError::TooManyPieces => write!(f, "payload would need more than 2^32 pieces"),
+            SplitError::InvalidHeader => write!(f, "chunk is not a payload piece or its header is damaged"),
+            SplitError::UnsupportedVersion(version) => write!(f, "unsupported piece format version {}", version),
+            SplitError::MixedPayloads => write!(f, "the chunks hold pieces of more than one payload"),
+            SplitError::Missing { count, first } => {
+                let sequences: Vec<String> = first.iter().map(|s| s.to_string()).collect();
+                write!(f, "missing {} payload piece(s): {}", count, sequences.join(", "))?;
+                match *count as usize > first.len() {
+                    true => write!(f, ", ..."),
+                    false => Ok(()),
+                }
+            }
+            SplitError::Duplicate(sequence) => write!(f, "payload piece {} appears more than once", sequence),
+            SplitError::ChecksumMismatch => write!(f, "reassembled payload doesn't match its checksum"),
+        }
+    }
+}
+
+impl std::error::Error for SplitError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn refs(pieces: &[Vec<u8>]) -> Vec<&[u8]> {
+        pieces.iter().map(|piece| piece.as_slice()).collect()
+    }
+
+    #[test]
+    fn test_split_and_join() {
+        let payload: Vec<u8> = (0..100).collect();
+        let pieces = split(&payload, 30).unwrap();
+        assert_eq!(pieces.len(), 4);
+        assert!(pieces.iter().all(|piece| is_piece(piece)));
+        assert_eq!(pieces[3].len(), HEADER_LEN + 10);
+        assert_eq!(join(&refs(&pieces)).unwrap(), payload);
+    }
+
+    #[test]
+    fn test_join_out_of_order() {
+        let pieces = split(b"abcdefghij", 3).unwrap();
+        let shuffled = vec![pieces[2].as_slice(), pieces[0].as_slice(), pieces[3].as_slice(), pieces[1].as_slice()];
+        assert_eq!(join(&shuffled).unwrap(), b"abcdefghij");
+    }
+
+    #[test]
+    fn test_empty_payload() {
+        let pieces = split(b"", 8).unwrap();
+        assert_eq!(pieces.len(), 1);
+        assert_eq!(join(&refs(&pieces)).unwrap(), b"");
+    }
+
+    #[test]
+    fn test_missing_and_duplicate_pieces() {
+        let pieces = split(b"abcdefghij", 3).unwrap();
+        assert_eq!(join(&[&pieces[0], &pieces[2]]), Err(SplitError::Missing { count: 2, first: vec![1, 3] }));
+        assert_eq!(join(&[&pieces[0], &pieces[1], &pieces[1], &pieces[2], &pieces[3]]), Err(SplitError::Duplicate(1)));
+        assert_eq!(join(&[]), Err(SplitError::Missing { count: 1, first: vec![0] }));
+    }
+
+    #[test]
+    fn test_huge_piece_count() {
+        // a forged header claiming u32::MAX pieces must not be counted up to
+        let mut piece = split(b"abc", 3).unwrap().remove(0);
+        piece[13..17].copy_from_slice(&u32::MAX.to_be_bytes());
+        piece[9..13].copy_from_slice(&5u32.to_be_bytes());
+        let err = join(&[&piece]).unwrap_err();
+        assert_eq!(err, SplitError::Missing { count: u32::MAX - 1, first: vec![0, 1, 2, 3, 4, 6, 7, 8, 9, 10] });
+        assert!(err.to_string().ends_with("9, 10, ..."));
+    }
+
+    #[test]
+    fn test_group_pieces() {
+        let first = split(b"abcdefghij", 5).unwrap();
+        let second = split(b"0123456789", 5).unwrap();
+        let data: Vec<&[u8]> = vec![b"plain", &first[0], &second[0], &first[1], b"plain", &second[1]];
+        assert_eq!(group_pieces(&data), vec![vec![0], vec![1, 3], vec![2, 5], vec![4]]);
+    }
+
+    #[test]
+    fn test_mixed_and_damaged_pieces() {
+        let first = split(b"abcdefghij", 5).unwrap();
+        let second = split(b"0123456789", 5).unwrap();
+        assert_eq!(join(&[&first[0], &second[1]]), Err(SplitError::MixedPayloads));
+
+        let mut damaged = first.clone();
+        damaged[1][HEADER_LEN] ^= 1;
+        assert_eq!(join(&refs(&damaged)), Err(SplitError::ChecksumMismatch));
+        assert_eq!(join(&[b"plain message"]), Err(SplitError::InvalidHeader));
+        assert_eq!(split(b"x", 0), Err(SplitError::InvalidPieceLength(0)));
+    }
+}
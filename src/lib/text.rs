@@ -0,0 +1,379 @@
+use std::fmt::Display;
+
+use super::chunk::{chunk, Chunk};
+use super::decoder;
+use super::encoder;
+use super::png::{ChunkPlacement, Png};
+
+pub const TEXT_CHUNK_TYPES: [&str; 3] = ["tEXt", "zTXt", "iTXt"];
+const COMPRESSION_LEVEL: u32 = 9;
+// zTXt and iTXt are inflated no further than this
+const MAX_TEXT_LEN: usize = 16 << 20;
+
+// tEXt and zTXt are Latin-1 throughout, iTXt has a Latin-1 keyword but
+// UTF-8 for everything else
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TextChunk {
+    Text {
+        keyword: String,
+        text: String,
+    },
+    CompressedText {
+        keyword: String,
+        text: String,
+    },
+    InternationalText {
+        keyword: String,
+        compressed: bool,
+        language_tag: String,
+        translated_keyword: String,
+        text: String,
+    },
+}
+
+fn latin1_decode(bytes: &[u8]) -> String {
+    bytes.iter().map(|&byte| byte as char).collect()
+}
+
+fn latin1_encode(s: &str) -> Result<Vec<u8>, TextError> {
+    s.chars()
+        .map(|c| u8::try_from(c as u32).map_err(|_| TextError::NotLatin1))
+        .collect()
+}
+
+pub fn is_latin1(s: &str) -> bool {
+    s.chars().all(|c| (c as u32) <= 0xff)
+}
+
+// 1 to 79 printable Latin-1 characters, no leading, trailing or double spaces
+pub fn validate_keyword(keyword: &str) -> Result<Vec<u8>, TextError> {
+    let bytes = latin1_encode(keyword).map_err(|_| TextError::InvalidKeyword)?;
+    let printable = bytes.iter().all(|&byte| (32..=126).contains(&byte) || byte >= 161);
+    if bytes.is_empty() || bytes.len() > 79 || !printable
+        || keyword.starts_with(' ') || keyword.ends_with(' ') || keyword.contains("  ") {
+        return Err(TextError::InvalidKeyword);
+    }
+    Ok(bytes)
+}
+
+// splits off the bytes up to the next null separator
+fn split_null(data: &[u8]) -> Result<(&[u8], &[u8]), TextError> {
+    let pos = data.iter().position(|&byte| byte == 0).ok_or(TextError::MissingSeparator)?;
+    Ok((&data[..pos], &data[pos + 1..]))
+}
+
+fn inflate(data: &[u8]) -> Result<Vec<u8>, TextError> {
+    decoder::inflate_limited(data, MAX_TEXT_LEN).map_err(|err| match err {
+        decoder::DecodeError::TooLarge(limit) => TextError::TooLong(limit),
+        err => TextError::Inflate(format!("{:?}", err)),
+    })
+}
+
+impl TextChunk {
+    pub fn keyword(&self) -> &str {
+        match self {
+            TextChunk::Text { keyword, .. }
+            | TextChunk::CompressedText { keyword, .. }
+            | TextChunk::InternationalText { keyword, .. } => keyword,
+        }
+    }
+
+    pub fn text(&self) -> &str {
+        match self {
+            TextChunk::Text { text, .. }
+            | TextChunk::CompressedText { text, .. }
+            | TextChunk::InternationalText { text, .. } => text,
+        }
+    }
+
+    pub fn chunk_type(&self) -> &'static str {
+        match self {
+            TextChunk::Text { .. } => "tEXt",
+            TextChunk::CompressedText { .. } => "zTXt",
+            TextChunk::InternationalText { .. } => "iTXt",
+        }
+    }
+
+    pub fn to_chunk(&self) -> Result<Chunk, TextError> {
+        let mut data = validate_keyword(self.keyword())?;
+        data.push(0);
+        match self {
+            TextChunk::Text { text, .. } => data.extend(latin1_encode(text)?),
+            TextChunk::CompressedText { text, .. } => {
+                data.push(0);
+                data.extend(encoder::deflate(&latin1_encode(text)?, COMPRESSION_LEVEL));
+            }
+            TextChunk::InternationalText { compressed, language_tag, translated_keyword, text, .. } => {
+                if language_tag.contains('\0') || translated_keyword.contains('\0') {
+                    return Err(TextError::MissingSeparator);
+                }
+                data.push(*compressed as u8);
+                data.push(0);
+                data.extend_from_slice(language_tag.as_bytes());
+                data.push(0);
+                data.extend_from_slice(translated_keyword.as_bytes());
+                data.push(0);
+                match compressed {
+                    true => data.extend(encoder::deflate(text.as_bytes(), COMPRESSION_LEVEL)),
+                    false => data.extend_from_slice(text.as_bytes()),
+                }
+            }
+        }
+        Ok(chunk(self.chunk_type(), data))
+    }
+}
+
+impl TryFrom<&Chunk> for TextChunk {
+    type Error = TextError;
+
+    fn try_from(chunk: &Chunk) -> Result<Self, Self::Error> {
+        let chunk_type = chunk.chunk_type().to_string();
+        let (keyword, rest) = split_null(chunk.data())?;
+        let keyword = latin1_decode(keyword);
+        match chunk_type.as_str() {
+            "tEXt" => Ok(TextChunk::Text { keyword, text: latin1_decode(rest) }),
+            "zTXt" => {
+                let (&method, compressed) = rest.split_first().ok_or(TextError::Truncated)?;
+                if method != 0 {
+                    return Err(TextError::UnknownCompressionMethod(method));
+                }
+                Ok(TextChunk::CompressedText { keyword, text: latin1_decode(&inflate(compressed)?) })
+            }
+            "iTXt" => {
+                if rest.len() < 2 {
+                    return Err(TextError::Truncated);
+                }
+                let compressed = match (rest[0], rest[1]) {
+                    (0, _) => false,
+                    (1, 0) => true,
+                    (1, method) => return Err(TextError::UnknownCompressionMethod(method)),
+                    (flag, _) => return Err(TextError::InvalidCompressionFlag(flag)),
+                };
+                let (language_tag, rest) = split_null(&rest[2..])?;
+                let (translated_keyword, text) = split_null(rest)?;
+                let text = match compressed {
+                    true => inflate(text)?,
+                    false => text.to_vec(),
+                };
+                let utf8 = |bytes: &[u8]| String::from_utf8(bytes.to_vec()).map_err(|_| TextError::InvalidUtf8);
+                Ok(TextChunk::InternationalText {
+                    keyword,
+                    compressed,
+                    language_tag: utf8(language_tag)?,
+                    translated_keyword: utf8(translated_keyword)?,
+                    text: utf8(&text)?,
+                })
+            }
+            _ => Err(TextError::NotATextChunk),
+        }
+    }
+}
+
+impl Display for TextChunk {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TextChunk::InternationalText { language_tag, translated_keyword, .. }
+                if !language_tag.is_empty() || !translated_keyword.is_empty() =>
+            {
+                write!(f, "{} [{} / {}]: {}", self.keyword(), language_tag, translated_keyword, self.text())
+            }
+            _ => write!(f, "{}: {}", self.keyword(), self.text()),
+        }
+    }
+}
+
+fn is_text_chunk(chunk: &Chunk) -> bool {
+    TEXT_CHUNK_TYPES.contains(&chunk.chunk_type().to_string().as_str())
+}
+
+// every text chunk in file order, malformed ones included as errors
+pub fn text_chunks(png: &Png) -> Vec<Result<TextChunk, TextError>> {
+    png.chunks().iter().filter(|chunk| is_text_chunk(chunk)).map(TextChunk::try_from).collect()
+}
+
+// removes every text chunk with this keyword, whatever its type
+pub fn delete_text(png: &mut Png, keyword: &str) -> Result<usize, TextError> {
+    delete_text_except(png, keyword, None)
+}
+
+// `keep` spares one chunk, as its type and its index among chunks of that type
+// chunks are matched on the raw keyword bytes, so ones whose text can't be
+// read still go
+fn delete_text_except(png: &mut Png, keyword: &str, keep: Option<(&str, usize)>) -> Result<usize, TextError> {
+    let Ok(keyword) = latin1_encode(keyword) else {
+        return Ok(0);
+    };
+    let mut removed = 0;
+    for chunk_type in TEXT_CHUNK_TYPES {
+        let matches: Vec<usize> = png
+            .chunks_by_type(chunk_type)
+            .iter()
+            .enumerate()
+            .filter(|(n, chunk)| {
+                keep != Some((chunk_type, *n)) && split_null(chunk.data()).is_ok_and(|(raw, _)| raw == keyword)
+            })
+            .map(|(n, _)| n)
+            .collect();
+        for &n in matches.iter().rev() {
+            png.remove_nth_chunk(chunk_type, n).map_err(TextError::Png)?;
+            removed += 1;
+        }
+    }
+    Ok(removed)
+}
+
+// replaces any text chunks with the same keyword; the new chunk goes in
+// first, so the old text is still there if that fails
+pub fn set_text(png: &mut Png, text: &TextChunk) -> Result<(), TextError> {
+    let chunk = text.to_chunk()?;
+    let index = png.insert_chunk(chunk, ChunkPlacement::BeforeIend).map_err(|err| TextError::Png(format!("{:?}", err)))?;
+    let nth = png.chunks()[..index].iter().filter(|chunk| chunk.chunk_type().to_string() == text.chunk_type()).count();
+    delete_text_except(png, text.keyword(), Some((text.chunk_type(), nth)))?;
+    Ok(())
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum TextError {
+    NotATextChunk,
+    InvalidKeyword,
+    NotLatin1,
+    MissingSeparator,
+    Truncated,
+    InvalidCompressionFlag(u8),
+    UnknownCompressionMethod(u8),
+    Inflate(String),
+    TooLong(usize),
+    InvalidUtf8,
+    Png(String),
+}
+
+impl Display for TextError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TextError::NotATextChunk => write!(f, "not a tEXt, zTXt or iTXt chunk"),
+            TextError::InvalidKeyword => write!(f, "keywords are 1 to 79 printable Latin-1 characters without extra spaces"),
+            TextError::NotLatin1 => write!(f, "tEXt and zTXt only hold Latin-1 text"),
+            TextError::MissingSeparator => write!(f, "text chunk field is missing its null separator"),
+            TextError::Truncated => write!(f, "text chunk is truncated"),
+            TextError::InvalidCompressionFlag(flag) => write!(f, "invalid iTXt compression flag {}", flag),
+            TextError::UnknownCompressionMethod(method) => write!(f, "unknown text compression method {}", method),
+            TextError::Inflate(reason) => write!(f, "couldn't inflate the text: {}", reason),
+            TextError::TooLong(limit) => write!(f, "compressed text inflates to more than {} bytes", limit),
+            TextError::InvalidUtf8 => write!(f, "iTXt field is not valid UTF-8"),
+            TextError::Png(reason) => write!(f, "{}", reason),
+        }
+    }
+}
+
+impl std::error::Error for TextError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk_type::ChunkType;
+    use std::str::FromStr;
+
+    fn round_trip(text: TextChunk) {
+        let chunk = text.to_chunk().unwrap();
+        assert_eq!(chunk.chunk_type().to_string(), text.chunk_type());
+        assert_eq!(TextChunk::try_from(&chunk).unwrap(), text);
+    }
+
+    fn testing_png() -> Png {
+        let chunk = |chunk_type: &str, data: &[u8]| Chunk::new(ChunkType::from_str(chunk_type).unwrap(), data.to_vec());
+        Png::from_chunks(vec![
+            chunk("IHDR", b"header"),
+            chunk("tEXt", b"Author\0me"),
+            chunk("IDAT", b"pixels"),
+            chunk("tEXt", b"Comment\0first"),
+            chunk("IEND", b""),
+        ])
+        .unwrap()
+    }
+
+    #[test]
+    fn test_text_round_trips() {
+        round_trip(TextChunk::Text { keyword: "Title".to_string(), text: "Caf\u{e9} au lait".to_string() });
+        round_trip(TextChunk::CompressedText { keyword: "Comment".to_string(), text: "long ".repeat(50) });
+        for compressed in [false, true] {
+            round_trip(TextChunk::InternationalText {
+                keyword: "Title".to_string(),
+                compressed,
+                language_tag: "ja".to_string(),
+                translated_keyword: "\u{30bf}\u{30a4}\u{30c8}\u{30eb}".to_string(),
+                text: "\u{65e5}\u{672c}".to_string(),
+            });
+        }
+    }
+
+    #[test]
+    fn test_text_layout() {
+        let chunk = TextChunk::Text { keyword: "Author".to_string(), text: "\u{e9}".to_string() }.to_chunk().unwrap();
+        assert_eq!(chunk.data(), b"Author\0\xe9");
+        let chunk = TextChunk::InternationalText {
+            keyword: "K".to_string(),
+            compressed: false,
+            language_tag: "en".to_string(),
+            translated_keyword: String::new(),
+            text: "v".to_string(),
+        }
+        .to_chunk()
+        .unwrap();
+        assert_eq!(chunk.data(), b"K\0\0\0en\0\0v");
+    }
+
+    #[test]
+    fn test_invalid_text() {
+        let text = |keyword: &str, text: &str| TextChunk::Text { keyword: keyword.to_string(), text: text.to_string() };
+        assert_eq!(text("", "x").to_chunk().err(), Some(TextError::InvalidKeyword));
+        assert_eq!(text(" lead", "x").to_chunk().err(), Some(TextError::InvalidKeyword));
+        assert_eq!(text("two  spaces", "x").to_chunk().err(), Some(TextError::InvalidKeyword));
+        assert_eq!(text(&"k".repeat(80), "x").to_chunk().err(), Some(TextError::InvalidKeyword));
+        assert_eq!(text("Title", "\u{65e5}").to_chunk().err(), Some(TextError::NotLatin1));
+
+        let chunk = Chunk::new(ChunkType::from_str("tEXt").unwrap(), b"no separator".to_vec());
+        assert_eq!(TextChunk::try_from(&chunk), Err(TextError::MissingSeparator));
+        let chunk = Chunk::new(ChunkType::from_str("iTXt").unwrap(), b"K\0\x02\0\0\0v".to_vec());
+        assert_eq!(TextChunk::try_from(&chunk), Err(TextError::InvalidCompressionFlag(2)));
+
+        let mut data = b"Bomb  ".to_vec();
+        data.extend(encoder::deflate(&vec![b'a'; MAX_TEXT_LEN + 1], 9));
+        let chunk = Chunk::new(ChunkType::from_str("zTXt").unwrap(), data);
+        assert_eq!(TextChunk::try_from(&chunk), Err(TextError::TooLong(MAX_TEXT_LEN)));
+    }
+
+    #[test]
+    fn test_set_and_delete_text() {
+        let mut png = testing_png();
+        set_text(&mut png, &TextChunk::CompressedText { keyword: "Comment".to_string(), text: "second".to_string() })
+            .unwrap();
+        let texts: Vec<TextChunk> = text_chunks(&png).into_iter().map(Result::unwrap).collect();
+        assert_eq!(texts.len(), 2);
+        assert_eq!(texts[1].chunk_type(), "zTXt");
+        assert_eq!(texts[1].text(), "second");
+
+        // same keyword and chunk type, only the old one goes
+        set_text(&mut png, &TextChunk::CompressedText { keyword: "Comment".to_string(), text: "third".to_string() })
+            .unwrap();
+        let texts: Vec<TextChunk> = text_chunks(&png).into_iter().map(Result::unwrap).collect();
+        assert_eq!(texts.len(), 2);
+        assert_eq!(texts[1].text(), "third");
+
+        assert_eq!(delete_text(&mut png, "Author").unwrap(), 1);
+        assert_eq!(delete_text(&mut png, "Author").unwrap(), 0);
+        assert_eq!(text_chunks(&png).len(), 1);
+    }
+
+    #[test]
+    fn test_corrupt_text_is_replaced() {
+        let mut png = testing_png();
+        let corrupt = Chunk::new(ChunkType::from_str("zTXt").unwrap(), b"Comment  not deflate".to_vec());
+        png.insert_chunk(corrupt, ChunkPlacement::BeforeIend).unwrap();
+        assert!(text_chunks(&png)[2].is_err());
+
+        set_text(&mut png, &TextChunk::Text { keyword: "Comment".to_string(), text: "fresh".to_string() }).unwrap();
+        let texts: Vec<TextChunk> = text_chunks(&png).into_iter().map(Result::unwrap).collect();
+        assert_eq!(texts.len(), 2);
+        assert_eq!(texts[1].text(), "fresh");
+    }
+}
@@ -0,0 +1,161 @@
+use std::io::{self, Write};
+
+use crc::{Crc, CRC_32_ISO_HDLC};
+
+use super::chunk::Chunk;
+use super::chunk_type::ChunkType;
+use super::reader::SIGNATURE;
+
+static CRC: Crc<u32> = Crc::<u32>::new(&CRC_32_ISO_HDLC);
+const DEFAULT_IDAT_SIZE: usize = 8192;
+
+// writes the signature and then one chunk at a time; image data is taken
+// as a stream and cut into IDAT chunks, so at most one IDAT is buffered
+pub struct ChunkWriter<W: Write> {
+    writer: W,
+    idat_size: usize,
+    idat: Vec<u8>,
+}
+
+impl<W: Write> ChunkWriter<W> {
+    pub fn new(mut writer: W) -> io::Result<ChunkWriter<W>> {
+        writer.write_all(&SIGNATURE)?;
+        Ok(ChunkWriter { writer, idat_size: DEFAULT_IDAT_SIZE, idat: Vec::new() })
+    }
+
+    pub fn set_idat_size(&mut self, idat_size: usize) {
+        self.idat_size = idat_size.clamp(1, i32::MAX as usize);
+    }
+
+    // any image data written so far is closed off first
+    pub fn write_chunk(&mut self, chunk: &Chunk) -> io::Result<()> {
+        self.finish_image_data()?;
+        self.write_data(chunk.chunk_type(), chunk.data())
+    }
+
+    // writes straight through, so callers close off pending image data first
+    fn write_data(&mut self, chunk_type: &ChunkType, data: &[u8]) -> io::Result<()> {
+        if data.len() > i32::MAX as usize {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "chunk data is longer than 2^31 - 1 bytes"));
+        }
+        let mut digest = CRC.digest();
+        self.writer.write_all(&(data.len() as u32).to_be_bytes())?;
+        for part in [&chunk_type.bytes()[..], data] {
+            digest.update(part);
+            self.writer.write_all(part)?;
+        }
+        self.writer.write_all(&digest.finalize().to_be_bytes())
+    }
+
+    // the zlib stream of the image, in pieces of any size
+    pub fn write_image_data(&mut self, mut data: &[u8]) -> io::Result<()> {
+        while !data.is_empty() {
+            let take = (self.idat_size - self.idat.len()).min(data.len());
+            self.idat.extend_from_slice(&data[..take]);
+            data = &data[take..];
+            if self.idat.len() == self.idat_size {
+                self.flush_idat()?;
+            }
+        }
+        Ok(())
+    }
+
+    // a Write over the image data, e.g. for a ZlibEncoder to deflate into
+    pub fn image_data(&mut self) -> ImageData<'_, W> {
+        ImageData(self)
+    }
+
+    pub fn finish_image_data(&mut self) -> io::Result<()> {
+        match self.idat.is_empty() {
+            true => Ok(()),
+            false => self.flush_idat(),
+        }
+    }
+
+    pub fn finish(mut self) -> io::Result<W> {
+        self.finish_image_data()?;
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+
+    fn flush_idat(&mut self) -> io::Result<()> {
+        let idat = std::mem::take(&mut self.idat);
+        self.write_data(&ChunkType::try_from(*b"IDAT").unwrap(), &idat)?;
+        // keep the allocation for the next chunk
+        self.idat = idat;
+        self.idat.clear();
+        Ok(())
+    }
+}
+
+pub struct ImageData<'a, W: Write>(&'a mut ChunkWriter<W>);
+
+impl<W: Write> Write for ImageData<'_, W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.write_image_data(buf)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.writer.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reader::ChunkReader;
+    use flate2::write::ZlibEncoder;
+    use flate2::Compression;
+    use std::str::FromStr;
+
+    fn chunk(chunk_type: &str, data: &[u8]) -> Chunk {
+        Chunk::new(ChunkType::from_str(chunk_type).unwrap(), data.to_vec())
+    }
+
+    fn read_back(bytes: &[u8]) -> Vec<Chunk> {
+        ChunkReader::new(bytes).unwrap().collect::<Result<_, _>>().unwrap()
+    }
+
+    #[test]
+    fn test_writes_chunks_with_crcs() {
+        let chunks = [chunk("IHDR", &[1; 13]), chunk("ruSt", b"hidden"), chunk("IEND", b"")];
+        let mut writer = ChunkWriter::new(Vec::new()).unwrap();
+        for chunk in &chunks {
+            writer.write_chunk(chunk).unwrap();
+        }
+        let bytes = writer.finish().unwrap();
+        let mut expected = SIGNATURE.to_vec();
+        chunks.iter().for_each(|chunk| expected.extend(chunk.as_bytes()));
+        assert_eq!(bytes, expected);
+    }
+
+    #[test]
+    fn test_splits_image_data() {
+        let mut writer = ChunkWriter::new(Vec::new()).unwrap();
+        writer.set_idat_size(4);
+        writer.write_chunk(&chunk("IHDR", &[0; 13])).unwrap();
+        writer.write_image_data(b"abc").unwrap();
+        writer.write_image_data(b"defghij").unwrap();
+        writer.write_chunk(&chunk("IEND", b"")).unwrap();
+        let chunks = read_back(&writer.finish().unwrap());
+        let data: Vec<&[u8]> = chunks.iter().map(|chunk| chunk.data()).collect();
+        assert_eq!(data, vec![&[0; 13][..], b"abcd", b"efgh", b"ij", b""]);
+    }
+
+    #[test]
+    fn test_deflates_into_image_data() {
+        // pseudo random, so it doesn't deflate into a single chunk
+        let pixels: Vec<u8> = (0..100_000u32).map(|i| (i.wrapping_mul(2_654_435_761) >> 24) as u8).collect();
+        let mut writer = ChunkWriter::new(Vec::new()).unwrap();
+        writer.set_idat_size(1000);
+        let mut encoder = ZlibEncoder::new(writer.image_data(), Compression::default());
+        encoder.write_all(&pixels).unwrap();
+        encoder.finish().unwrap();
+        let chunks = read_back(&writer.finish().unwrap());
+        assert!(chunks.len() > 1);
+        assert!(chunks[..chunks.len() - 1].iter().all(|chunk| chunk.data().len() == 1000));
+        let compressed: Vec<u8> = chunks.iter().flat_map(|chunk| chunk.data().to_vec()).collect();
+        assert_eq!(crate::decoder::inflate(&compressed).unwrap(), pixels);
+    }
+}
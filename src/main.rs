@@ -1,57 +1,496 @@
 #![allow(dead_code)]
+#![allow(special_module_name)]
 use std::str;
 
 mod lib;
 use lib::*;
 
-use std::{ffi::OsString, fs::File, io::{Read, Write}, path::PathBuf};
+use std::{ffi::OsString, fs::{File, OpenOptions}, io::{BufReader, BufWriter, Read, Write}, path::PathBuf};
+#[cfg(unix)]
+use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
 
 pub type Error = Box<dyn std::error::Error>;
 pub type Result<T> = std::result::Result<T, Error>;
 
 pub fn get_png_from_file(os_string: &OsString) -> Png {
     let path = PathBuf::from(os_string);
-    println!("{}", path.to_str().unwrap());
-    let mut file = File::open(path).unwrap();
-    let mut contents = Vec::new();
-    file.read_to_end(&mut contents).unwrap();
-    let contents_slice: &[u8] = &contents;
-    let png = Png::try_from(contents_slice).unwrap();
-    png
+    // stderr, so payloads written to stdout stay clean
+    eprintln!("{}", path.to_str().unwrap());
+    let file = File::open(path).unwrap_or_else(|err| exit_with_error(err));
+    Png::from_reader(BufReader::new(file)).unwrap_or_else(|err| exit_with_error(err))
+}
+
+fn exit_with_error(err: impl std::fmt::Display) -> ! {
+    eprintln!("error: {}", err);
+    std::process::exit(1);
+}
+
+fn read_private_key(path: &OsString) -> x25519_dalek::StaticSecret {
+    let contents = std::fs::read_to_string(path).unwrap_or_else(|err| exit_with_error(err));
+    let bytes = hex::decode_array::<32>(&contents).unwrap_or_else(|err| exit_with_error(err));
+    x25519_dalek::StaticSecret::from(bytes)
+}
+
+fn seal_message(message: Vec<u8>, encode_args: &Encode) -> Vec<u8> {
+    // compress first, ciphertext doesn't deflate
+    let message = match encode_args.compress {
+        true => compression::compress(&message, compression::CompressionMethod::Deflate, 9)
+            .unwrap_or_else(|err| exit_with_error(err)),
+        false => message,
+    };
+    if !encode_args.recipients.is_empty() {
+        let private_key = read_private_key(encode_args.private_key_path.as_ref().unwrap());
+        return sealed_box::seal(&message, &private_key, &encode_args.recipients).unwrap_or_else(|err| exit_with_error(err));
+    }
+    match &encode_args.password {
+        Some(password) => crypto::encrypt(&message, password).unwrap_or_else(|err| exit_with_error(err)),
+        None => message,
+    }
+}
+
+fn open_message(data: Vec<u8>, decode_args: &Decode) -> Vec<u8> {
+    let data = if sealed_box::is_sealed(&data) {
+        let path = decode_args.private_key_path.as_ref()
+            .unwrap_or_else(|| exit_with_error("the message is sealed, pass --private-key to read it"));
+        let opened = sealed_box::open(&data, &read_private_key(path)).unwrap_or_else(|err| exit_with_error(err));
+        eprintln!("sealed by: {}", hex::encode(opened.sender.as_bytes()));
+        opened.message
+    } else {
+        match (crypto::is_encrypted(&data), &decode_args.password) {
+            (true, Some(password)) => crypto::decrypt(&data, password).unwrap_or_else(|err| exit_with_error(err)),
+            (true, None) => exit_with_error("the message is encrypted, pass --password to read it"),
+            (false, _) => data,
+        }
+    };
+    match compression::is_compressed(&data) {
+        true => compression::decompress(&data).unwrap_or_else(|err| exit_with_error(err)),
+        false => data,
+    }
+}
+
+fn is_packed(data: &[u8]) -> bool {
+    crypto::is_encrypted(data) || sealed_box::is_sealed(data) || compression::is_compressed(data) || payload::is_file(data)
+}
+
+fn read_payload(source: &PayloadSource) -> Vec<u8> {
+    let file = match source {
+        PayloadSource::Message(message) => return message.as_bytes().to_vec(),
+        PayloadSource::File(path) => {
+            let contents = std::fs::read(path).unwrap_or_else(|err| exit_with_error(err));
+            let name = PathBuf::from(path).file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default();
+            payload::FilePayload::new(&name, contents)
+        },
+        PayloadSource::Stdin => {
+            let mut contents = Vec::new();
+            std::io::stdin().read_to_end(&mut contents).unwrap_or_else(|err| exit_with_error(err));
+            payload::FilePayload::new("", contents)
+        },
+    };
+    file.to_bytes().unwrap_or_else(|err| exit_with_error(err))
+}
+
+fn reveal(data: Vec<u8>, decode_args: &Decode) {
+    let file = match payload::is_file(&data) {
+        true => Some(payload::FilePayload::try_from(data.as_slice()).unwrap_or_else(|err| exit_with_error(err))),
+        false => None,
+    };
+    match (&decode_args.out_path, file) {
+        (Some(out), file) if out == "-" => {
+            let contents = file.map(|file| file.contents).unwrap_or(data);
+            std::io::stdout().write_all(&contents).unwrap_or_else(|err| exit_with_error(err));
+        },
+        (Some(out), file) => {
+            let mut path = PathBuf::from(out);
+            let contents = match file {
+                Some(file) => {
+                    if path.is_dir() {
+                        path.push(file.file_name().unwrap_or_else(|err| exit_with_error(err)));
+                    }
+                    file.contents
+                },
+                None => data,
+            };
+            std::fs::write(&path, &contents).unwrap_or_else(|err| exit_with_error(err));
+            println!("wrote {} bytes to {}", contents.len(), path.display());
+        },
+        (None, Some(file)) => {
+            println!("hidden file: {} ({} bytes), pass --out to extract it", file.name, file.contents.len());
+        },
+        (None, None) => println!("hidden message is: \n {}", String::from_utf8_lossy(&data)),
+    }
 }
 
 pub fn execute_encoding(encode_args: Encode){
     let mut png = get_png_from_file(&encode_args.input_file_path);
-    let chunk_containg_msg = Chunk::new(encode_args.chunk_type, encode_args.message.into_bytes());
-    png.append_chunk(chunk_containg_msg);
-    if let Some(op_path) = encode_args.output_file_path {
-        let mut file2 = File::create(PathBuf::from(op_path)).unwrap();
-        file2.write_all(&png.as_bytes()).unwrap();
-    }else {
-        let mut file = File::create(PathBuf::from(encode_args.input_file_path)).unwrap();	
-        file.write_all(&png.as_bytes()).unwrap();    
+    let payload = seal_message(read_payload(&encode_args.payload), &encode_args);
+    match encode_args.method {
+        HidingMethod::Chunk(chunk_type) => {
+            let pieces = match encode_args.split_size {
+                Some(size) => split::split(&payload, size).unwrap_or_else(|err| exit_with_error(err)),
+                None if payload.len() > i32::MAX as usize => split::split(&payload, split::MAX_PIECE_LEN).unwrap(),
+                None => vec![payload],
+            };
+            for piece in pieces {
+                let chunk_containg_msg = Chunk::new(chunk_type, piece);
+                png.insert_chunk(chunk_containg_msg, ChunkPlacement::BeforeIend).unwrap();
+            }
+        },
+        HidingMethod::Lsb(lsb_options) => {
+            let mut image = png.decode_image().unwrap_or_else(|err| exit_with_error(format!("can't decode the image: {:?}", err)));
+            lsb::embed(&mut image, &payload, &lsb_options).unwrap_or_else(|err| exit_with_error(err));
+            let interlaced = png.ihdr().is_ok_and(|ihdr| ihdr.interlace_method == ihdr::InterlaceMethod::Adam7);
+            png.replace_image_data(&image, &EncodeOptions::default())
+                .unwrap_or_else(|err| exit_with_error(format!("can't encode the image: {:?}", err)));
+            if interlaced {
+                eprintln!("note: the image was Adam7 interlaced, the output is not");
+            }
+        },
     }
+    let op_path = encode_args.output_file_path.unwrap_or(encode_args.input_file_path);
+    let file = File::create(PathBuf::from(op_path)).unwrap_or_else(|err| exit_with_error(err));
+    png.write_to(BufWriter::new(file)).unwrap_or_else(|err| exit_with_error(err));
 }
 
 pub fn execute_decoding(decode_args: Decode){
     let png = get_png_from_file(&decode_args.input_file_path);
-    if let Some(chunk) =  png.chunk_by_type(str::from_utf8(&decode_args.chunk_type.bytes()).unwrap()) {
-        println!("hidden message is: \n {}", chunk);
-    }else {
-        println!("no message found associated with this chunk type");
+    match &decode_args.method {
+        HidingMethod::Chunk(chunk_type) => {
+            let chunks = png.chunks_by_type(str::from_utf8(&chunk_type.bytes()).unwrap());
+            let data: Vec<&[u8]> = chunks.iter().map(|chunk| chunk.data()).collect();
+            // pieces of a split payload count as one message
+            let groups = split::group_pieces(&data);
+            let selected: Vec<&Vec<usize>> = match decode_args.selection {
+                Selection::First => groups.iter().take(1).collect(),
+                Selection::All => groups.iter().collect(),
+                Selection::Index(n) => groups.iter().filter(|group| group.contains(&n)).collect(),
+            };
+            if selected.is_empty() {
+                println!("no message found associated with this chunk type");
+            }
+            for group in selected {
+                let chunk = chunks[group[0]];
+                if decode_args.selection == Selection::All {
+                    println!("[chunk {}]", group[0]);
+                }
+                if split::is_piece(chunk.data()) {
+                    let pieces: Vec<&[u8]> = group.iter().map(|&index| data[index]).collect();
+                    let payload = split::join(&pieces).unwrap_or_else(|err| exit_with_error(err));
+                    reveal(open_message(payload, &decode_args), &decode_args);
+                } else if is_packed(chunk.data()) || decode_args.out_path.is_some() {
+                    reveal(open_message(chunk.data().to_vec(), &decode_args), &decode_args);
+                } else {
+                    println!("hidden message is: \n {}", chunk);
+                }
+            }
+        },
+        HidingMethod::Lsb(lsb_options) => {
+            let image = png.decode_image().unwrap_or_else(|err| exit_with_error(format!("can't decode the image: {:?}", err)));
+            match lsb::extract(&image, lsb_options) {
+                Ok(message) => reveal(open_message(message, &decode_args), &decode_args),
+                Err(lsb::LsbError::InvalidHeader) => println!("no message found in the pixel data"),
+                Err(err) => exit_with_error(err),
+            }
+        },
     }
 
 }
 pub fn execute_removing(remove_args: Remove){
     let mut png = get_png_from_file(&remove_args.input_file_path);
-    let removed_chunk = png.remove_chunk(str::from_utf8(&remove_args.chunk_type.bytes()).unwrap()).unwrap();
-    println!("removed chunk: {}", removed_chunk);
+    let chunk_type = str::from_utf8(&remove_args.chunk_type.bytes()).unwrap().to_string();
+    // pieces of a split payload go together, as they do when decoding
+    let group = {
+        let data: Vec<&[u8]> = png.chunks_by_type(&chunk_type).iter().map(|chunk| chunk.data()).collect();
+        let groups = split::group_pieces(&data);
+        match remove_args.selection {
+            Selection::First => groups.into_iter().next(),
+            Selection::Index(n) => groups.into_iter().find(|group| group.contains(&n)),
+            Selection::All => None,
+        }
+    };
+    let removed = match (remove_args.selection, group) {
+        (Selection::All, _) => png.remove_chunks_by_type(&chunk_type),
+        // from the back, so the earlier indices still hold
+        (_, Some(group)) => group.iter().rev().map(|&n| png.remove_nth_chunk(&chunk_type, n))
+            .collect::<std::result::Result<Vec<Chunk>, String>>()
+            .map(|chunks| chunks.into_iter().rev().collect()),
+        (Selection::Index(n), None) => png.remove_nth_chunk(&chunk_type, n).map(|chunk| vec![chunk]),
+        (Selection::First, None) => png.remove_chunk(&chunk_type).map(|chunk| vec![chunk]),
+    };
+    for removed_chunk in removed.unwrap_or_else(|err| exit_with_error(err)) {
+        println!("removed chunk: {}", removed_chunk);
+    }
+    if let Some(op_path) = remove_args.output_file_path {
+        let mut file = File::create(PathBuf::from(op_path)).unwrap();
+        file.write_all(&png.as_bytes()).unwrap();
+    }
 }
 pub fn execute_printing(print_args: Print){
+    if print_args.headers_only {
+        let file = File::open(&print_args.input_file_path).unwrap_or_else(|err| exit_with_error(err));
+        let reader = reader::ChunkReader::new(BufReader::new(file)).unwrap_or_else(|err| exit_with_error(err));
+        for header in reader.headers() {
+            let header = header.unwrap_or_else(|err| exit_with_error(err));
+            println!("{:>12}  {}  {} bytes", header.offset, header.chunk_type, header.length);
+        }
+        return;
+    }
     let png = get_png_from_file(&print_args.input_file_path);
     println!("[PNG]: \n {}", png);
 }
 
+pub fn execute_keygen(keygen_args: Keygen){
+    let (private, public) = match keygen_args.signing {
+        true => {
+            let key = signature::generate_signing_key();
+            (key.to_bytes(), key.verifying_key().to_bytes())
+        },
+        false => {
+            let secret = sealed_box::generate_secret();
+            (secret.to_bytes(), x25519_dalek::PublicKey::from(&secret).to_bytes())
+        },
+    };
+    // private keys are only readable by their owner, and an existing key is
+    // never replaced unless asked to
+    let mut options = OpenOptions::new();
+    options.write(true);
+    match keygen_args.force {
+        true => options.create(true).truncate(true),
+        false => options.create_new(true),
+    };
+    #[cfg(unix)]
+    options.mode(0o600);
+    let mut file = options.open(PathBuf::from(&keygen_args.output_file_path)).unwrap_or_else(|err| exit_with_error(err));
+    #[cfg(unix)]
+    file.set_permissions(std::fs::Permissions::from_mode(0o600)).unwrap_or_else(|err| exit_with_error(err));
+    writeln!(file, "{}", hex::encode(&private)).unwrap_or_else(|err| exit_with_error(err));
+    println!("public key: {}", hex::encode(&public));
+}
+
+pub fn execute_signing(sign_args: Sign){
+    let mut png = get_png_from_file(&sign_args.input_file_path);
+    let contents = std::fs::read_to_string(&sign_args.private_key_path).unwrap_or_else(|err| exit_with_error(err));
+    let key = ed25519_dalek::SigningKey::from_bytes(&hex::decode_array::<32>(&contents).unwrap_or_else(|err| exit_with_error(err)));
+    let covered = signature::sign(&mut png, &key, &sign_args.include).unwrap_or_else(|err| exit_with_error(err));
+    for chunk in covered {
+        println!("signed chunk {}: {}", chunk.index, chunk.chunk_type);
+    }
+    let op_path = sign_args.output_file_path.unwrap_or(sign_args.input_file_path);
+    let mut file = File::create(PathBuf::from(op_path)).unwrap();
+    file.write_all(&png.as_bytes()).unwrap();
+}
+
+pub fn execute_verifying(verify_args: Verify){
+    let png = get_png_from_file(&verify_args.input_file_path);
+    let verification = signature::verify(&png).unwrap_or_else(|err| exit_with_error(err));
+    if verify_args.public_key.is_some_and(|key| key != verification.signer) {
+        exit_with_error(format!("valid signature, but from another key: {}", hex::encode(verification.signer.as_bytes())));
+    }
+    println!("valid signature from: {}", hex::encode(verification.signer.as_bytes()));
+    for chunk in verification.covered {
+        println!("covered chunk {}: {}", chunk.index, chunk.chunk_type);
+    }
+    // the signer key is stored next to the signature, so without a trusted
+    // key all this shows is that the file is consistent with itself
+    if verify_args.public_key.is_none() {
+        println!("UNPINNED: signer not verified, pass --public-key to check who signed it");
+        std::process::exit(1);
+    }
+}
+
+pub fn execute_text(text_args: Text){
+    let mut png = get_png_from_file(&text_args.input_file_path);
+    match text_args.action {
+        TextAction::List => {
+            for entry in text::text_chunks(&png) {
+                match entry {
+                    Ok(text_chunk) => println!("{} {}", text_chunk.chunk_type(), text_chunk),
+                    Err(err) => println!("malformed text chunk: {}", err),
+                }
+            }
+            return;
+        },
+        TextAction::Set { keyword, value, compress, language_tag, translated_keyword } => {
+            let international = language_tag.is_some() || translated_keyword.is_some() || !text::is_latin1(&value);
+            let text_chunk = match (international, compress) {
+                (true, compressed) => text::TextChunk::InternationalText {
+                    keyword,
+                    compressed,
+                    language_tag: language_tag.unwrap_or_default(),
+                    translated_keyword: translated_keyword.unwrap_or_default(),
+                    text: value,
+                },
+                (false, true) => text::TextChunk::CompressedText { keyword, text: value },
+                (false, false) => text::TextChunk::Text { keyword, text: value },
+            };
+            text::set_text(&mut png, &text_chunk).unwrap_or_else(|err| exit_with_error(err));
+            println!("set {} {}", text_chunk.chunk_type(), text_chunk);
+        },
+        TextAction::Delete { keyword } => {
+            let removed = text::delete_text(&mut png, &keyword).unwrap_or_else(|err| exit_with_error(err));
+            println!("deleted {} text chunk(s)", removed);
+        },
+    }
+    let op_path = text_args.output_file_path.unwrap_or(text_args.input_file_path);
+    let mut file = File::create(PathBuf::from(op_path)).unwrap();
+    file.write_all(&png.as_bytes()).unwrap();
+}
+
+fn read_exif(png: &Png) -> exif::Exif {
+    match exif::Exif::from_png(png) {
+        Ok(Some(exif)) => exif,
+        Ok(None) => exit_with_error("the PNG has no eXIf chunk"),
+        Err(err) => exit_with_error(err),
+    }
+}
+
+pub fn execute_exif(exif_args: ShowExif){
+    let mut png = get_png_from_file(&exif_args.input_file_path);
+    let mut exif = read_exif(&png);
+    if exif_args.orientation.is_none() && exif_args.model.is_none() {
+        let or_none = |value: Option<String>| value.unwrap_or_else(|| "-".to_string());
+        println!("make: {}", or_none(exif.make().map(String::from)));
+        println!("model: {}", or_none(exif.model().map(String::from)));
+        println!("orientation: {}", or_none(exif.orientation().map(|o| format!("{:?}", o))));
+        println!("date time: {}", or_none(exif.date_time().map(|d| d.to_string())));
+        println!("date time original: {}", or_none(exif.date_time_original().map(|d| d.to_string())));
+        println!("date time digitized: {}", or_none(exif.date_time_digitized().map(|d| d.to_string())));
+        let gps = exif.gps().map(|gps| match gps.altitude {
+            Some(altitude) => format!("{:.6}, {:.6} at {}m", gps.latitude, gps.longitude, altitude),
+            None => format!("{:.6}, {:.6}", gps.latitude, gps.longitude),
+        });
+        println!("gps: {}", or_none(gps));
+        return;
+    }
+    if let Some(orientation) = exif_args.orientation {
+        exif.set_orientation(orientation);
+    }
+    if let Some(model) = &exif_args.model {
+        exif.set_model(model);
+    }
+    exif.write_to_png(&mut png).unwrap_or_else(|err| exit_with_error(err));
+    let op_path = exif_args.output_file_path.unwrap_or(exif_args.input_file_path);
+    let mut file = File::create(PathBuf::from(op_path)).unwrap();
+    file.write_all(&png.as_bytes()).unwrap();
+}
+
+pub fn execute_strip_gps(strip_args: StripGps){
+    let mut png = get_png_from_file(&strip_args.input_file_path);
+    let mut exif = read_exif(&png);
+    if !exif.strip_gps() {
+        println!("no GPS data found");
+        return;
+    }
+    exif.write_to_png(&mut png).unwrap_or_else(|err| exit_with_error(err));
+    let op_path = strip_args.output_file_path.unwrap_or(strip_args.input_file_path);
+    let mut file = File::create(PathBuf::from(op_path)).unwrap();
+    file.write_all(&png.as_bytes()).unwrap();
+    println!("removed GPS data");
+}
+
+pub fn execute_palette(palette_args: PrintPalette){
+    let png = get_png_from_file(&palette_args.input_file_path);
+    let info = palette::PaletteInfo::from_png(&png).unwrap_or_else(|err| exit_with_error(err));
+    match &info.plte {
+        Some(plte) => {
+            println!("palette: {} entries", plte.entries.len());
+            for (index, [red, green, blue]) in plte.entries.iter().enumerate() {
+                let alpha = info.trns.as_ref().map_or(255, |trns| trns.alpha(index));
+                match &info.hist {
+                    Some(hist) => println!("  {:3}: #{:02x}{:02x}{:02x} alpha {:3} frequency {}", index, red, green, blue, alpha, hist.frequencies[index]),
+                    None => println!("  {:3}: #{:02x}{:02x}{:02x} alpha {:3}", index, red, green, blue, alpha),
+                }
+            }
+        },
+        None => println!("the image has no palette"),
+    }
+    match info.bkgd {
+        Some(palette::Bkgd::PaletteIndex(index)) => println!("background: palette entry {}", index),
+        Some(palette::Bkgd::Gray(gray)) => println!("background: gray {}", gray),
+        Some(palette::Bkgd::Rgb(red, green, blue)) => println!("background: rgb({}, {}, {})", red, green, blue),
+        None => (),
+    }
+    match &info.trns {
+        Some(palette::Trns::Gray(gray)) => println!("transparent gray: {}", gray),
+        Some(palette::Trns::Rgb(red, green, blue)) => println!("transparent color: rgb({}, {}, {})", red, green, blue),
+        _ => (),
+    }
+    for splt in &info.suggested {
+        println!("suggested palette \"{}\": {} entries at {} bits", splt.name, splt.entries.len(), splt.sample_depth);
+    }
+}
+
+pub fn execute_set_dpi(dpi_args: SetDpi){
+    let mut png = get_png_from_file(&dpi_args.input_file_path);
+    let phys = physical::Phys::from_dpi(dpi_args.dpi, dpi_args.dpi).unwrap_or_else(|err| exit_with_error(err));
+    physical::set_chunk(&mut png, phys.to_chunk(), ChunkPlacement::AfterIhdr).unwrap_or_else(|err| exit_with_error(err));
+    let op_path = dpi_args.output_file_path.unwrap_or(dpi_args.input_file_path);
+    let mut file = File::create(PathBuf::from(op_path)).unwrap();
+    file.write_all(&png.as_bytes()).unwrap();
+    // pHYs stores whole pixels per meter, so report what was actually written
+    let (dpi_x, _) = phys.dpi().unwrap();
+    println!("resolution set to {:.2} DPI ({} pixels per meter)", dpi_x, phys.pixels_per_unit_x);
+}
+
+pub fn execute_touch(touch_args: Touch){
+    let mut png = get_png_from_file(&touch_args.input_file_path);
+    let time = physical::Time::now();
+    physical::set_chunk(&mut png, time.to_chunk(), ChunkPlacement::BeforeIend).unwrap_or_else(|err| exit_with_error(err));
+    let op_path = touch_args.output_file_path.unwrap_or(touch_args.input_file_path);
+    let mut file = File::create(PathBuf::from(op_path)).unwrap();
+    file.write_all(&png.as_bytes()).unwrap();
+    println!("modification time set to {}", time);
+}
+
+pub fn execute_frames(frames_args: Frames){
+    let png = get_png_from_file(&frames_args.input_file_path);
+    let animation = png.animation().unwrap_or_else(|err| exit_with_error(err));
+    match frames_args.action {
+        FramesAction::List => {
+            let plays = match animation.actl.num_plays {
+                0 => String::from("loops forever"),
+                plays => format!("plays {} time(s)", plays),
+            };
+            println!("{} frames, {:.2}s, {}", animation.frames.len(), animation.duration(), plays);
+            for (index, frame) in animation.frames.iter().enumerate() {
+                let control = frame.control;
+                println!(
+                    "frame {}: {}x{} at ({}, {}), delay {:.3}s, dispose {}, blend {}{}",
+                    index, control.width, control.height, control.x_offset, control.y_offset,
+                    control.delay(), control.dispose_op, control.blend_op,
+                    if frame.is_default_image { " (default image)" } else { "" },
+                );
+            }
+        },
+        FramesAction::Extract { out_dir } => {
+            let canvases = animation.composite(&png).unwrap_or_else(|err| exit_with_error(err));
+            let out_dir = PathBuf::from(out_dir);
+            std::fs::create_dir_all(&out_dir).unwrap_or_else(|err| exit_with_error(err));
+            // each frame is written out before the next one is composited
+            let mut extracted = 0;
+            for (index, canvas) in canvases.enumerate() {
+                let canvas = canvas.unwrap_or_else(|err| exit_with_error(err));
+                let frame_png = Png::from_image(&canvas, &EncodeOptions::default()).unwrap();
+                let mut file = File::create(out_dir.join(format!("frame_{:03}.png", index))).unwrap();
+                file.write_all(&frame_png.as_bytes()).unwrap();
+                extracted += 1;
+            }
+            println!("extracted {} frames to {}", extracted, out_dir.display());
+        },
+    }
+}
+
+pub fn execute_build_animation(build_args: BuildAnimation){
+    let mut builder = apng::AnimationBuilder::new().num_plays(build_args.num_plays).crop_to_changes(build_args.crop);
+    for path in &build_args.frame_file_paths {
+        let png = get_png_from_file(path);
+        builder = builder.png_frame(&png, build_args.delay_ms, 1000).unwrap_or_else(|err| exit_with_error(err));
+    }
+    let png = builder.build().unwrap_or_else(|err| exit_with_error(err));
+    let mut file = File::create(PathBuf::from(build_args.output_file_path)).unwrap();
+    file.write_all(&png.as_bytes()).unwrap();
+    println!("built an animation of {} frames", build_args.frame_file_paths.len());
+}
+
 fn main() -> Result<()> {
     match commands::parse_command()
     {
@@ -59,6 +498,17 @@ fn main() -> Result<()> {
         Decd(decode) => execute_decoding(decode),
         Remv(remove) => execute_removing(remove),
         Prnt(print_) => execute_printing(print_),
+        Kgen(keygen) => execute_keygen(keygen),
+        Sgnt(sign) => execute_signing(sign),
+        Vrfy(verify) => execute_verifying(verify),
+        Txts(text) => execute_text(text),
+        Exfs(exif) => execute_exif(exif),
+        Sgps(strip) => execute_strip_gps(strip),
+        Pltt(palette) => execute_palette(palette),
+        Sdpi(set_dpi) => execute_set_dpi(set_dpi),
+        Tuch(touch) => execute_touch(touch),
+        Frms(frames) => execute_frames(frames),
+        Anim(build) => execute_build_animation(build),
     }
     Ok(())
 }